@@ -1,4 +1,5 @@
 use frontend::input::AxisValue;
+use backend::world::species::Metric;
 use core::clock::{SpeedFactor, SecondsValue};
 use std::f32::consts;
 
@@ -31,9 +32,26 @@ pub const INITIAL_SPAWN_RADIUS_INCREMENT: f32 = 0.5;
 pub const MATURITY_MINION_DEFAULT: f32 = 0.5;
 pub const MATURITY_DEFAULT: f32 = 1.0;
 pub const WORLD_RADIUS: f32 = 80.;
+/// the default arena has this many feeders on a circle, when the configuration defines none
+pub const EMITTER_COUNT: usize = 7;
 pub const EMITTER_DISTANCE: f32 = 30.;
 pub const EMITTER_PERIOD: SecondsValue = 0.4;
 pub const EMITTER_SPREAD_ANGLE: f32 = consts::PI / 12.;
+/// speed of the emitted resources
+pub const EMITTER_VELOCITY: f32 = 5.;
+/// how close to a feeder a click must be to remove it
+pub const EMITTER_PICK_RADIUS: f32 = 5.;
+/// arena settings, relative to the resources folder
+pub const WORLD_CONFIG_FILE: &'static str = "config/world.json";
+/// speed of the whirl around each feeder, at its radius; the water is still unless the world config stirs it
+pub const FLOW_FEEDER_VORTEX_STRENGTH: f32 = 0.;
+pub const FLOW_FEEDER_VORTEX_RADIUS: f32 = 12.;
+/// speed and size of the turbulent eddies, and how fast they change
+pub const FLOW_TURBULENCE: f32 = 0.;
+pub const FLOW_TURBULENCE_SCALE: f32 = 20.;
+pub const FLOW_TURBULENCE_RATE: f32 = 0.05;
+/// drag of the water on bodies, per unit of mass and of relative speed
+pub const FLOW_COUPLING: f32 = 0.5;
 pub const BULLET_SPEED_SCALE: f32 = 100.;
 pub const BULLET_FIRE_RATE_SCALE: SecondsValue = 0.5;
 pub const BULLET_FULL_CHARGE: SecondsValue = 1.0;
@@ -43,6 +61,7 @@ pub const DENSITY_RESOURCE: f32 = DENSITY_DEFAULT;
 pub const DENSITY_PLAYER: f32 = 1.0;
 pub const DENSITY_MINION: f32 = 0.2;
 pub const DENSITY_SPORE: f32 = 0.5;
+pub const DENSITY_ENEMY: f32 = 0.3;
 pub const RESTITUTION_DEFAULT: f32 = 0.6;
 pub const RESTITUTION_PLAYER: f32 = 0.1;
 pub const FRICTION_DEFAULT: f32 = 0.7;
@@ -68,6 +87,66 @@ pub const DEFAULT_RESOURCE_GENE_POOL: &'static [&'static str] = &[
 
 pub const DUMP_FILE_PATTERN_CSV: &'static str = "resources/%Y%m%d_%H%M%S.csv";
 pub const DUMP_FILE_PATTERN_JSON: &'static str = "resources/%Y%m%d_%H%M%S.json";
+pub const DUMP_FILE_PATTERN_BINARY: &'static str = "resources/%Y%m%d_%H%M%S.oids";
+pub const DUMP_LINEAGE_DIR: &'static str = "resources/lineage";
+pub const DUMP_FILE_PATTERN_NEWICK: &'static str = "resources/lineage/%Y%m%d_%H%M%S.nwk";
+pub const DUMP_FILE_PATTERN_LINEAGE: &'static str = "resources/lineage/%Y%m%d_%H%M%S.json";
+/// size at which the event journal moves on to a new file
+pub const JOURNAL_FILE_MAX_BYTES: u64 = 16 * 1024 * 1024;
+/// safety limits on the body plans grown from the genome
+pub const MAX_BODY_SEGMENTS: usize = 24;
+pub const MAX_BELLY_SEGMENTS: usize = 6;
+pub const MAX_LIMB_DEPTH: usize = 2;
+pub const ENEMY_GENE_POOL_SIZE: usize = 8;
+pub const ENEMY_SPAWN_RADIUS_RATIO: f32 = 0.6;
+/// energy each predator mouth in contact takes from a minion per second, 2 a frame at 60 frames per second
+pub const ENEMY_BITE_RATE: f32 = 120.0;
+/// fraction of the bitten energy the predators get to keep, shared between the mouths which bit
+pub const ENEMY_BITE_EFFICIENCY: f32 = 0.8;
+pub const BRAIN_MIN_HIDDEN_UNITS: usize = 2;
+pub const BRAIN_MAX_HIDDEN_UNITS: usize = 8;
+pub const BRAIN_MAX_LEARNING_RATE: f32 = 0.01;
+/// length of a genome: the largest brain reads 2,880 bits of weights, and the largest body plan
+/// about 1,400 bits more, so that no gene is ever read twice
+pub const GENOME_BYTES: usize = 576;
+pub const MAX_VISION_RAYS: usize = 5;
+pub const VISION_MIN_FOV: f32 = consts::PI * 0.1;
+pub const VISION_MAX_FOV: f32 = consts::PI * 1.5;
+/// vision rays reach this many times the radius of the sensor
+pub const VISION_RANGE_SCALE: f32 = 10.;
+/// on a torus, a vision ray is cast again from the opposite side at most this many times in all
+pub const MAX_RAY_LEGS: usize = 3;
+/// speeds and fasting times above these saturate the sensors
+pub const SENSOR_SPEED_RANGE: f32 = 20.;
+pub const SENSOR_FASTING_RANGE: f32 = 30.;
+/// number of segments of the boundary of a circular world
+pub const BOUNDARY_CIRCLE_VERTICES: usize = 64;
+/// size of the cells of the spatial index, roughly the size of a grown minion
+pub const SPATIAL_CELL_SIZE: f32 = 5.;
+/// number of chemical channels, each gland secretes into one of them
+pub const PHEROMONE_CHANNELS: usize = 2;
+pub const PHEROMONE_CELL_SIZE: f32 = 4.;
+/// how fast pheromones spread to the neighbouring cells, per second
+pub const PHEROMONE_DIFFUSION: f32 = 0.2;
+/// fraction of the pheromones which evaporates each second
+pub const PHEROMONE_EVAPORATION: f32 = 0.1;
+/// the most a gland can secrete in a second
+pub const GLAND_MAX_RATE: f32 = 1.;
+/// gradients above this saturate the sensors
+pub const SENSOR_PHEROMONE_RANGE: f32 = 0.5;
+/// concentration below which the overlay does not draw a cell
+pub const PHEROMONE_OVERLAY_THRESHOLD: f32 = 0.01;
+/// unless set in the `species` section of the world configuration
+pub const SPECIES_METRIC: Metric = Metric::Hamming;
+/// fraction of differing bits for Metric::Hamming, normalized RMS distance for Metric::Phenotype
+pub const SPECIES_DISTANCE_THRESHOLD: f32 = 0.12;
+pub const SPECIES_CLASSIFY_PERIOD: SecondsValue = 5.;
+/// simulated seconds between two metrics records, unless given on the command line
+pub const METRICS_DEFAULT_PERIOD: SecondsValue = 10.;
+/// genetic diversity is measured on at most this many minions, as it compares every pair
+pub const METRICS_DIVERSITY_SAMPLE: usize = 200;
+/// width in pixels of exported SVG pictures, the height follows from the scene
+pub const SVG_WIDTH: u32 = 1024;
 
 pub const AMBIENT_LIGHTS: &'static [[f32; 4]] = &[
 	[1.0, 1.0, 1.0, 1.0],
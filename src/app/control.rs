@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{BufRead, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use serde_json;
+use app::Event;
+use app::SimulationUpdate;
+use backend::world::species::SpeciesId;
+use core::clock::{SecondsValue, SpeedFactor};
+use core::rng::SimulationRng;
+use core::geometry::Position;
+
+/// What the control endpoint reports about a headless run
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Status {
+	/// simulation steps so far
+	pub count: usize,
+	/// simulated seconds
+	pub elapsed: SecondsValue,
+	/// length of the last step
+	pub dt: SecondsValue,
+	pub population: usize,
+	pub extinctions: usize,
+	/// population of each living species, by species id
+	pub species: Vec<(SpeciesId, usize)>,
+	pub paused: bool,
+	pub speed_factor: SpeedFactor,
+	/// whether the run keeps to the speed factor, rather than going flat out
+	pub throttled: bool,
+	/// the last saved snapshot, which a restart goes back to
+	pub last_saved: Option<String>,
+}
+
+impl Status {
+	pub fn new(update: &SimulationUpdate, paused: bool, speed_factor: SpeedFactor, throttled: bool, last_saved: Option<&str>) -> Self {
+		Status {
+			count: update.count,
+			elapsed: update.elapsed.get(),
+			dt: update.dt.get(),
+			population: update.population,
+			extinctions: update.extinctions,
+			species: update.species.clone(),
+			paused,
+			speed_factor,
+			throttled,
+			last_saved: last_saved.map(|s| s.to_owned()),
+		}
+	}
+}
+
+/// A small HTTP server on a localhost port, answering from its own thread.
+/// Every request must carry the token of the server as `?token=`, and requests from web pages,
+/// which browsers mark with an `Origin` header, are refused, so that only local tools can drive the run.
+/// `GET /status` returns the last published `Status` as JSON, and
+/// `POST` requests queue the matching `Event` for the main loop:
+///
+/// - `/pause` pauses or resumes the simulation
+/// - `/speed/next` and `/speed/prev` cycle the speed factor
+/// - `/speed/max` goes flat out again, until the speed factor is changed
+/// - `/save/world` and `/save/pool` save a world snapshot or the gene pool
+/// - `/restart` goes back to the last saved snapshot
+/// - `/minion?x=X&y=Y` spawns a minion at the given position
+/// - `/quit` stops the run
+pub struct Server {
+	address: SocketAddr,
+	token: String,
+	status: Arc<Mutex<Status>>,
+	commands: Receiver<Event>,
+}
+
+impl Server {
+	pub fn bind(port: u16) -> io::Result<Self> {
+		let listener = TcpListener::bind(("127.0.0.1", port))?;
+		let address = listener.local_addr()?;
+		let status = Arc::new(Mutex::new(Status::default()));
+		let (sender, commands) = channel();
+		let token = format!("{:016x}{:016x}", SimulationRng::random_seed(), SimulationRng::random_seed());
+		let shared_status = status.clone();
+		let shared_token = token.clone();
+		thread::Builder::new().name("ControlServer".to_string()).spawn(move || {
+			for stream in listener.incoming() {
+				let result = stream.and_then(|mut stream| serve(&mut stream, &shared_token, &shared_status, &sender));
+				if let Err(e) = result {
+					warn!("Control request failed: {}", e);
+				}
+			}
+		})?;
+		Ok(Server { address, token, status, commands })
+	}
+
+	pub fn address(&self) -> SocketAddr {
+		self.address
+	}
+
+	/// The secret every request has to carry, made up anew for each run
+	pub fn token(&self) -> &str {
+		&self.token
+	}
+
+	pub fn publish(&self, status: Status) {
+		*self.status.lock().unwrap() = status;
+	}
+
+	/// The commands received since the last call, in order
+	pub fn commands(&self) -> Vec<Event> {
+		let mut acc = Vec::new();
+		while let Ok(event) = self.commands.try_recv() {
+			acc.push(event);
+		}
+		acc
+	}
+}
+
+fn query(target: &str) -> (&str, HashMap<&str, &str>) {
+	let mut parts = target.splitn(2, '?');
+	let path = parts.next().unwrap_or("");
+	let params = parts.next().unwrap_or("")
+		.split('&')
+		.filter_map(|pair| {
+			let mut kv = pair.splitn(2, '=');
+			match (kv.next(), kv.next()) {
+				(Some(k), Some(v)) => Some((k, v)),
+				_ => None,
+			}
+		})
+		.collect();
+	(path, params)
+}
+
+fn position(params: &HashMap<&str, &str>) -> Option<Position> {
+	let x = params.get("x").and_then(|x| x.parse::<f32>().ok());
+	let y = params.get("y").and_then(|y| y.parse::<f32>().ok());
+	match (x, y) {
+		(Some(x), Some(y)) => Some(Position::new(x, y)),
+		_ => None,
+	}
+}
+
+/// The event a command stands for, or the HTTP status and reason to reply with
+fn command(path: &str, params: &HashMap<&str, &str>) -> Result<Event, (u16, &'static str)> {
+	match path {
+		"/pause" => Ok(Event::TogglePause),
+		"/speed/next" => Ok(Event::NextSpeedFactor),
+		"/speed/prev" => Ok(Event::PrevSpeedFactor),
+		"/speed/max" => Ok(Event::Unthrottle),
+		"/save/world" => Ok(Event::SaveWorldToFile),
+		"/save/pool" => Ok(Event::SaveGenePoolToFile),
+		"/restart" => Ok(Event::RestartFromCheckpoint),
+		"/minion" => position(params).map(Event::NewMinion).ok_or((400, "Bad Request")),
+		"/quit" => Ok(Event::AppQuit),
+		_ => Err((404, "Not Found")),
+	}
+}
+
+fn respond(stream: &mut TcpStream, code: u16, reason: &str, body: &str) -> io::Result<()> {
+	write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		   code, reason, body.len(), body)?;
+	stream.flush()
+}
+
+fn serve(stream: &mut TcpStream, token: &str, status: &Mutex<Status>, sender: &Sender<Event>) -> io::Result<()> {
+	const MAX_HEADER_LINES: usize = 100;
+	stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+	let mut reader = io::BufReader::new(stream.try_clone()?);
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line)?;
+	// headers are read so that the client sees a clean close, only the origin is looked at
+	let mut from_web_page = false;
+	for _ in 0..MAX_HEADER_LINES {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+			break;
+		}
+		if line.to_ascii_lowercase().starts_with("origin:") {
+			from_web_page = true;
+		}
+	}
+	let mut parts = request_line.split_whitespace();
+	let (method, target) = match (parts.next(), parts.next()) {
+		(Some(method), Some(target)) => (method, target),
+		_ => return respond(stream, 400, "Bad Request", "{\"error\":\"malformed request\"}"),
+	};
+	let (path, params) = query(target);
+	if from_web_page {
+		return respond(stream, 403, "Forbidden", "{\"error\":\"requests from web pages are not accepted\"}");
+	}
+	if params.get("token") != Some(&token) {
+		return respond(stream, 403, "Forbidden", "{\"error\":\"missing or wrong token\"}");
+	}
+	match (method, path) {
+		("GET", "/status") => {
+			let body = serde_json::to_string(&*status.lock().unwrap())
+				.map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+			respond(stream, 200, "OK", &body)
+		}
+		("POST", _) => match command(path, &params) {
+			Ok(event) => {
+				let body = format!("{{\"queued\":{}}}", serde_json::to_string(&format!("{:?}", event)).unwrap_or_default());
+				sender.send(event).map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e.to_string()))?;
+				respond(stream, 202, "Accepted", &body)
+			}
+			Err((code, reason)) => respond(stream, code, reason, &format!("{{\"error\":\"{}\"}}", reason)),
+		},
+		(_, "/status") => respond(stream, 405, "Method Not Allowed", "{\"error\":\"use GET\"}"),
+		_ => match command(path, &params) {
+			Err((404, reason)) => respond(stream, 404, reason, "{\"error\":\"Not Found\"}"),
+			_ => respond(stream, 405, "Method Not Allowed", "{\"error\":\"use POST\"}"),
+		},
+	}
+}
@@ -59,7 +59,10 @@ impl InputController for DefaultController {
 			F6 -> SaveGenePoolToFile,
 			F7 -> SaveWorldToFile,
 			F8 -> RestartFromCheckpoint,
+			F9 -> SaveLineageToFile,
 			F10 -> ToggleDebug,
+			F11 -> ToggleSpecies,
+			F12 -> TogglePheromones,
 			GamepadStart -> ToggleDebug,
 			Z -> DeselectAll,
 			L -> NextLight,
@@ -137,6 +140,12 @@ impl InputController for DefaultController {
 			} else {
 				VectorDirection::None
 			}));
+		if input_state.key_once(input::Key::F3) {
+			events.push(Event::AddFeeder(mouse_world_pos));
+		}
+		if input_state.key_once(input::Key::F4) {
+			events.push(Event::RemoveFeeder(mouse_world_pos));
+		}
 		if input_state.key_once(input::Key::MouseMiddle) {
 			if input_state.any_ctrl_pressed() {
 				events.push(Event::RandomizeMinion(mouse_world_pos));
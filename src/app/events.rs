@@ -31,12 +31,17 @@ pub enum Event {
 
 	NextSpeedFactor,
 	PrevSpeedFactor,
+	/// a headless run goes flat out again, whatever the speed factor
+	Unthrottle,
 
 	Reload,
 	SaveGenePoolToFile,
+	SaveLineageToFile,
 	SaveWorldToFile,
 	RestartFromCheckpoint,
 	ToggleDebug,
+	ToggleSpecies,
+	TogglePheromones,
 
 	TogglePause,
 	ToggleGui,
@@ -45,6 +50,8 @@ pub enum Event {
 
 	NewMinion(Position),
 	RandomizeMinion(Position),
+	AddFeeder(Position),
+	RemoveFeeder(Position),
 
 	PickMinion(Position),
 	SelectMinion(usize),
@@ -11,18 +11,23 @@ use frontend::gfx_window_glutin;
 use conrod;
 
 use core::resource::filesystem::ResourceLoaderBuilder;
+use backend::world::agent::Plasticity;
+use backend::world::persist;
 use core::math::Directional;
 use core::clock::{seconds, SecondsValue, Hourglass, SystemTimer};
 use ctrlc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use app;
+use app::control;
 use winit::{self, WindowEvent, VirtualKeyCode, KeyboardInput};
 use glutin;
 use glutin::GlContext;
 
-pub fn main_loop(minion_gene_pool: &str, world_file: Option<String>, fullscreen: Option<usize>, width: Option<u32>, height: Option<u32>, audio_device: Option<usize>) {
+pub fn main_loop(minion_gene_pool: &str, world_file: Option<String>, seed: Option<u64>, plasticity: Plasticity, snapshot_format: persist::Format, journal: Option<String>, fullscreen: Option<usize>, width: Option<u32>, height: Option<u32>, audio_device: Option<usize>) {
 	const WIDTH: u32 = 1280;
 	const HEIGHT: u32 = 1024;
 
@@ -64,7 +69,11 @@ pub fn main_loop(minion_gene_pool: &str, world_file: Option<String>, fullscreen:
 	let mapper = app::WinitEventMapper::new();
 
 	// Create a new game and run it.
-	let mut app = app::App::new(w as u32, h as u32, 100.0, &res, minion_gene_pool, world_file);
+	let mut app = app::App::new(w as u32, h as u32, 100.0, &res, minion_gene_pool, world_file, seed, plasticity);
+	app.set_snapshot_format(snapshot_format);
+	if let Some(ref prefix) = journal {
+		app.open_journal(prefix);
+	}
 
 	let mut ui = ui::conrod_ui::Ui::new(&res,
 										&mut factory,
@@ -163,14 +172,22 @@ pub fn main_loop(minion_gene_pool: &str, world_file: Option<String>, fullscreen:
 	};
 }
 
-pub fn main_loop_headless(minion_gene_pool: &str, world_file: Option<String>) {
+pub fn main_loop_headless(minion_gene_pool: &str, world_file: Option<String>, seed: Option<u64>, plasticity: Plasticity, snapshot_format: persist::Format, journal: Option<String>,
+						  metrics: Option<(String, SecondsValue)>, control_port: Option<u16>) {
 	const WIDTH: u32 = 1024;
 	const HEIGHT: u32 = 1024;
 	let res = ResourceLoaderBuilder::new()
 		.add(path::Path::new("resources"))
 		.build();
 
-	let mut app = app::App::new(WIDTH, HEIGHT, 100.0, &res, minion_gene_pool, world_file);
+	let mut app = app::App::new(WIDTH, HEIGHT, 100.0, &res, minion_gene_pool, world_file, seed, plasticity);
+	app.set_snapshot_format(snapshot_format);
+	if let Some(ref prefix) = journal {
+		app.open_journal(prefix);
+	}
+	if let Some((ref path, period)) = metrics {
+		app.record_metrics(path, period);
+	}
 	let mut no_audio = ui::NullAlertPlayer::new();
 	app.init(app::SystemMode::Batch);
 
@@ -181,11 +198,26 @@ pub fn main_loop_headless(minion_gene_pool: &str, world_file: Option<String>) {
 		r.store(false, Ordering::SeqCst);
 	}).expect("Error setting Ctrl-C handler");
 
+	let control = control_port.and_then(|port| match control::Server::bind(port) {
+		Ok(server) => {
+			info!("Control endpoint listening on http://{}, requests need ?token={}", server.address(), server.token());
+			Some(server)
+		}
+		Err(e) => {
+			error!("Unable to open the control endpoint on port {}: {}", port, e);
+			None
+		}
+	});
+
 	let wall_clock = SystemTimer::new();
 	let mut output_hourglass = Hourglass::new(seconds(5.0), &wall_clock);
 	let mut save_hourglass = Hourglass::new(seconds(300.0), &wall_clock);
+	let mut status_hourglass = Hourglass::new(seconds(0.25), &wall_clock);
 
 	const FRAME_SIMULATION_LENGTH: SecondsValue = 1.0 / 60.0;
+	// runs flat out until the speed factor is changed, then keeps to it from the wall and simulation time it was set at
+	let mut pace: Option<(SecondsValue, SecondsValue)> = None;
+	let mut last_update: Option<app::SimulationUpdate> = None;
 	'main: loop {
 		if !app.is_running() {
 			break 'main;
@@ -196,8 +228,40 @@ pub fn main_loop_headless(minion_gene_pool: &str, world_file: Option<String>) {
 			app.save_world_to_file();
 			break 'main;
 		}
+
+		if let Some(ref control) = control {
+			let commands = control.commands();
+			for event in &commands {
+				app.interact(*event);
+				// changing speed, or resuming, keeps the pace from now on, until asked to go flat out again
+				let now = (wall_clock.seconds().get(), last_update.as_ref().map(|u| u.elapsed.get()).unwrap_or(0.));
+				match *event {
+					app::Event::NextSpeedFactor | app::Event::PrevSpeedFactor => pace = Some(now),
+					app::Event::TogglePause => pace = pace.map(|_| now),
+					app::Event::Unthrottle => pace = None,
+					_ => {}
+				}
+			}
+			if let Some(ref update) = last_update {
+				if !commands.is_empty() || status_hourglass.flip_if_expired(&wall_clock) {
+					control.publish(control::Status::new(update, app.is_paused(), app.speed_factor(), pace.is_some(), app.last_saved()));
+				}
+			}
+		}
+
+		if app.is_paused() {
+			thread::sleep(Duration::from_millis(10));
+			continue;
+		}
 // update and measure
 		let simulation_update = app.simulate(seconds(FRAME_SIMULATION_LENGTH));
+		if let Some((wall_start, simulation_start)) = pace {
+			let ahead = (simulation_update.elapsed.get() - simulation_start) / app.speed_factor()
+				- (wall_clock.seconds().get() - wall_start);
+			if ahead > 0. {
+				thread::sleep(Duration::from_millis((ahead * 1000.) as u64));
+			}
+		}
 		if save_hourglass.flip_if_expired(&wall_clock) {
 			app.save_world_to_file();
 		}
@@ -205,13 +269,15 @@ pub fn main_loop_headless(minion_gene_pool: &str, world_file: Option<String>) {
 		app.play_alerts(&mut no_audio);
 		if output_hourglass.flip_if_expired(&wall_clock) {
 			info!(
-				"C: {} E: {:.3} FT: {:.2} P: {} X: {}",
+				"C: {} E: {:.3} FT: {:.2} P: {} X: {} S: {}",
 				simulation_update.count,
 				simulation_update.elapsed,
 				simulation_update.dt,
 				simulation_update.population,
-				simulation_update.extinctions
+				simulation_update.extinctions,
+				simulation_update.species.len()
 			)
 		}
+		last_update = Some(simulation_update);
 	}
 }
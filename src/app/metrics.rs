@@ -0,0 +1,191 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use serde_json;
+use app::constants::*;
+use backend::messagebus::{Inbox, Message, ReceiveDrain};
+use backend::world;
+use backend::world::agent::AgentType;
+use backend::world::journal;
+use backend::world::species;
+use core::clock::{Seconds, SecondsValue};
+
+/// One line of the metrics file, summing up the last period of the simulation
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Sample {
+	/// simulation time at the end of the period
+	pub seconds: SecondsValue,
+	/// simulation steps so far
+	pub frames: usize,
+	/// simulated seconds per wall clock second over the period
+	pub speed: f64,
+	pub minion: usize,
+	pub spore: usize,
+	pub player: usize,
+	pub friendly_bullet: usize,
+	pub enemy: usize,
+	pub enemy_bullet: usize,
+	/// resources on the field
+	pub resource: usize,
+	pub prop: usize,
+	/// creatures hatched during the period
+	pub births: usize,
+	pub deaths_starvation: usize,
+	pub deaths_predation: usize,
+	pub deaths_border: usize,
+	/// of the living minions
+	pub mean_energy: f32,
+	pub mean_age: SecondsValue,
+	/// mean Hamming distance between the genomes of the living minions
+	pub diversity: f32,
+	pub species: usize,
+	pub extinctions: usize,
+}
+
+const COLUMNS: &'static [&'static str] = &[
+	"seconds", "frames", "speed",
+	"minion", "spore", "player", "friendly_bullet", "enemy", "enemy_bullet", "resource", "prop",
+	"births", "deaths_starvation", "deaths_predation", "deaths_border",
+	"mean_energy", "mean_age", "diversity", "species", "extinctions",
+];
+
+impl Sample {
+	fn set_population(&mut self, agent_type: AgentType, n: usize) {
+		match agent_type {
+			AgentType::Minion => self.minion = n,
+			AgentType::Spore => self.spore = n,
+			AgentType::Player => self.player = n,
+			AgentType::FriendlyBullet => self.friendly_bullet = n,
+			AgentType::Enemy => self.enemy = n,
+			AgentType::EnemyBullet => self.enemy_bullet = n,
+			AgentType::Resource => self.resource = n,
+			AgentType::Prop => self.prop = n,
+		}
+	}
+
+	fn count(&mut self, event: &journal::Event) {
+		match *event {
+			journal::Event::Birth { agent_type, .. } if agent_type != AgentType::Spore => self.births += 1,
+			journal::Event::Death { cause: journal::Cause::Starvation, .. } => self.deaths_starvation += 1,
+			journal::Event::Death { cause: journal::Cause::Predation, .. } => self.deaths_predation += 1,
+			journal::Event::Death { cause: journal::Cause::Border, .. } => self.deaths_border += 1,
+			_ => {}
+		}
+	}
+
+	/// Takes the state of the world at the end of the period
+	fn measure(&mut self, world: &world::World) {
+		for agent_type in AgentType::all() {
+			self.set_population(*agent_type, world.agents(*agent_type).len());
+		}
+		let minions = world.agents(AgentType::Minion);
+		if !minions.is_empty() {
+			let n = minions.len();
+			self.mean_energy = minions.iter().map(|(_, agent)| agent.state.energy()).sum::<f32>() / n as f32;
+			self.mean_age = minions.iter().map(|(_, agent)| agent.segments[0].state.age_seconds().get()).sum::<SecondsValue>() / n as SecondsValue;
+		}
+		// a copy of the generator of the world, so that measuring does not change the course of the simulation
+		self.diversity = species::diversity(minions, METRICS_DIVERSITY_SAMPLE, &mut world.rng().clone());
+		self.species = world.species().counts().len();
+		self.extinctions = world.extinctions();
+	}
+
+	fn to_csv(&self) -> String {
+		format!("{:.3},{},{:.3},{},{},{},{},{},{},{},{},{},{},{},{},{:.4},{:.3},{:.4},{},{}",
+				self.seconds, self.frames, self.speed,
+				self.minion, self.spore, self.player, self.friendly_bullet, self.enemy, self.enemy_bullet, self.resource, self.prop,
+				self.births, self.deaths_starvation, self.deaths_predation, self.deaths_border,
+				self.mean_energy, self.mean_age, self.diversity, self.species, self.extinctions)
+	}
+}
+
+/// How the metrics are written, chosen by the extension of the file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	Csv,
+	/// a JSON object per line
+	JsonLines,
+}
+
+impl Format {
+	pub fn of(file_path: &str) -> Self {
+		match Path::new(file_path).extension().and_then(|e| e.to_str()) {
+			Some("csv") => Format::Csv,
+			_ => Format::JsonLines,
+		}
+	}
+}
+
+/// Writes a `Sample` every `period` simulated seconds. Births and deaths are counted
+/// from the journal records on the bus, everything else is measured on the world.
+pub struct Recorder {
+	format: Format,
+	out: io::BufWriter<fs::File>,
+	inbox: Inbox,
+	period: SecondsValue,
+	due: SecondsValue,
+	frames: usize,
+	sample: Sample,
+	/// simulation and wall clock time at the beginning of the period
+	started: (SecondsValue, SecondsValue),
+}
+
+impl Recorder {
+	/// Truncates the file, and starts the first period at the given simulation and wall clock time
+	pub fn create(file_path: &str, period: SecondsValue, inbox: Inbox, now: Seconds, wall: Seconds) -> io::Result<Self> {
+		let format = Format::of(file_path);
+		let mut out = io::BufWriter::new(fs::File::create(file_path)?);
+		if format == Format::Csv {
+			writeln!(out, "{}", COLUMNS.join(","))?;
+			out.flush()?;
+		}
+		Ok(Recorder {
+			format,
+			out,
+			inbox,
+			period,
+			due: now.get() + period,
+			frames: 0,
+			sample: Sample::default(),
+			started: (now.get(), wall.get()),
+		})
+	}
+
+	/// Counts the events of the last frame, and writes a sample if the period is over
+	pub fn update(&mut self, world: &world::World, wall: Seconds) -> io::Result<()> {
+		self.frames += 1;
+		for message in self.inbox.drain() {
+			if let Message::Journal(record) = message {
+				self.sample.count(&record.event);
+			}
+		}
+		let now = world.seconds().get();
+		if now < self.due {
+			return Ok(());
+		}
+		while self.due <= now {
+			self.due += self.period;
+		}
+		let elapsed = wall.get() - self.started.1;
+		self.sample.seconds = now;
+		self.sample.frames = self.frames;
+		self.sample.speed = if elapsed > 0. { (now - self.started.0) / elapsed } else { 0. };
+		self.sample.measure(world);
+		self.write()?;
+		self.sample = Sample::default();
+		self.started = (now, wall.get());
+		Ok(())
+	}
+
+	fn write(&mut self) -> io::Result<()> {
+		match self.format {
+			Format::Csv => writeln!(self.out, "{}", self.sample.to_csv())?,
+			Format::JsonLines => {
+				serde_json::to_writer(&mut self.out, &self.sample).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+				self.out.write_all(b"\n")?;
+			}
+		}
+		self.out.flush()
+	}
+}
@@ -4,7 +4,9 @@ use backend::obj::*;
 use backend::systems;
 use backend::world;
 use backend::world::agent;
+use backend::world::agent::Plasticity;
 use backend::world::segment;
+use backend::world::species::SpeciesId;
 use backend::messagebus::{Inbox, ReceiveDrain, Outbox, PubSub, Whiteboard, Message};
 use cgmath;
 use cgmath::Matrix4;
@@ -16,13 +18,14 @@ use core::math::Directional;
 use core::math::Relative;
 use core::math::Smooth;
 use core::resource::ResourceLoader;
+use core::rng::SimulationRng;
 use core::util::Cycle;
 use core::view::Viewport;
 use core::view::WorldTransform;
 use frontend::input;
 use frontend::ui;
 use std::fs;
-use getopts::Options;
+use getopts::{Matches, Options};
 use num;
 use rayon::prelude::*;
 
@@ -44,9 +47,25 @@ mod winit_event;
 mod controller;
 mod events;
 mod paint;
+mod tools;
+mod metrics;
+mod control;
 
 pub mod constants;
 
+/// The seed given with `-s`, exiting if it is not a number: a run which silently
+/// fell back to a random seed could not be reproduced
+fn seed_option(options: &Matches) -> Option<u64> {
+	match options.opt_str("s").map(|v| v.parse::<u64>()) {
+		None => None,
+		Some(Ok(seed)) => Some(seed),
+		Some(Err(_)) => {
+			eprintln!("Invalid seed, expected a number from 0 to {}", u64::max_value());
+			process::exit(1)
+		}
+	}
+}
+
 pub fn run(args: &[OsString]) {
 	let mut opt = Options::new();
 	opt.optflag("t", "terminal", "Headless mode");
@@ -56,24 +75,101 @@ pub fn run(args: &[OsString]) {
 	opt.optopt("w", "width", "Window width", "1024");
 	opt.optopt("h", "height", "Window height", "1024");
 	opt.optopt("a", "audio_device", "Audio device index (portaudio)", "0");
+	opt.optopt("s", "seed", "Random seed, for reproducible runs", "12345");
+	opt.optopt("l", "learning", "Lifetime learning of brain weights: off, hebbian or reward", "off");
+	opt.optflag("j", "json", "JSON output for tool subcommands");
+	opt.optopt("o", "output", "Output file for the pool and svg subcommands, standard output if none", "pool.csv");
+	opt.optflag("b", "binary", "Save world snapshots in the compressed binary format (.oids)");
+	opt.optopt("m", "metrics", "Write metrics of a headless run to a CSV or JSON Lines file", "resources/metrics.csv");
+	opt.optopt("p", "metrics_period", "Simulated seconds between metrics records", "10");
+	opt.optopt("c", "control", "Serve status and accept commands over HTTP on this localhost port, in headless mode", "8080");
+	opt.optopt("e", "journal", "Append simulation events to a rotating journal with this file prefix", "resources/journal");
 	match opt.parse(args) {
+		Ok(ref options) if options.free.get(1).map(|c| c == "explain").unwrap_or(false) => {
+			if let Err(e) = tools::explain(&options.free[2..], options.opt_present("j")) {
+				eprintln!("explain: {}", e);
+				process::exit(1)
+			}
+		}
+		Ok(ref options) if options.free.get(1).map(|c| c == "pool").unwrap_or(false) => {
+			let seed = seed_option(options);
+			if let Err(e) = tools::pool(&options.free[2..], options.opt_str("o").as_ref().map(|s| s.as_str()), seed) {
+				eprintln!("pool: {}", e);
+				process::exit(1)
+			}
+		}
+		Ok(ref options) if options.free.get(1).map(|c| c == "svg").unwrap_or(false) => {
+			if let Err(e) = tools::svg(&options.free[2..], options.opt_str("o").as_ref().map(|s| s.as_str())) {
+				eprintln!("svg: {}", e);
+				process::exit(1)
+			}
+		}
+		Ok(ref options) if options.free.get(1).map(|c| c == "convert").unwrap_or(false) => {
+			if let Err(e) = tools::convert(&options.free[2..]) {
+				eprintln!("convert: {}", e);
+				process::exit(1)
+			}
+		}
 		Ok(options) => {
 			let pool_file_name = options.free.get(1).map(|n| n.as_str()).unwrap_or(
 				"minion_gene_pool.csv",
 			);
 			let mut world_file = options.opt_str("i");
+			let seed = seed_option(&options);
+			let plasticity = match options.opt_str("l").map(|v| v.parse::<Plasticity>()) {
+				None => Plasticity::default(),
+				Some(Ok(plasticity)) => plasticity,
+				Some(Err(message)) => {
+					eprintln!("{}", message);
+					process::exit(1)
+				}
+			};
+
+			let journal = options.opt_str("e");
+			if !options.opt_present("t") {
+				if let Some(flag) = ["m", "p", "c"].iter().find(|flag| options.opt_present(flag)) {
+					eprintln!("-{} only applies to headless runs, together with -t", flag);
+					process::exit(1)
+				}
+			}
+			let metrics_period = match options.opt_str("p").map(|p| p.parse::<SecondsValue>()) {
+				None => METRICS_DEFAULT_PERIOD,
+				Some(Ok(period)) if period > 0. => period,
+				Some(_) => {
+					eprintln!("Invalid metrics period, expected a positive number of seconds");
+					process::exit(1)
+				}
+			};
+			let metrics = options.opt_str("m").map(|path| (path, metrics_period));
+			let control_port = match options.opt_str("c").map(|p| p.parse::<u16>()) {
+				None => None,
+				Some(Ok(port)) => Some(port),
+				Some(Err(_)) => {
+					eprintln!("Invalid control port, expected a number from 0 to 65535");
+					process::exit(1)
+				}
+			};
+			let snapshot_format = if options.opt_present("b") {
+				world::persist::Format::Binary
+			} else {
+				world::persist::Format::Json
+			};
 
 			// TODO: tidy up
 			if !options.opt_present("n") && world_file.is_none() {
+				// snapshots are named after the time they were taken, whatever their format
 				let mut max_path = "".to_owned();
+				let mut max_stem = "".to_owned();
 				if let Ok(dir) = fs::read_dir("resources") {
 					for entry in dir {
-						let path_name = entry
-							.unwrap().path()
-							.to_str()
-							.unwrap_or("")
-							.to_owned();
-						if path_name.ends_with(".json") && path_name > max_path {
+						let path = match entry {
+							Ok(entry) => entry.path(),
+							Err(_) => continue,
+						};
+						let path_name = path.to_str().unwrap_or("").to_owned();
+						let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("").to_owned();
+						if world::persist::Format::is_snapshot(&path_name) && stem > max_stem {
+							max_stem = stem;
 							max_path = path_name;
 						}
 					}
@@ -85,14 +181,14 @@ pub fn run(args: &[OsString]) {
 			}
 
 			if options.opt_present("t") {
-				main::main_loop_headless(pool_file_name, world_file);
+				main::main_loop_headless(pool_file_name, world_file, seed, plasticity, snapshot_format, journal, metrics, control_port);
 			} else {
 				let fullscreen = options.opt_default("f", "0").and_then(|v| v.parse::<usize>().ok());
 				let width = options.opt_default("w", "1024").and_then(|v| v.parse::<u32>().ok());
 				let height = options.opt_default("h", "1024").and_then(|v| v.parse::<u32>().ok());
 				let audio_device = options.opt_default("a", "0").and_then(|v| v.parse::<usize>().ok());
 
-				main::main_loop(pool_file_name, world_file, fullscreen, width, height, audio_device);
+				main::main_loop(pool_file_name, world_file, seed, plasticity, snapshot_format, journal, fullscreen, width, height, audio_device);
 			}
 		}
 		Err(message) => {
@@ -149,6 +245,8 @@ pub struct Systems {
 	ai: Arc<RwLock<systems::AiSystem>>,
 	alife: Arc<RwLock<systems::AlifeSystem>>,
 	particle: Arc<RwLock<systems::ParticleSystem>>,
+	pheromone: Arc<RwLock<systems::PheromoneSystem>>,
+	journal: Arc<RwLock<systems::JournalSystem>>,
 }
 
 impl Systems {
@@ -166,6 +264,8 @@ impl Systems {
 					SendSystem::boxed(self.game.clone()),
 					SendSystem::boxed(self.ai.clone()),
 					SendSystem::boxed(self.alife.clone()),
+					SendSystem::boxed(self.pheromone.clone()),
+					SendSystem::boxed(self.journal.clone()),
 				],
 			SystemMode::Batch =>
 				vec![
@@ -173,6 +273,8 @@ impl Systems {
 					SendSystem::boxed(self.game.clone()),
 					SendSystem::boxed(self.ai.clone()),
 					SendSystem::boxed(self.alife.clone()),
+					SendSystem::boxed(self.pheromone.clone()),
+					SendSystem::boxed(self.journal.clone()),
 				]
 		}
 	}
@@ -233,6 +335,8 @@ impl Systems {
 bitflags! {
 	pub struct DebugFlags: u32 {
 		const DEBUG_TARGETS = 0x1;
+		const DEBUG_SPECIES = 0x2;
+		const DEBUG_PHEROMONES = 0x4;
 	}
 }
 
@@ -263,6 +367,8 @@ pub struct App {
 	systems: Systems,
 	//
 	last_saved: Option<String>,
+	snapshot_format: world::persist::Format,
+	metrics: Option<metrics::Recorder>,
 	//
 	debug_flags: DebugFlags,
 	has_ui_overlay: bool,
@@ -282,6 +388,8 @@ pub struct SimulationUpdate {
 	pub elapsed: Seconds,
 	pub population: usize,
 	pub extinctions: usize,
+	/// population of each living species, by species id
+	pub species: Vec<(SpeciesId, usize)>,
 }
 
 #[derive(Clone, Debug)]
@@ -297,7 +405,8 @@ pub struct FrameUpdate {
 }
 
 impl App {
-	pub fn new<R>(w: u32, h: u32, scale: f32, resource_loader: &R, minion_gene_pool: &str, world_file: Option<String>) -> Self
+	pub fn new<R>(w: u32, h: u32, scale: f32, resource_loader: &R, minion_gene_pool: &str, world_file: Option<String>, seed: Option<u64>,
+				  plasticity: Plasticity) -> Self
 		where
 			R: ResourceLoader<u8>, {
 		let system_timer = SystemTimer::new();
@@ -312,12 +421,28 @@ impl App {
 			_ => false
 		}));
 
-		let mut new_world = world::World::new(resource_loader, minion_gene_pool);
-		let last_saved = world_file.map(|world_file| {
-			world::persist::Serializer::load(&world_file, &mut new_world)
-				.expect(&format!("Could not load {}", &world_file));
-			world_file
+		let initial_seed = seed.unwrap_or_else(SimulationRng::random_seed);
+		info!("Random seed: {}", initial_seed);
+		let mut new_world = world::World::new(resource_loader, minion_gene_pool, initial_seed);
+		// a snapshot which cannot be read leaves the fresh world untouched
+		let last_saved = world_file.and_then(|world_file| {
+			match world::persist::Serializer::load(&world_file, &mut new_world) {
+				Ok(report) => {
+					report.log(&world_file);
+					Some(world_file)
+				}
+				Err(e) => {
+					error!("Could not load {}: {}. Starting from a fresh world", &world_file, e);
+					None
+				}
+			}
 		});
+		// an explicit seed takes precedence over the generator state stored in the snapshot
+		if let Some(seed) = seed {
+			new_world.reseed(seed);
+		}
+		info!("Brain learning: {:?}", plasticity);
+		new_world.set_plasticity(plasticity);
 
 		App {
 			viewport: Viewport::rect(w, h, scale),
@@ -345,6 +470,8 @@ impl App {
 			is_paused: false,
 			// savegame
 			last_saved,
+			snapshot_format: world::persist::Format::default(),
+			metrics: None,
 			// debug
 			debug_flags: DebugFlags::empty(),
 			has_ui_overlay: true,
@@ -383,12 +510,15 @@ impl App {
 			Event::NextSpeedFactor => { self.speed_factors.next(); }
 			Event::PrevSpeedFactor => { self.speed_factors.prev(); }
 			Event::ToggleDebug => self.debug_flags.toggle(DebugFlags::DEBUG_TARGETS),
+			Event::ToggleSpecies => self.debug_flags.toggle(DebugFlags::DEBUG_SPECIES),
+			Event::TogglePheromones => self.debug_flags.toggle(DebugFlags::DEBUG_PHEROMONES),
 			Event::RestartFromCheckpoint => self.restart_from_checkpoint(),
 
 			Event::AppQuit => self.quit(),
 			Event::TogglePause => self.is_paused = !self.is_paused,
 			Event::ToggleGui => self.has_ui_overlay = !self.has_ui_overlay,
 			Event::SaveGenePoolToFile => self.save_gene_pool_to_file(),
+			Event::SaveLineageToFile => self.save_lineage_to_file(),
 			Event::SaveWorldToFile => self.save_world_to_file(),
 			Event::BeginDrag(_, _) => { self.camera.zero(); }
 			Event::Drag(start, end) => { self.camera.set_relative(start - end); }
@@ -400,8 +530,11 @@ impl App {
 			Event::DeselectAll => self.deselect_all_minions(),
 			Event::NewMinion(pos) => self.new_minion(pos),
 			Event::RandomizeMinion(pos) => self.randomize_minion(pos),
+			Event::AddFeeder(pos) => self.add_feeder(pos),
+			Event::RemoveFeeder(pos) => self.remove_feeder(pos),
 			Event::PrimaryFire(_, _) => { /* Handled by the gameplay system */ }
 			Event::Reload => { /* Handled in the main loop */ }
+			Event::Unthrottle => { /* Handled in the main loop */ }
 			Event::PickMinion(_) => { /* Handled by the physics system */ }
 		}
 	}
@@ -430,6 +563,18 @@ impl App {
 		self.world.new_minion(pos, Motion::default());
 	}
 
+	fn add_feeder(&mut self, pos: Position) {
+		let id = self.world.add_feeder(world::FeederSpec::at(pos));
+		info!("Added feeder {} at {:?}", id, pos);
+	}
+
+	fn remove_feeder(&mut self, pos: Position) {
+		if let Some(id) = self.world.feeder_near(pos, EMITTER_PICK_RADIUS) {
+			self.world.remove_feeder(id);
+			info!("Removed feeder {}", id);
+		}
+	}
+
 	fn primary_fire(&mut self, bullet_speed: f32, rate: SecondsValue) {
 		// forwards the message to the bus
 		self.bus.post(Event::PrimaryFire(bullet_speed, rate).into());
@@ -451,14 +596,21 @@ impl App {
 	}
 
 	pub fn save_gene_pool_to_file(&self) {
-		match self.world.dump() {
+		match self.world.dump(self.last_saved.as_ref().map(|s| s.as_str())) {
 			Err(_) => error!("Failed to save gene pool"),
 			Ok(name) => info!("Saved {}", name),
 		}
 	}
 
+	pub fn save_lineage_to_file(&self) {
+		match self.world.dump_lineage() {
+			Err(_) => error!("Failed to save lineage"),
+			Ok(name) => info!("Saved {}", name),
+		}
+	}
+
 	pub fn save_world_to_file(&mut self) {
-		let result = self.world.serialize();
+		let result = self.world.serialize(self.snapshot_format);
 		match result {
 			Err(_) => error!("Failed to save world state"),
 			Ok(name) => {
@@ -468,6 +620,31 @@ impl App {
 		}
 	}
 
+	/// Format of the world snapshots saved from now on
+	pub fn set_snapshot_format(&mut self, format: world::persist::Format) {
+		self.snapshot_format = format;
+	}
+
+	/// Appends births, deaths and the other events of the simulation to a journal named after `prefix`
+	pub fn open_journal(&mut self, prefix: &str) {
+		if let Err(e) = self.systems.journal.write().unwrap().open(prefix) {
+			error!("Unable to open the journal {}: {}", prefix, e);
+		}
+	}
+
+	/// Writes metrics of the simulation to the given CSV or JSON Lines file, every `period` simulated seconds
+	pub fn record_metrics(&mut self, file_path: &str, period: SecondsValue) {
+		let inbox = self.bus.subscribe(Box::new(|message|
+			if let &Message::Journal(_) = message { true } else { false }));
+		match metrics::Recorder::create(file_path, period, inbox, self.world.seconds(), self.wall_clock.seconds()) {
+			Ok(recorder) => {
+				info!("Writing metrics to {} every {}s", file_path, period);
+				self.metrics = Some(recorder);
+			}
+			Err(e) => error!("Unable to write metrics to {}: {}", file_path, e),
+		}
+	}
+
 	fn set_last_saved(&mut self, name: String) {
 		self.last_saved = Some(name)
 	}
@@ -489,7 +666,10 @@ impl App {
 		self.systems.clear();
 		self.world.clear();
 		if let Some(ref world_file) = self.last_saved {
-			world::persist::Serializer::load(&world_file, &mut self.world).is_ok();
+			match world::persist::Serializer::load(&world_file, &mut self.world) {
+				Ok(report) => report.log(world_file),
+				Err(e) => error!("Could not load {}: {}", world_file, e),
+			}
 		};
 		self.bus.post(world::alert::Alert::RestartFromCheckpoint.into())
 	}
@@ -498,6 +678,18 @@ impl App {
 		self.is_running
 	}
 
+	pub fn is_paused(&self) -> bool {
+		self.is_paused
+	}
+
+	pub fn speed_factor(&self) -> SpeedFactor {
+		self.speed_factors.get()
+	}
+
+	pub fn last_saved(&self) -> Option<&str> {
+		self.last_saved.as_ref().map(|s| s.as_str())
+	}
+
 	pub fn on_input_event(&mut self, e: &input::Event) {
 		self.input_state.event(e);
 	}
@@ -551,6 +743,7 @@ impl App {
 	fn cleanup_before(&mut self) {
 		self.world.cleanup_before();
 		self.systems.unregister(&self.world.sweep());
+		self.world.reindex();
 	}
 
 	fn init_systems(&mut self, mode: SystemMode) {
@@ -572,6 +765,17 @@ impl App {
 		self.world.tick(dt);
 	}
 
+	fn update_metrics(&mut self) {
+		let result = match self.metrics {
+			Some(ref mut metrics) => metrics.update(&self.world, self.wall_clock.seconds()),
+			None => Ok(()),
+		};
+		if let Err(e) = result {
+			error!("Metrics stopped, unable to write: {}", e);
+			self.metrics = None;
+		}
+	}
+
 	pub fn receive(&mut self) {
 		for event in self.reply_inbox.drain().into_iter() {
 			match event {
@@ -646,6 +850,7 @@ impl App {
 		self.update_systems(dt);
 		self.cleanup_after();
 		self.tick(dt);
+		self.update_metrics();
 
 		self.simulations_count += 1;
 
@@ -656,6 +861,7 @@ impl App {
 			elapsed: self.world.seconds(),
 			population: self.world.agents(agent::AgentType::Minion).len(),
 			extinctions: self.world.extinctions(),
+			species: self.world.species().counts(),
 		}
 	}
 }
@@ -2,6 +2,9 @@ use super::*;
 use frontend::render;
 use frontend::render::Style;
 use frontend::render::Draw;
+use backend::world::sensor::Seen;
+use backend::world::topology::Topology;
+use std::f32::consts;
 
 impl App {
 	pub fn environment(&self) -> Environment {
@@ -79,17 +82,26 @@ impl App {
 		where R: render::Draw {
 		use cgmath::SquareMatrix;
 		let extent = &self.world.extent;
-		let points = &[
-			extent.min,
-			Position::new(extent.min.x, extent.max.y),
-			extent.max,
-			Position::new(extent.max.x, extent.min.y),
-			extent.min,
-		];
+		let points = match self.world.topology() {
+			Topology::Circular => {
+				let (center, radius) = Topology::circle(extent);
+				(0..BOUNDARY_CIRCLE_VERTICES + 1).map(|i| {
+					let angle = i as f32 * 2. * consts::PI / BOUNDARY_CIRCLE_VERTICES as f32;
+					center + Position::new(angle.cos(), angle.sin()) * radius
+				}).collect::<Vec<_>>()
+			}
+			_ => vec![
+				extent.min,
+				Position::new(extent.min.x, extent.max.y),
+				extent.max,
+				Position::new(extent.max.x, extent.min.y),
+				extent.min,
+			],
+		};
 		renderer.draw_lines(
 			None,
 			Matrix4::identity(),
-			points,
+			&points,
 			render::Appearance::rgba(self.lights.get()),
 		);
 		renderer.draw_quad(
@@ -103,7 +115,8 @@ impl App {
 	fn paint_feeders<R>(&self, renderer: &mut R) where R: render::DrawBuffer {
 		let mut batch_buffer = render::PrimitiveBuffer::new();
 		for e in self.world.feeders() {
-			let transform = Self::from_position(&e.transform().position);
+			// feeders shrink as their food runs out
+			let transform = Self::from_position(&e.transform().position) * Matrix4::from_scale(0.5 + 0.5 * e.fullness());
 			batch_buffer.draw_ball(None, transform, render::Appearance::rgba(self.lights.get()));
 		}
 		renderer.draw_buffer(batch_buffer)
@@ -150,6 +163,19 @@ impl App {
 						render::Appearance::rgba([0., 1., 0., 1.]),
 					);
 
+					let vision = agent.brain().vision;
+					let vision_range = sensor.growing_radius() * VISION_RANGE_SCALE;
+					for (i, sight) in agent.state.sight().iter().enumerate() {
+						let ray = Matrix2::from_angle(Rad(a0 + vision.ray_angle(i))) * (-Position::unit_y());
+						let p1 = p0 + ray * vision_range * sight.distance;
+						let appearance = if sight.seen == Seen::Nothing {
+							render::Appearance::rgba([0.5, 0.5, 0.5, 0.5])
+						} else {
+							render::Appearance::rgba([1., 0., 1., 1.])
+						};
+						batch_buffer.draw_lines(Some(Style::DebugLines), Matrix4::identity(), &[p0, p1], appearance);
+					}
+
 					let trajectory = agent.state.trajectory();
 					let appearance = render::Appearance::new(sensor.color(), [2.0, 1.0, 0., 0.]);
 					batch_buffer.draw_lines(Some(Style::DebugLines), Matrix4::identity(), &trajectory, appearance);
@@ -185,6 +211,42 @@ impl App {
 		};
 	}
 
+	fn paint_species<R>(&self, renderer: &mut R)
+		where R: render::DrawBuffer {
+		if self.debug_flags.contains(DebugFlags::DEBUG_SPECIES) {
+			let mut batch_buffer = render::PrimitiveBuffer::new();
+			let taxonomy = self.world.species();
+			for (id, agent) in self.world.agents(world::agent::AgentType::Minion).iter() {
+				if let Some(species) = taxonomy.species_of(*id).and_then(|s| taxonomy.get(s)) {
+					let core = &agent.segments()[0];
+					let transform = Self::from_position(&core.transform.position)
+						* Matrix4::from_scale(core.growing_radius() * 0.5);
+					batch_buffer.draw_ball(None, transform, render::Appearance::rgba(species.color()));
+				}
+			}
+			renderer.draw_buffer(batch_buffer)
+		}
+	}
+
+	fn paint_pheromones<R>(&self, renderer: &mut R)
+		where R: render::DrawBuffer {
+		if self.debug_flags.contains(DebugFlags::DEBUG_PHEROMONES) {
+			// one hue per channel, fading in with the concentration
+			const CHANNEL_COLORS: [[f32; 3]; 4] = [[0., 1., 1.], [1., 0., 1.], [1., 1., 0.], [0., 1., 0.]];
+			let mut batch_buffer = render::PrimitiveBuffer::new();
+			let field = self.world.pheromones();
+			let scale = field.cell_size() * 0.5;
+			for channel in 0..field.channel_count() {
+				let c = CHANNEL_COLORS[channel % CHANNEL_COLORS.len()];
+				for (center, value) in field.cells_above(channel, PHEROMONE_OVERLAY_THRESHOLD) {
+					let transform = Self::from_position(&center) * Matrix4::from_scale(scale);
+					batch_buffer.draw_ball(None, transform, render::Appearance::rgba([c[0], c[1], c[2], value.min(1.) * 0.5]));
+				}
+			}
+			renderer.draw_buffer(batch_buffer)
+		}
+	}
+
 	pub fn paint<R>(&self, renderer: &mut R)
 		where R: render::Draw + render::DrawBatch + render::DrawBuffer {
 		self.paint_feeders(renderer);
@@ -192,6 +254,8 @@ impl App {
 		self.paint_particles(renderer);
 		self.paint_particles_trails(renderer);
 		self.paint_extent(renderer);
+		self.paint_pheromones(renderer);
+		self.paint_species(renderer);
 		self.paint_hud(renderer);
 	}
 }
\ No newline at end of file
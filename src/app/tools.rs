@@ -0,0 +1,205 @@
+use app::constants::*;
+use backend::world::agent::AgentType;
+use backend::world::explain;
+use backend::world::gen;
+use backend::world::pool;
+use backend::world::persist::Serializer;
+use backend::world::svg;
+use backend::world::World;
+use core::resource::filesystem::ResourceLoaderBuilder;
+use core::rng::SimulationRng;
+use serialize::base64::FromBase64;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+fn read_genomes(source: &str) -> io::Result<Vec<gen::Dna>> {
+	if Path::new(source).is_file() {
+		let mut data = Vec::new();
+		fs::File::open(source)?.read_to_end(&mut data)?;
+		let pool = gen::GenePool::parse_from_resource(&data)?;
+		Ok(pool.gene_pool_iter().cloned().collect())
+	} else {
+		source.from_base64()
+			.map(|dna| vec![dna.into_boxed_slice()])
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", source, e)))
+	}
+}
+
+/// Decodes base64 genomes or whole gene pool files and prints what they develop into
+pub fn explain(sources: &[String], json: bool) -> io::Result<()> {
+	if sources.is_empty() {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "usage: rust-oids explain [-j] <dna|gene_pool.csv>..."));
+	}
+	let mut reports = Vec::new();
+	for source in sources {
+		for dna in read_genomes(source)? {
+			reports.push(explain::explain_minion(&dna));
+		}
+	}
+	if json {
+		println!("{}", explain::to_json(&reports)?);
+	} else {
+		for (i, report) in reports.iter().enumerate() {
+			println!("# {}", i);
+			println!("{}", report);
+		}
+	}
+	Ok(())
+}
+
+/// Rewrites a world snapshot in the format of the target file, upgrading it to the current version
+pub fn convert(paths: &[String]) -> io::Result<()> {
+	if paths.len() != 2 {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "usage: rust-oids convert <from.json|oids> <to.json|oids>"));
+	}
+	let report = Serializer::convert(&paths[0], &paths[1])?;
+	if !report.is_clean() {
+		println!("{}", report);
+	}
+	println!("{} -> {}", paths[0], paths[1]);
+	Ok(())
+}
+
+const POOL_USAGE: &'static str = "usage: rust-oids pool [-o <out.csv>] [-s <seed>] <command>
+    merge <pool.csv>...
+    dedupe <distance> <pool.csv>...
+    sample random|diverse <n> <pool.csv>...
+    extract <snapshot.json|oids>
+    diff <a.csv> <b.csv>    writes the genomes of a which are not in b";
+
+fn usage() -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidInput, POOL_USAGE)
+}
+
+/// All the records of the given gene pool files, in order, reporting the rows that had to be skipped
+fn read_pools(sources: &[String]) -> io::Result<Vec<pool::Record>> {
+	if sources.is_empty() {
+		return Err(usage());
+	}
+	let mut records = Vec::new();
+	for source in sources {
+		let mut data = Vec::new();
+		fs::File::open(source)?.read_to_end(&mut data)?;
+		let parsed = pool::parse(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", source, e)))?;
+		for e in &parsed.errors {
+			eprintln!("{}: skipping {}", source, e);
+		}
+		records.extend(parsed.records);
+	}
+	Ok(records)
+}
+
+fn parse_arg<T: ::std::str::FromStr>(arg: Option<&String>) -> io::Result<T> {
+	arg.and_then(|a| a.parse::<T>().ok()).ok_or_else(usage)
+}
+
+/// Curates gene pool files: every command writes a pool that a new world can start from
+pub fn pool(args: &[String], output: Option<&str>, seed: Option<u64>) -> io::Result<()> {
+	let records = match args.get(0).map(|c| c.as_str()) {
+		Some("merge") => read_pools(&args[1..])?,
+		Some("dedupe") => {
+			let distance: f32 = parse_arg(args.get(1))?;
+			let records = read_pools(&args[2..])?;
+			let before = records.len();
+			let kept = pool::dedupe(records, distance);
+			eprintln!("{} duplicates removed, {} genomes left", before - kept.len(), kept.len());
+			kept
+		}
+		Some("sample") => {
+			let n: usize = parse_arg(args.get(2))?;
+			let records = read_pools(&args[3..])?;
+			match args.get(1).map(|m| m.as_str()) {
+				Some("random") => {
+					let mut rng = SimulationRng::with_seed(seed.unwrap_or_else(SimulationRng::random_seed));
+					pool::sample_random(records, n, &mut rng)
+				}
+				Some("diverse") => pool::sample_diverse(records, n),
+				_ => return Err(usage()),
+			}
+		}
+		Some("extract") if args.len() == 2 => {
+			let (records, report) = Serializer::extract_gene_pool(&args[1])?;
+			if !report.is_clean() {
+				eprintln!("{}", report);
+			}
+			records
+		}
+		Some("diff") if args.len() == 3 => {
+			let a = read_pools(&args[1..2])?;
+			let b = read_pools(&args[2..3])?;
+			let (only_a, only_b, both) = pool::overlap(&a, &b);
+			eprintln!("{} genomes only in {}, {} only in {}, {} in both", only_a, args[1], only_b, args[2], both);
+			pool::difference(&a, &b)
+		}
+		_ => return Err(usage()),
+	};
+	match output {
+		Some(path) => {
+			let mut out = io::BufWriter::new(fs::File::create(path)?);
+			pool::write(&mut out, &records)?;
+			eprintln!("{} genomes written to {}", records.len(), path);
+			Ok(())
+		}
+		None => {
+			let stdout = io::stdout();
+			let mut out = stdout.lock();
+			pool::write(&mut out, &records)
+		}
+	}
+}
+
+
+const SVG_USAGE: &'static str = "usage: rust-oids svg [-o <out.svg>] <command>
+    organism <dna|gene_pool.csv> [index]
+    sheet <pool.csv>...
+    world <snapshot.json|oids>";
+
+/// Draws organisms, gene pools or world snapshots as SVG, without opening a window
+pub fn svg(args: &[String], output: Option<&str>) -> io::Result<()> {
+	let usage = || io::Error::new(io::ErrorKind::InvalidInput, SVG_USAGE);
+	let scene = match args.get(0).map(|c| c.as_str()) {
+		Some("organism") if args.len() == 2 || args.len() == 3 => {
+			let genomes = read_genomes(&args[1])?;
+			let index = match args.get(2) {
+				Some(i) => i.parse::<usize>().map_err(|_| usage())?,
+				None => 0,
+			};
+			let dna = genomes.get(index).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+				format!("{}: no genome at index {}, there are {}", args[1], index, genomes.len())))?;
+			svg::organism(dna)
+		}
+		Some("sheet") if args.len() > 1 => svg::contact_sheet(&read_pools(&args[1..])?),
+		Some("world") if args.len() == 2 => {
+			// the configuration still comes from the resources, everything else from the snapshot
+			let res = ResourceLoaderBuilder::new()
+				.add(Path::new("resources"))
+				.build();
+			let mut world = World::new(&res, "minion_gene_pool.csv", 0);
+			let report = Serializer::load(&args[1], &mut world)?;
+			if !report.is_clean() {
+				eprintln!("{}", report);
+			}
+			// snapshots keep the layout of the arena, the walls are built from it on the first frame
+			if world.agents(AgentType::Prop).is_empty() {
+				world.init_props();
+			}
+			svg::world(&world)
+		}
+		_ => return Err(usage()),
+	};
+	match output {
+		Some(path) => {
+			let mut out = io::BufWriter::new(fs::File::create(path)?);
+			scene.write(&mut out, SVG_WIDTH)?;
+			eprintln!("Picture written to {}", path);
+			Ok(())
+		}
+		None => {
+			let stdout = io::stdout();
+			let mut out = stdout.lock();
+			scene.write(&mut out, SVG_WIDTH)
+		}
+	}
+}
@@ -1,5 +1,6 @@
 use app::Event;
 use backend::world::alert::Alert;
+use backend::world::journal;
 use backend::world::particle::Emitter;
 use std::sync::mpsc::{Sender, Receiver};
 use std::sync::mpsc;
@@ -9,6 +10,7 @@ pub enum Message {
 	Alert(Alert),
 	Event(Event),
 	NewEmitter(Emitter),
+	Journal(journal::Record),
 }
 
 impl From<Emitter> for Message {
@@ -29,6 +31,12 @@ impl From<Alert> for Message {
 	}
 }
 
+impl From<journal::Record> for Message {
+	fn from(value: journal::Record) -> Self {
+		Message::Journal(value)
+	}
+}
+
 impl Into<Option<Emitter>> for Message {
 	fn into(self) -> Option<Emitter> {
 		match self {
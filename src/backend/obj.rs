@@ -256,6 +256,8 @@ pub struct Material {
 #[derive(Clone)]
 pub struct Livery {
 	pub albedo: Rgba,
+	/// the hue in the genes, which `albedo` was derived from
+	pub hue: f32,
 	pub frequency: f32,
 	pub phase: f32,
 	pub amplitude: f32,
@@ -278,6 +280,7 @@ impl Default for Livery {
 	fn default() -> Self {
 		Livery {
 			albedo: [1., 1., 1., 1.],
+			hue: 0.,
 			frequency: 0.5,
 			phase: 0.,
 			amplitude: 0.5,
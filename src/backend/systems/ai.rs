@@ -5,100 +5,182 @@ use backend::obj;
 use backend::obj::Identified;
 use backend::obj::Transformable;
 use backend::world;
+use backend::world::AgentState;
 use backend::world::agent;
 use backend::world::agent::Personality;
 use backend::world::agent::TypedAgent;
 use backend::world::segment;
 use backend::world::segment::Intent;
+use backend::world::sensor::{Channel, Sensors};
+use backend::world::topology::Topology;
 use cgmath::*;
-use core::geometry::Position;
+use core::geometry::{Position, Rect};
 use itertools::Itertools;
 use app::constants::*;
 
-type IdPositionMap = HashMap<obj::Id, Position>;
+/// What an agent knows about its surroundings, as found in the spatial index
+#[derive(Clone, Copy)]
+struct Perception {
+	/// the food the agent is after, if any is in sight
+	target: Option<(obj::Id, Position)>,
+	/// the nearest agent of the same kind
+	neighbour: Option<Position>,
+	/// gradient of each pheromone channel at the sensor
+	scent: [Position; PHEROMONE_CHANNELS],
+}
+
+impl Default for Perception {
+	fn default() -> Self {
+		Perception {
+			target: None,
+			neighbour: None,
+			scent: [Position::zero(); PHEROMONE_CHANNELS],
+		}
+	}
+}
+
+type PerceptionMap = HashMap<obj::Id, Perception>;
 
 pub struct AiSystem {
+	plasticity: agent::Plasticity,
 	beacons: Box<[Position]>,
-	targets: IdPositionMap,
+	perceptions: PerceptionMap,
+	/// distances are measured across the border of a wrapping world
+	topology: Topology,
+	extent: Rect,
 }
 
 impl System for AiSystem {
 	fn clear(&mut self) {
 		self.beacons = Box::new([]);
-		self.targets.clear();
+		self.perceptions.clear();
 	}
 
 	fn import(&mut self, world: &world::World) {
+		self.plasticity = world.plasticity();
+		self.topology = world.topology();
+		self.extent = world.extent;
 		self.beacons = world
 			.feeders()
 			.iter()
 			.map(|e| e.transform().position)
 			.collect::<Vec<_>>()
 			.into_boxed_slice();
-		self.targets = world
-			.agents(agent::AgentType::Resource)
-			.iter()
-			.filter(|&(_, ref v)| v.state.is_active())
-			.map(|(_, v)| (v.id(), v.transform().position))
-			.collect::<HashMap<_, _>>();
+		self.perceptions.clear();
+		// predators hunt minions, and roam between feeders where minions gather when there are none in sight
+		for &(agent_type, food) in &[
+			(agent::AgentType::Minion, agent::AgentType::Resource),
+			(agent::AgentType::Enemy, agent::AgentType::Minion),
+		] {
+			Self::perceive(world, agent_type, food, &mut self.perceptions);
+		}
 	}
 
 	fn export(&self, world: &mut world::World, _outbox: &Outbox) {
-		Self::update_minions(
-			&self.targets,
-			&self.beacons,
-			&mut world.agents_mut(agent::AgentType::Minion),
-		);
+		for &(agent_type, food) in &[
+			(agent::AgentType::Minion, agent::AgentType::Resource),
+			(agent::AgentType::Enemy, agent::AgentType::Minion),
+		] {
+			Self::update_agents(
+				self.plasticity,
+				self.topology,
+				&self.extent,
+				food,
+				&self.perceptions,
+				&self.beacons,
+				&mut world.agents_mut(agent_type),
+			);
+		}
 	}
 }
 
 impl Default for AiSystem {
 	fn default() -> Self {
 		AiSystem {
+			plasticity: agent::Plasticity::default(),
 			beacons: Box::new([]),
-			targets: HashMap::new(),
+			perceptions: PerceptionMap::new(),
+			topology: Topology::default(),
+			extent: Rect::default(),
 		}
 	}
 }
 
 impl AiSystem {
-	fn update_minions(targets: &IdPositionMap, beacons: &[Position], minions: &mut agent::AgentMap) {
-		fn nearest_beacon<'a>(beacons: &'a [Position], p: &'a Position) -> &'a Position {
+	/// Looks up the target and the nearest neighbour of every agent of the given type.
+	/// Agents stick to their target while it lives, otherwise they pick the nearest food within radar range.
+	fn perceive(world: &world::World, agent_type: agent::AgentType, food: agent::AgentType, perceptions: &mut PerceptionMap) {
+		let topology = world.topology();
+		for (id, agent) in world.agents(agent_type).iter() {
+			let sensor = match agent.segments().iter().find(|s| s.flags.contains(segment::Flags::SENSOR)) {
+				Some(sensor) => sensor,
+				None => continue,
+			};
+			let p0 = sensor.transform.position;
+			let radar_range = sensor.growing_radius() * 10.;
+			let current = match *agent.state.target() {
+				Some(target) => match world.agent(target) {
+					Some(other) if other.state.is_active() => Some((target, other.transform().position)),
+					_ => None,
+				},
+				None => None,
+			};
+			// only the cells within radar range are searched, however scarce the food
+			let target = current.or_else(|| {
+				world.within(food, p0, radar_range)
+					.into_iter()
+					.map(|(id, p)| (topology.distance(&world.extent, p0, p), id, p))
+					.filter(|&(d, _, _)| d < radar_range)
+					.fold(None, |nearest: Option<(f32, obj::Id, Position)>, candidate| match nearest {
+						Some(n) if n.0 <= candidate.0 => Some(n),
+						_ => Some(candidate),
+					})
+					.map(|(_, id, p)| (id, p))
+			});
+			let neighbour = world.nearest(agent_type, p0, 2)
+				.into_iter()
+				.find(|&(other, _)| other != *id)
+				.map(|(_, p)| p);
+			let mut scent = [Position::zero(); PHEROMONE_CHANNELS];
+			for (channel, gradient) in scent.iter_mut().enumerate() {
+				*gradient = world.pheromones().gradient(channel, p0);
+			}
+			perceptions.insert(*id, Perception { target, neighbour, scent });
+		}
+	}
+
+	/// Steers the agents towards their target, which is always of the `food` type.
+	/// Touching food is fine, touching anything else makes the agent run away.
+	fn update_agents(plasticity: agent::Plasticity, topology: Topology, extent: &Rect, food: agent::AgentType,
+					 perceptions: &PerceptionMap, beacons: &[Position], agents: &mut agent::AgentMap) {
+		let distance2 = |from: &Position, to: &Position| topology.delta(extent, *from, *to).magnitude2();
+		let nearest_beacon = |beacons: &[Position], p: &Position| -> Position {
 			beacons
 				.iter()
-				.fold1(|n, b| if (p - n).magnitude2() < (p - b).magnitude2() {
+				.fold1(|n, b| if distance2(p, n) < distance2(p, b) {
 					n
 				} else {
 					b
 				})
-				.unwrap_or(p)
-		}
+				.cloned()
+				.unwrap_or(*p)
+		};
 
-		for (_, agent) in minions.iter_mut() {
+		for (_, agent) in agents.iter_mut() {
 			let brain = agent.brain().clone();
 			let core = agent.first_segment(segment::Flags::CORE);
 			let head = agent.first_segment(segment::Flags::SENSOR);
 			if let Some(sensor) = head {
 				let p0 = sensor.transform.position;
 				let radar_range = sensor.growing_radius() * 10.;
-				let current_target = agent.state.target().clone();
 				let current_target_position = agent.state.target_position().clone();
-				// if our original target is dead then we need to find another one
-				let new_target: Option<(obj::Id, Position)> = match current_target {
-					None => {
-						targets
-							.iter()
-							.find(|&(_, &p)| (p - p0).magnitude() < radar_range)
-							.map(|(&id, &position)| (id, position))
-					}
-					Some(id) => targets.get(&id).map(|&position| (id, position)),
-				};
-				// and failing that again, we target
-				match new_target {
+				let perception = perceptions.get(&agent.id()).cloned().unwrap_or_default();
+				// if our original target is dead and there is no other food in sight, we target
+				match perception.target {
 					None => {
 						agent.state.retarget(
 							None,
-							*nearest_beacon(
+							nearest_beacon(
 								beacons,
 								&current_target_position,
 							),
@@ -108,21 +190,66 @@ impl AiSystem {
 				};
 				// find where our target is in the world
 				let target_position = agent.state.target_position().clone();
-				// and transform the world position into the head's frame
-				let t0 = target_position - sensor.transform.position;
-				let t = t0.normalize_to(t0.magnitude().min(radar_range));
 				// direction in which the head is pointing, normalized
 				let s = Matrix2::from_angle(Rad(sensor.transform.angle)) * (-Position::unit_y());
+				// and transform a world position into the head's frame, up to the radar range
+				let to_head = |p: Position| {
+					let t0 = topology.delta(extent, p0, p);
+					if t0.magnitude2() > 0. {
+						let t = t0.normalize_to(t0.magnitude().min(radar_range));
+						(t.dot(s), t.perp_dot(s))
+					} else {
+						(0., 0.)
+					}
+				};
+				let (target_ahead, target_side) = to_head(target_position);
+				let (neighbour_ahead, neighbour_side) = perception.neighbour
+					.map(&to_head)
+					.unwrap_or((0., 0.));
 				// some proprioception, feeding back the angle betweent the neck and the first torso
 				let neck_angle = consts::PI + sensor.transform.angle -
-					core.map(|t| t.transform.angle).unwrap_or(
+					core.as_ref().map(|t| t.transform.angle).unwrap_or(
 						sensor.transform.angle,
 					);
-				// we pass the relative position of the target decomposed in our frame of reference to the neural network
-				// expecting four components we can use as thresholds
-				let r = agent.brain().response(
-					&[neck_angle, t.dot(s), t.perp_dot(s), 0.],
-				);
+				let speed = core.as_ref().map(|t| t.motion.velocity.magnitude()).unwrap_or(0.);
+				let touch = agent.segments().iter().any(|segment| segment.state.last_touched.is_some());
+				let beacon_distance = distance2(&p0, &nearest_beacon(beacons, &p0)).sqrt();
+				// we pass the relative position of the target decomposed in our frame of reference to the neural network,
+				// along with what we know about our own state, and get four components we can use as thresholds
+				let mut sensors = Sensors::default();
+				sensors
+					.set(Channel::NeckAngle, neck_angle)
+					.set(Channel::TargetAhead, target_ahead)
+					.set(Channel::TargetSide, target_side)
+					.set(Channel::EnergyRatio, agent.state.energy_ratio())
+					.set(Channel::Speed, (speed / SENSOR_SPEED_RANGE).min(1.))
+					.set(Channel::Touch, if touch { 1. } else { 0. })
+					.set(Channel::BeaconDistance, (beacon_distance / WORLD_RADIUS).min(1.))
+					.set(Channel::NeighbourAhead, neighbour_ahead)
+					.set(Channel::NeighbourSide, neighbour_side)
+					.set(Channel::Fasting, (agent.state.fasting() as f32 / SENSOR_FASTING_RANGE).min(1.));
+				for (i, gradient) in perception.scent.iter().enumerate() {
+					let saturate = |v: f32| (v / SENSOR_PHEROMONE_RANGE).max(-1.).min(1.);
+					sensors
+						.set(Channel::PheromoneAhead(i), saturate(gradient.dot(s)))
+						.set(Channel::PheromoneSide(i), saturate(gradient.perp_dot(s)));
+				}
+				for (i, sight) in agent.state.sight().iter().take(MAX_VISION_RAYS).enumerate() {
+					sensors
+						.set(Channel::RayProximity(i), sight.proximity())
+						.set(Channel::RayKind(i), sight.seen.code());
+				}
+				let r = agent.brain().response(sensors.values());
+				// food is the only reward, and plain Hebbian learning needs none
+				let reward = agent.state.collect_reward();
+				let modulation = match plasticity {
+					agent::Plasticity::Off => 0.,
+					agent::Plasticity::Hebbian => 1.,
+					agent::Plasticity::Reward => reward,
+				};
+				if modulation != 0. {
+					agent.brain_mut().learn(sensors.values(), modulation);
+				}
 
 				let segments = &mut agent.segments_mut();
 				for segment in segments.iter_mut() {
@@ -132,7 +259,7 @@ impl AiSystem {
 						let f = Matrix2::from_angle(Rad(segment.transform.angle)) * Position::unit_y() * power;
 						let intent = if let Some(refs) = segment.state.last_touched {
 							match refs.id().type_of() {
-								agent::AgentType::Resource => Intent::Idle,
+								touched if touched == food => Intent::Idle,
 								_ => {
 									let fear: f32 = brain.fear();
 									Intent::RunAway(f * fear)
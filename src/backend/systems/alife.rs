@@ -1,49 +1,61 @@
 use super::*;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use rand;
 use core::geometry;
-use core::clock::SimulationTimer;
+use core::clock::{SecondsValue, SimulationTimer};
 use backend::obj;
 use backend::obj::Transformable;
 use backend::obj::Identified;
 use backend::world;
 use backend::world::gen;
 use backend::world::agent;
+use backend::world::agent::TypedAgent;
 use backend::world::segment;
 use backend::world::particle;
 use backend::world::AgentState;
 use backend::world::alert;
+use backend::world::journal;
+use backend::world::topology::Topology;
 use backend::messagebus::Outbox;
 use serialize::base64::{self, ToBase64};
+use app::constants::*;
 
-type StateMap = HashMap<obj::Id, agent::State>;
-type GeneMap = HashMap<obj::Id, gen::Dna>;
+type EnergyMap = HashMap<obj::Id, f32>;
+/// the mate which fertilises each spore, and its genome
+type MateMap = HashMap<obj::Id, (obj::Id, gen::Dna)>;
+/// who ate each resource, ordered so that the journal records them in the same order on every run
+type EaterMap = BTreeMap<obj::Id, obj::Id>;
+/// how many predator mouths bite each minion
+type BiteMap = HashMap<obj::Id, usize>;
+type Spawns = Box<[(geometry::Transform, gen::Dna, obj::Id, agent::Lineage)]>;
+type Corpses = Box<[(Box<[geometry::Transform]>, gen::Dna)]>;
 
 pub struct AlifeSystem {
 	dt: Seconds,
 	simulation_timer: SimulationTimer,
 	source: Box<[world::Feeder]>,
-	eaten: StateMap,
-	touched: GeneMap,
+	eaten: EnergyMap,
+	eaters: EaterMap,
+	touched: MateMap,
 }
 
 impl System for AlifeSystem {
 	fn clear(&mut self) {
 		self.source = Box::new([]);
 		self.eaten.clear();
+		self.eaters.clear();
 		self.touched.clear();
 	}
 
 	fn import(&mut self, world: &world::World) {
+		// lifecycles count from the world clock, which a snapshot may have moved on
+		self.simulation_timer = SimulationTimer::from(world.seconds());
 		self.source = world.feeders().to_vec().into_boxed_slice();
-		self.eaten = Self::find_eaten_resources(
-			&world.agents(agent::AgentType::Minion),
-			&world.agents(agent::AgentType::Resource),
-		);
-		self.touched = Self::find_touched_spores(
-			&world.agents(agent::AgentType::Minion),
-			&world.agents(agent::AgentType::Spore),
-		);
+		let (eaten, eaters) = Self::find_eaten_resources(world);
+		self.eaten = eaten;
+		self.eaters = eaters;
+		self.touched = Self::find_touched_spores(world);
 	}
 
 	fn update(&mut self, _: &AgentState, dt: Seconds) {
@@ -52,6 +64,13 @@ impl System for AlifeSystem {
 	}
 
 	fn export(&self, world: &mut world::World, outbox: &Outbox) {
+		for (&resource, &eater) in self.eaters.iter() {
+			let position = match world.agent(resource) {
+				Some(agent) => agent.transform().position,
+				None => continue,
+			};
+			world.record(outbox, journal::Event::Eaten { resource, eater, position: (position.x, position.y) });
+		}
 		Self::update_resources(
 			self.dt,
 			&self.simulation_timer,
@@ -59,13 +78,32 @@ impl System for AlifeSystem {
 			&self.eaten,
 		);
 
-		let (spores, corpses) = Self::update_minions(
-			outbox,
-			self.dt,
-			world.extent.clone(),
-			&mut world.agents_mut(agent::AgentType::Minion),
-			&self.eaten,
-		);
+		// minions feed on resources and get bitten, predators feed on minions.
+		let meals = Self::meals(&self.eaten, &self.eaters);
+		let bites = Self::find_bites(world);
+		let (wounds, bitten) = Self::bite(self.dt, &world.agents(agent::AgentType::Minion), &bites);
+		let unhurt = EnergyMap::new();
+		let mut spores = Vec::new();
+		let mut corpses = Vec::new();
+		let topology = world.topology();
+		let now = world.seconds().get();
+		for &(agent_type, food, wounds) in &[
+			(agent::AgentType::Minion, &meals, &wounds),
+			(agent::AgentType::Enemy, &bitten, &unhurt),
+		] {
+			let (s, c) = Self::update_minions(
+				outbox,
+				self.dt,
+				now,
+				world.extent.clone(),
+				topology,
+				&mut world.agents_mut(agent_type),
+				food,
+				wounds,
+			);
+			spores.extend(s.into_vec());
+			corpses.extend(c.into_vec());
+		}
 
 		let (hatch, fertilised) = Self::update_spores(
 			self.dt,
@@ -74,25 +112,27 @@ impl System for AlifeSystem {
 			&self.touched,
 		);
 
-		for &(ref transform, ref dna) in spores.into_iter() {
+		for &(ref transform, ref dna, mother, ref lineage) in spores.iter() {
 			outbox.post(alert::Alert::NewSpore.into());
-			world.new_spore(outbox, transform.clone(), dna);
+			world.new_spore(outbox, transform.clone(), dna, mother, lineage);
 		}
 
-		for &(ref transform, ref dna) in hatch.into_iter() {
+		for &(ref transform, ref dna, ref foreign_dna, ref lineage) in hatch.into_iter() {
 			outbox.post(alert::Alert::NewMinion.into());
-			world.hatch_spore(outbox, transform.clone(), dna);
+			let dna = Self::crossover(world.rng_mut(), dna, foreign_dna);
+			world.hatch_spore(outbox, transform.clone(), &dna, lineage);
 		}
 
-		for &(ref transforms, ref dna) in corpses.into_iter() {
+		for &(ref transforms, ref dna) in corpses.iter() {
 			outbox.post(alert::Alert::DieMinion.into());
 			for transform in transforms.iter() {
 				world.decay_to_resource(outbox, transform.clone(), dna);
 			}
 		}
 
-		for _ in 0..fertilised {
+		for &(spore, father, position) in fertilised.iter() {
 			outbox.post(alert::Alert::DieMinion.into());
+			world.record(outbox, journal::Event::Fertilisation { spore, father, position: (position.x, position.y) });
 		}
 	}
 }
@@ -103,41 +143,127 @@ impl Default for AlifeSystem {
 			dt: Seconds::new(1. / 60.),
 			simulation_timer: SimulationTimer::new(),
 			source: Box::new([]),
-			eaten: StateMap::new(),
-			touched: GeneMap::new(),
+			eaten: EnergyMap::new(),
+			eaters: EaterMap::new(),
+			touched: MateMap::new(),
 		}
 	}
 }
 
 impl AlifeSystem {
-	fn find_eaten_resources(minions: &agent::AgentMap, resources: &agent::AgentMap) -> StateMap {
+	/// The active agent of the given type nearest to `segment` among those touching it, if any.
+	/// Candidates come from the spatial index, and are then checked against the circles of their segments.
+	fn touching(world: &world::World, segment: &segment::Segment, agent_type: agent::AgentType) -> Option<obj::Id> {
+		let p = segment.transform().position;
+		let r = segment.growing_radius();
+		let topology = world.topology();
+		let mut nearest: Option<(f32, obj::Id)> = None;
+		for (id, _) in world.within(agent_type, p, r) {
+			let agent = match world.agent(id) {
+				Some(agent) if agent.state.is_active() => agent,
+				_ => continue,
+			};
+			let gap = agent.segments.iter()
+				.map(|s| topology.distance(&world.extent, p, s.transform().position) - s.growing_radius() - r)
+				.fold(f32::INFINITY, f32::min);
+			let nearer = match nearest {
+				None => true,
+				Some((d, other)) => gap < d || (gap == d && id < other),
+			};
+			if gap <= 0. && nearer {
+				nearest = Some((gap, id));
+			}
+		}
+		nearest.map(|(_, id)| id)
+	}
+
+	/// Every resource touched by the mouth of an active minion is eaten by the first minion to reach it.
+	/// Returns the energy of each eaten resource, and who ate it.
+	fn find_eaten_resources(world: &world::World) -> (EnergyMap, EaterMap) {
 		let mut eaten = HashMap::new();
-		for (_, agent) in minions.iter().filter(|&(_, a)| a.state.is_active()) {
-			for segment in agent.segments.iter().filter(|&s| {
-				s.flags.contains(segment::Flags::MOUTH)
-			})
-				{
-					if let Some(key) = segment.state.last_touched {
-						if let Some(&agent::Agent { ref state, .. }) = resources.get(&key.id()) {
-							eaten.insert(key.id(), (*state).clone());
-						}
+		let mut eaters = EaterMap::new();
+		for (_, agent) in world.agents(agent::AgentType::Minion).iter().filter(|&(_, a)| a.state.is_active()) {
+			for segment in agent.segments.iter().filter(|&s| s.flags.contains(segment::Flags::MOUTH)) {
+				if let Some(resource) = Self::touching(world, segment, agent::AgentType::Resource) {
+					if eaten.contains_key(&resource) {
+						continue;
+					}
+					if let Some(food) = world.agent(resource) {
+						eaten.insert(resource, food.state.energy());
+						eaters.insert(resource, agent.id());
 					}
 				}
+			}
 		}
-		eaten
+		(eaten, eaters)
 	}
 
-	fn find_touched_spores(minions: &agent::AgentMap, spores: &agent::AgentMap) -> GeneMap {
+	/// What each minion gains from the resources it ate
+	fn meals(eaten: &EnergyMap, eaters: &EaterMap) -> EnergyMap {
+		let mut meals = HashMap::new();
+		for (resource, &eater) in eaters.iter() {
+			if let Some(&energy) = eaten.get(resource) {
+				*meals.entry(eater).or_insert(0.) += energy;
+			}
+		}
+		meals
+	}
+
+	/// Every predator mouth touching an active minion takes a bite out of it.
+	/// Returns the predator and the minion once per biting mouth.
+	fn find_bites(world: &world::World) -> Vec<(obj::Id, obj::Id)> {
+		let mut bites = Vec::new();
+		for (_, enemy) in world.agents(agent::AgentType::Enemy).iter().filter(|&(_, a)| a.state.is_active()) {
+			for segment in enemy.segments.iter().filter(|&s| s.flags.contains(segment::Flags::MOUTH)) {
+				if let Some(minion) = Self::touching(world, segment, agent::AgentType::Minion) {
+					bites.push((enemy.id(), minion));
+				}
+			}
+		}
+		bites
+	}
+
+	/// The energy each bitten minion loses over `dt`, no more than it has left, and what each predator
+	/// gains from it, shared between the mouths which bit, so that predators never get more than their prey lost
+	fn bite(dt: Seconds, minions: &agent::AgentMap, bites: &[(obj::Id, obj::Id)]) -> (EnergyMap, EnergyMap) {
+		let mut mouths: BiteMap = HashMap::new();
+		for &(_, minion) in bites {
+			*mouths.entry(minion).or_insert(0) += 1;
+		}
+		let mut wounds = HashMap::new();
+		for (&id, &mouths) in mouths.iter() {
+			if let Some(minion) = minions.get(&id) {
+				let wound = (ENEMY_BITE_RATE * dt.get() as f32 * mouths as f32).min(minion.state.energy().max(0.));
+				wounds.insert(id, wound);
+			}
+		}
+		let mut meals = HashMap::new();
+		for &(enemy, minion) in bites {
+			if let Some(&wound) = wounds.get(&minion) {
+				*meals.entry(enemy).or_insert(0.) += wound * ENEMY_BITE_EFFICIENCY / mouths[&minion] as f32;
+			}
+		}
+		(wounds, meals)
+	}
+
+	/// Spores can only be fertilised by creatures of the same kind as the one which laid them
+	fn mate_type(spore: &agent::Agent) -> agent::AgentType {
+		spore.lineage.mother.map(|mother| mother.type_of()).unwrap_or(agent::AgentType::Minion)
+	}
+
+	/// The first mate of the other gender touching each unfertilised spore, and its genome
+	fn find_touched_spores(world: &world::World) -> MateMap {
 		let mut touched = HashMap::new();
-		for (_, spore) in spores.iter().filter(|&(_, a)| {
+		for (_, spore) in world.agents(agent::AgentType::Spore).iter().filter(|&(_, a)| {
 			a.state.is_active() && !a.state.is_fertilised()
 		})
 			{
 				for segment in spore.segments.iter() {
-					if let Some(key) = segment.state.last_touched {
-						if let Some(ref agent) = minions.get(&key.id()) {
+					if let Some(mate) = Self::touching(world, segment, Self::mate_type(spore)) {
+						if let Some(agent) = world.agent(mate) {
 							if agent.gender() != spore.gender() {
-								touched.insert(key.id(), agent.dna().clone());
+								touched.insert(spore.id(), (mate, agent.dna().clone()));
+								break;
 							}
 						}
 					}
@@ -146,20 +272,30 @@ impl AlifeSystem {
 		touched
 	}
 
-	fn update_minions(outbox: &Outbox, dt: Seconds, extent: geometry::Rect, minions: &mut agent::AgentMap, eaten: &StateMap)
-					  -> (Box<[(geometry::Transform, gen::Dna)]>,
-						  Box<[(Box<[geometry::Transform]>, gen::Dna)]>,
-					  ) {
+	/// Lifecycle of minions and predators alike: growth, reproduction, feeding on the energy
+	/// each one gained in `food`, and energy lost to `wounds`.
+	/// Creatures die if the topology of the world makes its border lethal, and they cross it.
+	fn update_minions(outbox: &Outbox, dt: Seconds, now: SecondsValue, extent: geometry::Rect, topology: Topology, minions: &mut agent::AgentMap,
+					  food: &EnergyMap, wounds: &EnergyMap) -> (Spawns, Corpses) {
 		let mut spawns = Vec::new();
 		let mut corpses = Vec::new();
 		for (_, agent) in minions.iter_mut() {
 			if agent.state.is_active() {
 				agent.state.reset_growth();
+				// wounds were measured on the energy before fasting, and are never more than that
+				if let Some(&wound) = wounds.get(&agent.id()) {
+					agent.state.consume(wound);
+				}
+				if let Some(&energy) = food.get(&agent.id()) {
+					agent.state.absorb(energy);
+				}
+				agent.state.fast(dt);
 				let segment = agent.segment(0).unwrap().clone();
 				let id = agent.id();
 				let maturity = segment.state.maturity();
 				let livery_color = segment.livery.albedo;
 				let transform = segment.transform().clone();
+				let position = transform.position;
 				if maturity < 1. { // just grow a bit
 					let r = 0.1;
 					if agent.state.consume_ratio(1. - r, r) {
@@ -182,21 +318,17 @@ impl AlifeSystem {
 						spawns.push((
 							agent.last_segment().transform().clone(),
 							agent.dna().clone(),
+							id,
+							agent.lineage.clone(),
 						));
 					}
 				}
+				let mut cause = None;
 				for segment in agent.segments.iter_mut() {
 					let p = segment.transform().position;
-					if p.x < extent.min.x || p.x > extent.max.x || p.y < extent.min.y || p.y > extent.max.y {
+					if topology.is_lethal(&extent, p) {
 						agent.state.die();
-					}
-					if segment.flags.contains(segment::Flags::MOUTH) {
-						if let Some(id) = segment.state.last_touched {
-							if let Some(eaten_state) = eaten.get(&id.id()) {
-								let energy = eaten_state.energy();
-								agent.state.absorb(energy);
-							}
-						}
+						cause = Some(journal::Cause::Border);
 					}
 					agent.state.consume(
 						dt.get() as f32 * segment.state.get_charge() * segment.growing_radius(),
@@ -210,6 +342,19 @@ impl AlifeSystem {
 						.collect::<Vec<_>>();
 					corpses.push((transforms.into_boxed_slice(), agent.dna().clone()));
 					agent.state.die();
+					if cause.is_none() {
+						cause = Some(if wounds.contains_key(&id) { journal::Cause::Predation } else { journal::Cause::Starvation });
+					}
+				}
+
+				if let Some(cause) = cause {
+					outbox.post(journal::Record::new(now, journal::Event::Death {
+						id,
+						agent_type: id.type_of(),
+						cause,
+						age: now - agent.lineage.birth.get(),
+						position: (position.x, position.y),
+					}).into());
 				}
 
 				if let Some(segment) = agent.first_segment(segment::Flags::TRACKER) {
@@ -220,7 +365,7 @@ impl AlifeSystem {
 		(spawns.into_boxed_slice(), corpses.into_boxed_slice())
 	}
 
-	fn update_resources(dt: Seconds, timer: &SimulationTimer, resources: &mut agent::AgentMap, eaten: &StateMap) {
+	fn update_resources(dt: Seconds, timer: &SimulationTimer, resources: &mut agent::AgentMap, eaten: &EnergyMap) {
 		for (_, agent) in resources.iter_mut() {
 			if eaten.get(&agent.id()).is_some() {
 				agent.state.die();
@@ -236,11 +381,11 @@ impl AlifeSystem {
 		}
 	}
 
-	fn crossover(dna: &gen::Dna, foreign_dna: &Option<gen::Dna>) -> gen::Dna {
+	fn crossover<R: rand::Rng>(rng: &mut R, dna: &gen::Dna, foreign_dna: &Option<gen::Dna>) -> gen::Dna {
 		match foreign_dna {
 			&Some(ref foreign) => {
 				gen::Genome::copy_from(&foreign)
-					.crossover(&mut rand::thread_rng(), dna)
+					.crossover(rng, dna)
 					.dna()
 					.clone()
 			}
@@ -248,37 +393,37 @@ impl AlifeSystem {
 		}
 	}
 
-	fn update_spores(dt: Seconds, timer: &SimulationTimer, spores: &mut agent::AgentMap, touched: &GeneMap)
-					 -> (Box<[(geometry::Transform, gen::Dna)]>, usize) {
+	fn update_spores(dt: Seconds, timer: &SimulationTimer, spores: &mut agent::AgentMap, touched: &MateMap)
+					 -> (Box<[(geometry::Transform, gen::Dna, Option<gen::Dna>, agent::Lineage)]>, Vec<(obj::Id, obj::Id, geometry::Position)>) {
 		let mut spawns = Vec::new();
-		let mut fertilise_count = 0usize;
+		let mut fertilised = Vec::new();
 		for (spore_id, spore) in spores.iter_mut() {
 			if spore.state.lifecycle().is_expired(timer) {
 				spore.state.die();
 				spawns.push((
 					spore.transform().clone(),
-					Self::crossover(spore.dna(), spore.state.foreign_dna()),
+					spore.dna().clone(),
+					spore.state.foreign_dna().clone(),
+					spore.lineage.clone(),
 				))
 			} else if spore.state.is_active() {
-				for segment in spore.segments.iter_mut() {
-					if let Some(key) = segment.state.last_touched {
-						if let Some(touched_dna) = touched.get(&key.id()) {
-							debug!(
-								"fertilised: {} by {} as {}",
-								spore_id,
-								key.id(),
-								touched_dna.to_base64(base64::STANDARD)
-							);
-							fertilise_count += 1;
-							spore.state.fertilise(touched_dna);
-						}
-					}
+				let position = spore.transform().position;
+				if let Some(&(father, ref touched_dna)) = touched.get(spore_id) {
+					debug!(
+						"fertilised: {} by {} as {}",
+						spore_id,
+						father,
+						touched_dna.to_base64(base64::STANDARD)
+					);
+					fertilised.push((*spore_id, father, position));
+					spore.state.fertilise(touched_dna);
+					spore.lineage.father = Some(father);
 				}
 				for segment in spore.segments.iter_mut() {
 					segment.state.update(dt)
 				}
 			}
 		}
-		(spawns.into_boxed_slice(), fertilise_count)
+		(spawns.into_boxed_slice(), fertilised)
 	}
 }
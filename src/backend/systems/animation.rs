@@ -27,10 +27,12 @@ impl System for AnimationSystem {
 		let phase = world.phase_mut()[1] as f64 + self.dt.get() * self.speed * self.heartbeat_scale * self.background_animation_speed;
 		world.phase_mut()[0] = 0.5;
 		world.phase_mut()[1] = (phase % 1e+3) as f32;
-		for (_, agent) in &mut world.agents_mut(agent::AgentType::Minion).iter_mut() {
-			if agent.state.is_active() {
-				let energy = agent.state.energy();
-				agent.state.heartbeat((self.dt.get() * self.speed * self.heartbeat_scale) as f32 * clamp(energy, 50.0f32, 200.0f32))
+		for agent_type in &[agent::AgentType::Minion, agent::AgentType::Enemy] {
+			for (_, agent) in &mut world.agents_mut(*agent_type).iter_mut() {
+				if agent.state.is_active() {
+					let energy = agent.state.energy();
+					agent.state.heartbeat((self.dt.get() * self.speed * self.heartbeat_scale) as f32 * clamp(energy, 50.0f32, 200.0f32))
+				}
 			}
 		}
 	}
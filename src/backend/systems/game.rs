@@ -1,6 +1,6 @@
 use super::*;
 use std::f32::consts;
-use rand;
+use std::ops::Range;
 use rand::Rng;
 use app::constants::*;
 use app::Event;
@@ -10,6 +10,7 @@ use core::geometry::Transform;
 use backend::obj::Transformable;
 use backend::world;
 use backend::world::agent;
+use backend::world::journal;
 use backend::world::Emission;
 use backend::messagebus::{PubSub, Inbox, Whiteboard, ReceiveDrain, Message};
 
@@ -23,36 +24,13 @@ pub struct PlayerState {
 }
 
 pub struct GameSystem {
-	timer: SimulationTimer,
 	playerstate: PlayerState,
-	feeders: Vec<Feeder>,
+	feeders: Vec<world::Feeder>,
+	/// sequence numbers of the resources each feeder emits in this frame
+	spawns: Vec<Range<usize>>,
 	inbox: Option<Inbox>,
 }
 
-struct Feeder {
-	position: Position,
-	hourglass: Hourglass,
-	to_spawn: usize,
-	spawned: usize,
-	emission: Emission,
-	spin: Spin,
-	velocity: f32,
-}
-
-impl Feeder where {
-	fn new<T>(position: Position, rate: Seconds, emission: Emission, timer: &T) -> Self where T: Timer {
-		Feeder {
-			position,
-			hourglass: Hourglass::new(rate, timer),
-			to_spawn: 0,
-			spawned: 0,
-			emission,
-			spin: consts::PI,
-			velocity: 5.,
-		}
-	}
-}
-
 impl System for GameSystem {
 	fn attach(&mut self, bus: &mut PubSub) {
 		self.inbox = Some(bus.subscribe(Box::new(|ev|
@@ -62,6 +40,7 @@ impl System for GameSystem {
 	fn clear(&mut self) {
 		self.playerstate = PlayerState::default();
 		self.feeders = Vec::new();
+		self.spawns = Vec::new();
 	}
 
 	fn import(&mut self, world: &world::World) {
@@ -78,33 +57,14 @@ impl System for GameSystem {
 			}
 		}
 
-		let source = world.feeders();
-		// Add missing emitters - deletion not supported
-		for i in self.feeders.len()..source.len() {
-			let s = &source[i];
-			self.feeders.push(Feeder::new(
-				s.transform().position,
-				s.rate(),
-				s.emission(),
-				&self.timer,
-			));
-		}
-		for (i, d) in self.feeders.iter_mut().enumerate() {
-			d.position = source[i].transform().position;
-		}
+		self.feeders = world.feeders().to_vec();
 	}
 
 	fn update(&mut self, _: &world::AgentState, dt: Seconds) {
-		self.timer.tick(dt);
-		for e in &mut self.feeders {
-			e.spawned = e.to_spawn;
-		}
-		for e in &mut self.feeders {
-			if e.hourglass.is_expired(&self.timer) {
-				e.hourglass.flip(&self.timer);
-				e.to_spawn += 1;
-			}
-		}
+		self.spawns = self.feeders
+			.iter_mut()
+			.map(|feeder| feeder.update(dt.get()))
+			.collect();
 		// Byzantine way of processing trigger presses without trigger releases
 		// I should think of something less convoluted
 		if !self.playerstate.trigger_held {
@@ -123,19 +83,20 @@ impl System for GameSystem {
 	}
 
 	fn export(&self, world: &mut world::World, outbox: &Outbox) {
-		let rng = &mut rand::thread_rng();
-		for e in &self.feeders {
-			for i in e.spawned..e.to_spawn {
-				let r = match e.emission {
-					Emission::Random => rng.next_f32() * 2. * consts::PI,
+		for (e, spawns) in self.feeders.iter().zip(self.spawns.iter()) {
+			let position = e.transform().position;
+			for i in spawns.clone() {
+				let r = match e.emission() {
+					Emission::Random => world.rng_mut().next_f32() * 2. * consts::PI,
 					Emission::CCW(angle) => angle * i as f32,
 					Emission::CW(angle) => -angle * i as f32,
 				};
 				world.new_resource(
-					Transform::new(e.position, r),
-					Motion::new(Velocity::new(r.cos(), r.sin()) * e.velocity, e.spin),
+					Transform::new(position, r),
+					Motion::new(Velocity::new(r.cos(), r.sin()) * e.velocity(), e.spin()),
 				);
 			}
+			world.update_feeder(e.clone());
 		}
 
 		if self.playerstate.bullet_ready {
@@ -143,7 +104,22 @@ impl System for GameSystem {
 		}
 		// if there are no minions, spawn some
 		if world.agents(agent::AgentType::Minion).is_empty() {
+			let extinctions = world.extinctions();
 			world.init_minions();
+			// the first population is not an extinction
+			if world.extinctions() > extinctions {
+				let count = world.extinctions();
+				world.record(outbox, journal::Event::Extinction { count });
+			}
+		}
+		// and the same goes for predators
+		if world.agents(agent::AgentType::Enemy).is_empty() {
+			world.init_enemies();
+		}
+
+		// the walls of the arena are rebuilt after a restart
+		if world.agents(agent::AgentType::Prop).is_empty() && !world.obstacles().is_empty() {
+			world.init_props();
 		}
 
 		if world.agents(agent::AgentType::Player).is_empty() {
@@ -155,9 +131,9 @@ impl System for GameSystem {
 impl Default for GameSystem {
 	fn default() -> Self {
 		GameSystem {
-			timer: SimulationTimer::new(),
 			playerstate: PlayerState::default(),
 			feeders: Vec::new(),
+			spawns: Vec::new(),
 			inbox: None,
 		}
 	}
@@ -0,0 +1,68 @@
+use super::*;
+use std::io;
+use app::constants::*;
+use backend::world::journal;
+use backend::messagebus::{Inbox, Message, Whiteboard, ReceiveDrain};
+
+/// Appends the journal records posted on the bus to a rotating file, if one has been opened.
+/// Records posted while a frame is exported are written at the beginning of the next one,
+/// or when the system is dropped.
+#[derive(Default)]
+pub struct JournalSystem {
+	inbox: Option<Inbox>,
+	writer: Option<journal::Writer>,
+}
+
+impl System for JournalSystem {
+	fn attach(&mut self, bus: &mut PubSub) {
+		self.inbox = Some(bus.subscribe(Box::new(|message|
+			if let &Message::Journal(_) = message { true } else { false })));
+	}
+
+	fn import(&mut self, _: &world::World) {
+		self.drain();
+	}
+}
+
+impl Drop for JournalSystem {
+	/// The records of the last frame are only posted after its import, so they are written on the way out
+	fn drop(&mut self) {
+		self.drain();
+	}
+}
+
+impl JournalSystem {
+	/// Starts writing the journal to files named after `prefix`, carrying on an existing one
+	pub fn open(&mut self, prefix: &str) -> io::Result<()> {
+		let writer = journal::Writer::create(prefix, JOURNAL_FILE_MAX_BYTES)?;
+		info!("Writing the event journal to {}", writer.path().display());
+		self.writer = Some(writer);
+		Ok(())
+	}
+
+	/// Writes every record posted since the last call
+	fn drain(&mut self) {
+		let messages = match self.inbox {
+			Some(ref m) => m.drain(),
+			None => Vec::new(),
+		};
+		let result = match self.writer {
+			Some(ref mut writer) => Self::write(writer, messages),
+			None => Ok(()),
+		};
+		if let Err(e) = result {
+			error!("Journal stopped, unable to write: {}", e);
+			self.writer = None;
+		}
+	}
+
+	fn write(writer: &mut journal::Writer, messages: Vec<Message>) -> io::Result<()> {
+		for message in messages {
+			if let Message::Journal(record) = message {
+				writer.append(&record)?;
+			}
+		}
+		// whole lines only, so that the journal can be followed while it is written
+		writer.flush()
+	}
+}
@@ -4,6 +4,8 @@ pub mod ai;
 pub mod alife;
 pub mod game;
 pub mod particle;
+pub mod pheromone;
+pub mod journal;
 
 pub use self::physics::PhysicsSystem;
 pub use self::animation::AnimationSystem;
@@ -11,6 +13,8 @@ pub use self::game::GameSystem;
 pub use self::ai::AiSystem;
 pub use self::alife::AlifeSystem;
 pub use self::particle::ParticleSystem;
+pub use self::pheromone::PheromoneSystem;
+pub use self::journal::JournalSystem;
 
 use backend::world;
 use backend::messagebus::{PubSub, Outbox};
@@ -5,15 +5,15 @@ use app::constants::*;
 use num::Zero;
 use core::color::Rgba;
 use core::clock::{seconds, Seconds, SimulationTimer, TimerStopwatch};
+use core::rng::SimulationRng;
 use backend::world;
 use backend::world::particle::{EmitterAttachment, EmitterStyle};
 use backend::messagebus::{Inbox, Message, Whiteboard, ReceiveDrain};
 use std::collections::VecDeque;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use backend::world::AgentState;
 use std::iter::Iterator;
 use std::f32::consts;
-use rand;
 use rand::Rng;
 use num;
 use num::NumCast;
@@ -109,7 +109,7 @@ struct Particle {
 }
 
 trait Emitter {
-	fn emit(&mut self, dt: Seconds, id_counter: &mut usize, destination: &mut HashMap<obj::Id, ParticleBatch>) -> bool;
+	fn emit(&mut self, dt: Seconds, rng: &mut SimulationRng, id_counter: &mut usize, destination: &mut BTreeMap<obj::Id, ParticleBatch>) -> bool;
 	fn attached_to(&self) -> EmitterAttachment { EmitterAttachment::None }
 	fn update_transform(&mut self, _transform: Transform, _motion: Motion) {}
 }
@@ -333,8 +333,7 @@ impl SimpleEmitter {
 }
 
 impl Emitter for SimpleEmitter {
-	fn emit(&mut self, dt: Seconds, id_counter: &mut usize, destination: &mut HashMap<obj::Id, ParticleBatch>) -> bool {
-		let mut rng = rand::thread_rng();
+	fn emit(&mut self, dt: Seconds, rng: &mut SimulationRng, id_counter: &mut usize, destination: &mut BTreeMap<obj::Id, ParticleBatch>) -> bool {
 		let jitter_value = self.jitter;
 		let mut jitter = move |w| (rng.next_f32() * 2. * w - w) * jitter_value + 1.;
 		if self.active {
@@ -397,12 +396,15 @@ impl Emitter for SimpleEmitter {
 	}
 }
 
+/// Emitters and particles are kept in id order, and draw from the particle stream of the world,
+/// so that the particles saved in a snapshot come out the same on every run of the same seed
 #[allow(unused)]
 pub struct ParticleSystem {
 	id_counter: usize,
 	inbox: Option<Inbox>,
-	particles: HashMap<obj::Id, ParticleBatch>,
-	emitters: HashMap<obj::Id, Box<Emitter>>,
+	particles: BTreeMap<obj::Id, ParticleBatch>,
+	emitters: BTreeMap<obj::Id, Box<Emitter>>,
+	rng: SimulationRng,
 	dt: Seconds,
 	simulation_timer: SimulationTimer,
 	simulation_clock: TimerStopwatch,
@@ -414,12 +416,47 @@ impl System for ParticleSystem {
 			if let &Message::NewEmitter(_) = m { true } else { false })));
 	}
 
+	/// Picks up the particles of a restored world where they were, each in a batch of its own.
+	/// Their faders hold the values they had, and they drift on until the end of their lifespan.
+	fn init(&mut self, world: &world::World) {
+		for src in world.particles() {
+			let id = self.next_id();
+			let mut faders: FaderList = [None; MAX_FADER];
+			for (value, dest) in src.faders().iter().zip(faders.iter_mut()) {
+				*dest = Some(Fader::flat(*value));
+			}
+			let (color, effect) = src.palette();
+			let transform = src.transform();
+			let velocity = if src.speed() > 0. { src.direction() * src.speed() } else { Velocity::zero() };
+			let trail = src.trail().iter().cloned().collect::<VecDeque<_>>();
+			self.particles.insert(id, ParticleBatch {
+				id,
+				tag: src.tag(),
+				lifespan: src.lifespan(),
+				age: src.age(),
+				color,
+				effect,
+				dampening: 0.,
+				friction: 0.,
+				faders,
+				trail_length: trail.len() as u8,
+				particles: Box::new([Particle {
+					transform,
+					motion: Motion::new(velocity, 0.),
+					acceleration: Acceleration::zero(),
+					trail,
+				}]),
+			});
+		}
+	}
+
 	fn clear(&mut self) {
 		self.particles.clear();
 		self.emitters.clear();
 	}
 
 	fn import(&mut self, world: &world::World) {
+		self.rng = world.particle_rng().clone();
 		let mut emitters = Vec::new();
 		if let Some(ref inbox) = self.inbox {
 			for message in inbox.drain() {
@@ -559,6 +596,7 @@ impl System for ParticleSystem {
 	}
 
 	fn export(&self, world: &mut world::World, _outbox: &Outbox) {
+		*world.particle_rng_mut() = self.rng.clone();
 		for (_, particle_batch) in &self.particles {
 			for particle in &*particle_batch.particles {
 				let mut faders = [1.; MAX_FADER];
@@ -568,6 +606,7 @@ impl System for ParticleSystem {
 				world.add_particle(world::particle::Particle::new(
 					particle.transform.clone(),
 					particle.motion.velocity.normalize(),
+					particle.motion.velocity.magnitude(),
 					particle_batch.tag,
 					particle.trail
 						.iter()
@@ -577,6 +616,7 @@ impl System for ParticleSystem {
 					particle_batch.color,
 					particle_batch.effect,
 					particle_batch.age,
+					particle_batch.lifespan,
 				));
 			}
 		}
@@ -589,8 +629,9 @@ impl Default for ParticleSystem {
 		ParticleSystem {
 			id_counter: 0,
 			inbox: None,
-			emitters: HashMap::new(),
-			particles: HashMap::new(),
+			emitters: BTreeMap::new(),
+			particles: BTreeMap::new(),
+			rng: SimulationRng::with_seed(0),
 			dt: seconds(0.),
 			simulation_clock: TimerStopwatch::new(&simulation_timer),
 			simulation_timer,
@@ -609,7 +650,7 @@ impl ParticleSystem {
 		let mut expired: Vec<usize> = Vec::new();
 
 		for (id, emitter) in &mut self.emitters {
-			let alive = emitter.emit(dt, &mut self.id_counter, &mut self.particles);
+			let alive = emitter.emit(dt, &mut self.rng, &mut self.id_counter, &mut self.particles);
 			if !alive {
 				expired.push(*id);
 			}
@@ -0,0 +1,65 @@
+use super::*;
+use app::constants::*;
+use core::geometry::Position;
+use backend::world::AgentState;
+use backend::world::agent;
+use backend::world::pheromone;
+use backend::world::segment;
+
+/// Keeps the pheromone field of the world: creatures secrete into it from their tail,
+/// then every channel spreads to the neighbouring cells and evaporates.
+pub struct PheromoneSystem {
+	field: Option<pheromone::Field>,
+	/// channel, position and rate of every active gland
+	glands: Vec<(usize, Position, f32)>,
+}
+
+impl Default for PheromoneSystem {
+	fn default() -> Self {
+		PheromoneSystem {
+			field: None,
+			glands: Vec::new(),
+		}
+	}
+}
+
+impl System for PheromoneSystem {
+	fn clear(&mut self) {
+		self.field = None;
+		self.glands.clear();
+	}
+
+	fn import(&mut self, world: &world::World) {
+		self.field = Some(world.pheromones().clone());
+		self.glands.clear();
+		for agent_type in &[agent::AgentType::Minion, agent::AgentType::Enemy] {
+			for (_, agent) in world.agents(*agent_type).iter() {
+				let gland = agent.brain().gland;
+				if !agent.state.is_active() || gland.rate <= 0. {
+					continue;
+				}
+				let tail = agent.first_segment(segment::Flags::TAIL)
+					.or_else(|| agent.segments().last().cloned());
+				if let Some(tail) = tail {
+					self.glands.push((gland.channel, tail.transform.position, gland.rate));
+				}
+			}
+		}
+	}
+
+	fn update(&mut self, _: &AgentState, dt: Seconds) {
+		let dt = dt.get() as f32;
+		if let Some(ref mut field) = self.field {
+			for &(channel, position, rate) in &self.glands {
+				field.deposit(channel, position, rate * dt);
+			}
+			field.step(dt, PHEROMONE_DIFFUSION, PHEROMONE_EVAPORATION);
+		}
+	}
+
+	fn export(&self, world: &mut world::World, _outbox: &Outbox) {
+		if let Some(ref field) = self.field {
+			world.set_pheromones(field.clone());
+		}
+	}
+}
@@ -10,16 +10,23 @@ use wrapped2d::user_data::*;
 use wrapped2d::dynamics::world::callbacks::ContactAccess;
 use core::geometry::*;
 use core::geometry::Transform;
-use cgmath::InnerSpace;
+use cgmath::{InnerSpace, Matrix2, Rad};
 use backend::obj;
 use backend::obj::*;
 use backend::world;
 use backend::world::agent;
+use backend::world::agent::TypedAgent;
+use backend::world::flow;
+use backend::world::topology::Topology;
 use backend::world::segment;
 use backend::world::segment::Intent;
 use backend::world::segment::PilotRotation;
+use backend::world::sensor::{Seen, Sight};
 use backend::messagebus::{PubSub, Inbox, Message, Whiteboard, ReceiveDrain};
 
+/// The body of the world boundaries
+const EXTENT_ID: usize = 0xFFFFFFFFusize;
+
 struct AgentData;
 
 impl UserDataTypes for AgentData {
@@ -33,10 +40,18 @@ type ContactSet = Rc<RefCell<HashMap<agent::Key, agent::Key>>>;
 pub struct PhysicsSystem {
 	world: b2::World<AgentData>,
 	initial_extent: Rect,
+	topology: Topology,
+	/// the static body at the edge of the world, if the topology has one
+	boundary: Option<b2::BodyHandle>,
 	inbox: Option<Inbox>,
 	handles: HashMap<agent::Key, b2::BodyHandle>,
 	touched: ContactSet,
 	picked: HashSet<Id>,
+	flow: flow::Flow,
+	/// positions of the feeders, at the center of the vortices of the flow
+	feeders: Vec<Position>,
+	/// simulation time, which drives the turbulence
+	seconds: f32,
 }
 
 #[allow(unused)]
@@ -69,6 +84,7 @@ impl System for PhysicsSystem {
 
 	fn init(&mut self, world: &world::World) {
 		self.initial_extent = world.extent;
+		self.topology = world.topology();
 		self.init_extent();
 	}
 
@@ -78,6 +94,7 @@ impl System for PhysicsSystem {
 		self.handles.clear();
 		self.picked.clear();
 		self.world = Self::new_world(self.touched.clone());
+		self.boundary = None;
 		self.init_extent();
 	}
 
@@ -120,9 +137,21 @@ impl System for PhysicsSystem {
 				_ => {}
 			}
 		}
-		for (_, agent) in world.agents(agent::AgentType::Minion).iter() {
-			if agent.state.growth() > 0. {
-				self.refresh_registration(agent)
+		if world.topology() != self.topology {
+			self.topology = world.topology();
+			if let Some(handle) = self.boundary.take() {
+				self.world.destroy_body(handle);
+			}
+			self.init_extent();
+		}
+		self.flow = world.flow().clone();
+		self.feeders = world.feeders().iter().map(|f| f.transform().position).collect();
+		self.seconds = world.seconds().get() as f32;
+		for agent_type in &[agent::AgentType::Minion, agent::AgentType::Enemy] {
+			for (_, agent) in world.agents(*agent_type).iter() {
+				if agent.state.growth() > 0. {
+					self.refresh_registration(agent)
+				}
 			}
 		}
 	}
@@ -191,6 +220,22 @@ impl System for PhysicsSystem {
 			}
 		}
 
+		// the water drags every body along, proportionally to its mass
+		if !self.flow.is_still() {
+			for (h, b) in self.world.bodies() {
+				let body = b.borrow();
+				// the boundary and the props stay where they are
+				if (*body).body_type() != b2::BodyType::Dynamic {
+					continue;
+				}
+				let center = (*body).world_center().clone();
+				let water = self.flow.velocity_at(from_vec2(&center), self.seconds, &self.feeders);
+				let relative = water - from_vec2((*body).linear_velocity());
+				let force = relative * ((*body).mass() * self.flow.coupling);
+				dynamic_updates.push((h, Force(center, to_vec2(force))));
+			}
+		}
+
 		for (h, update) in dynamic_updates {
 			let b = &mut self.world.body_mut(h);
 			match update {
@@ -207,6 +252,9 @@ impl System for PhysicsSystem {
 			}
 		}
 		self.world.step(dt, 8, 3);
+		if self.topology.wraps() {
+			self.wrap_bodies();
+		}
 	}
 
 	fn export(&self, world: &mut world::World, outbox: &Outbox) {
@@ -226,8 +274,20 @@ impl System for PhysicsSystem {
 				}
 			}
 		}
-		for (_, agent) in world.agents_mut(agent::AgentType::Minion).iter_mut() {
-			agent.state.reset_growth()
+		for agent_type in &[agent::AgentType::Minion, agent::AgentType::Enemy] {
+			let sights = world.agents(*agent_type)
+				.iter()
+				.filter(|&(_, agent)| agent.state.is_active())
+				.map(|(id, agent)| (*id, self.look(agent)))
+				.collect::<Vec<_>>();
+			for (id, sight) in sights {
+				if let Some(agent) = world.agent_mut(id) {
+					agent.state.see(sight);
+				}
+			}
+			for (_, agent) in world.agents_mut(*agent_type).iter_mut() {
+				agent.state.reset_growth()
+			}
 		}
 		for id in &self.picked {
 			outbox.post(Event::SelectMinion(*id).into());
@@ -242,10 +302,15 @@ impl Default for PhysicsSystem {
 		PhysicsSystem {
 			inbox: None,
 			initial_extent: Rect::default(),
+			topology: Topology::default(),
+			boundary: None,
 			world: Self::new_world(touched.clone()),
 			handles: HashMap::with_capacity(5000),
 			picked: HashSet::with_capacity(100),
 			touched,
+			flow: flow::Flow::default(),
+			feeders: Vec::new(),
+			seconds: 0.,
 		}
 	}
 }
@@ -267,29 +332,72 @@ impl PhysicsSystem {
 	}
 
 
+	/// Builds the boundary of the world: a box, a circle, or nothing at all on a torus
 	fn init_extent(&mut self) {
 		let extent = self.initial_extent;
-		let mut f_def = b2::FixtureDef::new();
-		let mut b_def = b2::BodyDef::new();
-		b_def.body_type = b2::BodyType::Static;
-		let refs = agent::Key::with_id(0xFFFFFFFFusize);
-		let handle = self.world.create_body_with(&b_def, refs);
-
-		let mut rect = b2::ChainShape::new();
-		rect.create_loop(
-			&[
+		let vertices = match self.topology {
+			Topology::Torus => return,
+			Topology::Circular => {
+				let (center, radius) = Topology::circle(&extent);
+				(0..BOUNDARY_CIRCLE_VERTICES).map(|i| {
+					let angle = i as f32 * 2. * ::std::f32::consts::PI / BOUNDARY_CIRCLE_VERTICES as f32;
+					Self::p2v(&(center + Position::new(angle.cos(), angle.sin()) * radius))
+				}).collect::<Vec<_>>()
+			}
+			Topology::Lethal | Topology::Walled => vec![
 				Self::p2v(&extent.bottom_left()),
 				Self::p2v(&extent.bottom_right()),
 				Self::p2v(&extent.top_right()),
 				Self::p2v(&extent.top_left()),
 			],
-		);
+		};
+		let mut f_def = b2::FixtureDef::new();
+		let mut b_def = b2::BodyDef::new();
+		b_def.body_type = b2::BodyType::Static;
+		let refs = agent::Key::with_id(EXTENT_ID);
+		let handle = self.world.create_body_with(&b_def, refs);
+
+		let mut chain = b2::ChainShape::new();
+		chain.create_loop(&vertices);
 
 		self.world.body_mut(handle).create_fixture_with(
-			&rect,
+			&chain,
 			&mut f_def,
 			refs,
 		);
+		self.boundary = Some(handle);
+	}
+
+	/// Brings back every agent whose first segment left the world on the opposite side,
+	/// moving all of its segments together so that its joints are not torn apart
+	fn wrap_bodies(&mut self) {
+		let extent = self.initial_extent;
+		let mut offsets = HashMap::new();
+		for (_, b) in self.world.bodies() {
+			let body = b.borrow();
+			let key = (*body).user_data();
+			if key.segment_index == 0 {
+				let position = Self::v2p((*body).position());
+				if let Some(wrapped) = self.topology.wrap(&extent, position) {
+					offsets.insert(key.agent_id, wrapped - position);
+				}
+			}
+		}
+		if offsets.is_empty() {
+			return;
+		}
+		let mut moves = Vec::new();
+		for (h, b) in self.world.bodies() {
+			let body = b.borrow();
+			let key = (*body).user_data();
+			if let Some(offset) = offsets.get(&key.agent_id) {
+				let position = Self::v2p((*body).position()) + *offset;
+				moves.push((h, Self::p2v(&position), (*body).angle()));
+			}
+		}
+		for (h, position, angle) in moves {
+			self.world.body_mut(h).set_transform(&position, angle);
+		}
 	}
 
 	fn refresh_registration(&mut self, agent: &world::agent::Agent) {
@@ -420,7 +528,11 @@ impl PhysicsSystem {
 
 				let transform = segment.transform();
 				let mut b_def = b2::BodyDef::new();
-				b_def.body_type = b2::BodyType::Dynamic;
+				// props are the fixed obstacles of the arena
+				b_def.body_type = match object_id.type_of() {
+					agent::AgentType::Prop => b2::BodyType::Static,
+					_ => b2::BodyType::Dynamic,
+				};
 				b_def.linear_damping = material.linear_damping;
 				b_def.angular_damping = material.angular_damping;
 				b_def.angle = transform.angle;
@@ -497,6 +609,100 @@ impl PhysicsSystem {
 		world
 	}
 
+	/// Casts the vision rays of the first sensor of the agent, reporting the nearest hit of each
+	fn look(&self, agent: &world::agent::Agent) -> Vec<Sight> {
+		let vision = agent.brain().vision;
+		let sensor = match agent.segments().iter().find(|s| s.flags.contains(segment::Flags::SENSOR)) {
+			Some(sensor) => sensor,
+			None => return Vec::new(),
+		};
+		let id = agent.id();
+		let p0 = sensor.transform.position;
+		let range = sensor.growing_radius() * VISION_RANGE_SCALE;
+		let mut sight = Vec::with_capacity(vision.rays);
+		for i in 0..vision.rays {
+			// heads point towards their local -y
+			let angle = sensor.transform.angle + vision.ray_angle(i);
+			let p1 = p0 + Matrix2::from_angle(Rad(angle)) * (-Position::unit_y()) * range;
+			sight.push(if self.topology.wraps() {
+				self.cast_around(id, p0, p1)
+			} else {
+				self.cast(id, p0, p1)
+			});
+		}
+		sight
+	}
+
+	/// The first thing hit by a ray from `p0` to `p1`, ignoring the agent `id` itself
+	fn cast(&self, id: Id, p0: Position, p1: Position) -> Sight {
+		let mut nearest = Sight::nothing();
+		{
+			let mut callback = |body_h: b2::BodyHandle, _: b2::FixtureHandle, _: &b2::Vec2, _: &b2::Vec2, fraction: f32| {
+				let other = self.world.body(body_h).user_data().id();
+				if other == id {
+					// see through ourselves
+					-1.
+				} else {
+					let seen = if other == EXTENT_ID {
+						Seen::Wall
+					} else {
+						Seen::Agent(other.type_of())
+					};
+					nearest = Sight { distance: fraction, seen };
+					// clip the ray, so that only nearer hits are reported from now on
+					fraction
+				}
+			};
+			self.world.ray_cast(&mut callback, &Self::p2v(&p0), &Self::p2v(&p1));
+		}
+		nearest
+	}
+
+	/// Like `cast`, but a ray leaving the world comes back from the opposite side,
+	/// the way bodies do on a torus: each leg is cast inside the extent, then moved across
+	fn cast_around(&self, id: Id, p0: Position, p1: Position) -> Sight {
+		let extent = self.initial_extent;
+		let (width, height) = (extent.max.x - extent.min.x, extent.max.y - extent.min.y);
+		let (mut start, mut end) = (p0, p1);
+		// how much of the whole ray was covered by the previous legs
+		let mut covered = 0.;
+		// a ray crosses one vertical and one horizontal edge at most, unless it is longer than the world
+		for _ in 0..MAX_RAY_LEGS {
+			let d = end - start;
+			let exit = |from: f32, to: f32, min: f32, max: f32| -> f32 {
+				if to > max && to > from {
+					((max - from) / (to - from)).max(0.)
+				} else if to < min && to < from {
+					((min - from) / (to - from)).max(0.)
+				} else {
+					1.
+				}
+			};
+			let tx = exit(start.x, end.x, extent.min.x, extent.max.x);
+			let ty = exit(start.y, end.y, extent.min.y, extent.max.y);
+			let t = tx.min(ty);
+			let leg_end = start + d * t;
+			let span = (1. - covered) * t;
+			// box2d does not accept rays of zero length
+			if (leg_end - start).magnitude2() > 0. {
+				let hit = self.cast(id, start, leg_end);
+				if hit.seen != Seen::Nothing {
+					return Sight { distance: covered + hit.distance * span, seen: hit.seen };
+				}
+			}
+			if t >= 1. {
+				break;
+			}
+			covered += span;
+			let shift = Position::new(
+				if tx <= t { -d.x.signum() * width } else { 0. },
+				if ty <= t { -d.y.signum() * height } else { 0. });
+			start = leg_end + shift;
+			end = end + shift;
+		}
+		Sight::nothing()
+	}
+
 	pub fn pick(&self, pos: Position) -> Option<Id> {
 		let point = Self::p2v(&pos);
 		let eps = PICK_EPS;
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::mem;
+use std::slice;
+use std::str::FromStr;
 use std::f32;
 use num::Float;
 use num::FromPrimitive;
@@ -12,6 +15,8 @@ use backend::obj::*;
 use backend::world::gen::Dna;
 use backend::world::segment;
 use backend::world::segment::Segment;
+use backend::world::sensor;
+use backend::world::pheromone;
 
 #[repr(packed)]
 #[derive(Eq, Hash, PartialEq, Clone, Copy, Debug)]
@@ -76,7 +81,7 @@ impl TypedAgent for Id {
 }
 
 enum_from_primitive! {
-	#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+	#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 	pub enum AgentType {
 		Minion,
 		Spore,
@@ -124,14 +129,16 @@ impl AgentType {
 	}
 }
 
-// for simplicity, inputs = intermediate = output
-pub const N_WEIGHTS: usize = 4;
+// one input per sensor channel, while the number of hidden units is in the genes
+pub const N_INPUTS: usize = sensor::N_CHANNELS;
+pub const N_OUTPUTS: usize = 4;
 
-pub type InputVector<S> = [S; N_WEIGHTS];
-pub type OutputVector<S> = [S; N_WEIGHTS];
+pub type InputVector<S> = [S; N_INPUTS];
+pub type OutputVector<S> = [S; N_OUTPUTS];
 
-pub type WeightVector<T> = [T; N_WEIGHTS];
-pub type WeightMatrix<T> = [WeightVector<T>; N_WEIGHTS];
+/// Weights of a layer are stored by row, one row per output of the layer
+pub type WeightVector<T> = Vec<T>;
+pub type WeightMatrix<T> = Vec<WeightVector<T>>;
 
 #[derive(Clone, Default, Debug)]
 pub struct GBrain<T: Copy + Default> {
@@ -141,6 +148,10 @@ pub struct GBrain<T: Copy + Default> {
 	pub fear: T,
 	pub rest: T,
 	pub thrust: T,
+	/// how fast the weights change during the lifetime, zero for a brain which never learns
+	pub learning_rate: T,
+	pub vision: sensor::Vision,
+	pub gland: pheromone::Gland,
 	pub weights_in: WeightMatrix<T>,
 	pub weights_hidden: WeightMatrix<T>,
 	pub weights_out: WeightMatrix<T>,
@@ -174,10 +185,10 @@ pub trait Layer<S, T>
 		x / (S::one() + x.abs())
 	}
 
-	fn layer(inputs: &[S], weights: &[WeightVector<T>]) -> OutputVector<S> {
-		let mut outputs = [S::zero(); N_WEIGHTS];
+	fn layer(inputs: &[S], weights: &[WeightVector<T>]) -> Vec<S> {
+		let mut outputs = vec![S::zero(); weights.len()];
 		for i in 0..outputs.len() {
-			for j in 0..inputs.len() {
+			for j in 0..inputs.len().min(weights[i].len()) {
 				outputs[i] = outputs[i] + inputs[j] * weights[i][j].into();
 			}
 			outputs[i] = Self::activation(outputs[i])
@@ -186,6 +197,71 @@ pub trait Layer<S, T>
 	}
 }
 
+impl<T: Copy + Default> GBrain<T> {
+	pub fn hidden_units(&self) -> usize {
+		self.weights_hidden.len()
+	}
+}
+
+/// How brains change during the lifetime of their owner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Plasticity {
+	/// weights are fixed at birth, behaviour only changes through evolution
+	Off,
+	/// weights strengthen along the inputs and outputs which fire together
+	Hebbian,
+	/// as `Hebbian`, but only when rewarded by food
+	Reward,
+}
+
+impl Default for Plasticity {
+	fn default() -> Self {
+		Plasticity::Off
+	}
+}
+
+impl FromStr for Plasticity {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"off" => Ok(Plasticity::Off),
+			"hebbian" => Ok(Plasticity::Hebbian),
+			"reward" => Ok(Plasticity::Reward),
+			_ => Err(format!("unknown learning mode '{}', expected off, hebbian or reward", s)),
+		}
+	}
+}
+
+impl<T> GBrain<T>
+	where
+		T: Copy + Default + Float,
+{
+	/// Oja's rule: each weight moves towards the correlation of its input and output,
+	/// while the decay term keeps it bounded.
+	fn hebbian(weights: &mut WeightMatrix<T>, inputs: &[T], outputs: &[T], rate: T) {
+		for (row, &y) in weights.iter_mut().zip(outputs.iter()) {
+			for (w, &x) in row.iter_mut().zip(inputs.iter()) {
+				*w = *w + rate * y * (x - y * *w);
+			}
+		}
+	}
+
+	/// Updates all the layers from the activations produced by `input`, `modulation` scales the learning rate
+	pub fn learn(&mut self, input: &[T], modulation: T) {
+		let rate = self.learning_rate * modulation;
+		if rate == T::zero() {
+			return;
+		}
+		let output_in = <Self as Layer<T, T>>::layer(input, &self.weights_in);
+		let output_hidden = <Self as Layer<T, T>>::layer(&output_in, &self.weights_hidden);
+		let output_out = <Self as Layer<T, T>>::layer(&output_hidden, &self.weights_out);
+		Self::hebbian(&mut self.weights_in, input, &output_in, rate);
+		Self::hebbian(&mut self.weights_hidden, &output_in, &output_hidden, rate);
+		Self::hebbian(&mut self.weights_out, &output_hidden, &output_out, rate);
+	}
+}
+
 impl<S, T> Layer<S, T> for GBrain<T>
 	where
 		T: Copy + Default,
@@ -220,7 +296,11 @@ impl<T, S> Personality<S> for GBrain<T>
 		let output_in = Self::layer(input, &self.weights_in);
 		let output_hidden = Self::layer(&output_in, &self.weights_hidden);
 		let output_out = Self::layer(&output_hidden, &self.weights_out);
-		output_out
+		let mut outputs = [S::zero(); N_OUTPUTS];
+		for (o, v) in outputs.iter_mut().zip(output_out.into_iter()) {
+			*o = v;
+		}
+		outputs
 	}
 }
 
@@ -249,6 +329,34 @@ pub struct Limits {
 	max_energy: f32,
 }
 
+/// Parents, generation and birth time of an agent
+#[derive(Clone, Debug, Default)]
+pub struct Lineage {
+	pub mother: Option<Id>,
+	pub father: Option<Id>,
+	pub generation: usize,
+	pub birth: Seconds,
+}
+
+impl Lineage {
+	pub fn founder(birth: Seconds) -> Self {
+		Lineage {
+			birth,
+			..Default::default()
+		}
+	}
+
+	/// Lineage of the offspring of this agent, born at the given time
+	pub fn offspring(&self, mother: Id, birth: Seconds) -> Self {
+		Lineage {
+			mother: Some(mother),
+			father: None,
+			generation: self.generation + 1,
+			birth,
+		}
+	}
+}
+
 pub struct InitialState {
 	pub transform: Transform,
 	pub motion: Motion,
@@ -256,6 +364,9 @@ pub struct InitialState {
 	pub maturity: Option<f32>,
 	pub age_seconds: Seconds,
 	pub age_frames: usize,
+	pub lineage: Lineage,
+	/// the body of agents whose shape is not in their genes
+	pub shape: Option<Shape>,
 }
 
 impl Default for InitialState {
@@ -267,6 +378,8 @@ impl Default for InitialState {
 			maturity: None,
 			age_seconds: seconds(0.0),
 			age_frames: 0,
+			lineage: Lineage::default(),
+			shape: None,
 		}
 	}
 }
@@ -283,6 +396,9 @@ pub struct State {
 	limits: Limits,
 	foreign_dna: Option<Dna>,
 	trajectory: util::History<Position>,
+	fasting: SecondsValue,
+	reward: f32,
+	sight: Vec<sensor::Sight>,
 }
 
 impl State {
@@ -311,6 +427,21 @@ impl State {
 		self.energy = energy;
 	}
 
+	pub fn restore_lifecycle(&mut self, lifecycle: Hourglass) {
+		self.lifecycle = lifecycle;
+	}
+
+	/// Time since the last meal, and the reward not yet collected by learning
+	pub fn restore_hunger(&mut self, fasting: SecondsValue, reward: f32) {
+		self.fasting = fasting;
+		self.reward = reward;
+	}
+
+	/// Reward not yet collected by learning
+	pub fn reward(&self) -> f32 {
+		self.reward
+	}
+
 	pub fn consume(&mut self, q: f32) -> bool {
 		if self.energy >= q {
 			self.energy -= q;
@@ -331,6 +462,31 @@ impl State {
 
 	pub fn absorb(&mut self, q: f32) {
 		self.energy = self.limits.max_energy.min(self.energy + q);
+		self.fasting = 0.;
+		self.reward += q / self.limits.max_energy;
+	}
+
+	/// What each vision ray hit in the last frame
+	pub fn sight(&self) -> &[sensor::Sight] {
+		&self.sight
+	}
+
+	pub fn see(&mut self, sight: Vec<sensor::Sight>) {
+		self.sight = sight;
+	}
+
+	/// Energy absorbed since the last call, as a fraction of the maximum
+	pub fn collect_reward(&mut self) -> f32 {
+		mem::replace(&mut self.reward, 0.)
+	}
+
+	/// Time since the last meal, or since birth
+	pub fn fasting(&self) -> SecondsValue {
+		self.fasting
+	}
+
+	pub fn fast(&mut self, dt: Seconds) {
+		self.fasting += dt.get();
 	}
 
 	pub fn grow_by(&mut self, q: f32) {
@@ -409,6 +565,14 @@ impl State {
 		self.trajectory.push(position.clone())
 	}
 
+	/// Replaces the trajectory, given newest first like `trajectory()` returns it
+	pub fn restore_trajectory(&mut self, trajectory: &[Position]) {
+		self.trajectory.clear();
+		for position in trajectory.iter().rev() {
+			self.trajectory.push(*position);
+		}
+	}
+
 	pub fn trajectory(&self) -> Box<[Position]> {
 		self.trajectory
 			.into_iter()
@@ -423,6 +587,7 @@ pub struct Agent {
 	brain: Brain,
 	dna: Dna,
 	gender: u8,
+	pub lineage: Lineage,
 	pub state: State,
 	pub segments: Box<[Segment]>,
 }
@@ -490,6 +655,10 @@ impl Agent {
 		&self.brain
 	}
 
+	pub fn brain_mut(&mut self) -> &mut Brain {
+		&mut self.brain
+	}
+
 	pub fn first_segment(&self, flags: segment::Flags) -> Option<Segment> {
 		self.segments
 			.iter()
@@ -517,13 +686,156 @@ impl Agent {
 				limits: Limits { max_energy },
 				foreign_dna: None,
 				trajectory: util::History::new(600),
+				fasting: 0.,
+				reward: 0.,
+				sight: Vec::new(),
 			},
 			brain: brain.clone(),
 			gender,
+			lineage: Lineage::default(),
 			dna: dna.clone(),
 			segments,
 		}
 	}
 }
 
-pub type AgentMap = HashMap<Id, Agent>;
+/// Agents of a swarm, iterated in insertion order and looked up by id in constant time.
+/// Removing an agent leaves a hole in its slot, holes are reclaimed in bulk by compaction,
+/// so the relative order of the survivors never changes.
+#[derive(Clone, Default)]
+pub struct AgentMap {
+	slots: Vec<Option<(Id, Agent)>>,
+	index: HashMap<Id, usize>,
+	holes: usize,
+}
+
+pub struct Iter<'a> {
+	slots: slice::Iter<'a, Option<(Id, Agent)>>,
+}
+
+pub struct IterMut<'a> {
+	slots: slice::IterMut<'a, Option<(Id, Agent)>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = (&'a Id, &'a Agent);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while let Some(slot) = self.slots.next() {
+			if let &Some((ref id, ref agent)) = slot {
+				return Some((id, agent));
+			}
+		}
+		None
+	}
+}
+
+impl<'a> Iterator for IterMut<'a> {
+	type Item = (&'a Id, &'a mut Agent);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while let Some(slot) = self.slots.next() {
+			if let &mut Some((ref id, ref mut agent)) = slot {
+				return Some((id, agent));
+			}
+		}
+		None
+	}
+}
+
+impl AgentMap {
+	pub fn new() -> Self {
+		AgentMap {
+			slots: Vec::new(),
+			index: HashMap::new(),
+			holes: 0,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.index.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.index.is_empty()
+	}
+
+	pub fn clear(&mut self) {
+		self.slots.clear();
+		self.index.clear();
+		self.holes = 0;
+	}
+
+	#[allow(unused)]
+	pub fn contains_key(&self, id: &Id) -> bool {
+		self.index.contains_key(id)
+	}
+
+	pub fn get(&self, id: &Id) -> Option<&Agent> {
+		match self.index.get(id) {
+			Some(&slot) => match self.slots[slot] {
+				// a slot is only ever reachable through the id it was created for
+				Some((slot_id, ref agent)) if slot_id == *id => Some(agent),
+				_ => None,
+			},
+			None => None,
+		}
+	}
+
+	pub fn get_mut(&mut self, id: &Id) -> Option<&mut Agent> {
+		match self.index.get(id) {
+			Some(&slot) => match self.slots[slot] {
+				Some((slot_id, ref mut agent)) if slot_id == *id => Some(agent),
+				_ => None,
+			},
+			None => None,
+		}
+	}
+
+	/// Appends the agent, or replaces it in place if its id is already live
+	pub fn insert(&mut self, agent: Agent) -> Option<Agent> {
+		let id = agent.id;
+		let existing = self.index.get(&id).cloned();
+		match existing {
+			Some(slot) => mem::replace(&mut self.slots[slot], Some((id, agent)))
+				.map(|(_, previous)| previous),
+			None => {
+				self.index.insert(id, self.slots.len());
+				self.slots.push(Some((id, agent)));
+				None
+			}
+		}
+	}
+
+	pub fn remove(&mut self, id: &Id) -> Option<Agent> {
+		let removed = self.index.remove(id)
+			.and_then(|slot| self.slots[slot].take())
+			.map(|(_, agent)| agent);
+		if removed.is_some() {
+			self.holes += 1;
+			if self.holes > self.index.len() {
+				self.compact();
+			}
+		}
+		removed
+	}
+
+	pub fn iter(&self) -> Iter {
+		Iter { slots: self.slots.iter() }
+	}
+
+	pub fn iter_mut(&mut self) -> IterMut {
+		IterMut { slots: self.slots.iter_mut() }
+	}
+
+	fn compact(&mut self) {
+		self.slots.retain(|slot| slot.is_some());
+		self.index.clear();
+		for (i, slot) in self.slots.iter().enumerate() {
+			if let &Some((id, _)) = slot {
+				self.index.insert(id, i);
+			}
+		}
+		self.holes = 0;
+	}
+}
@@ -0,0 +1,63 @@
+use serde_json;
+use cgmath::InnerSpace;
+use backend::obj::Shape;
+use core::geometry::*;
+use core::geometry::Transform;
+
+/// A static body of the arena, spawned as a `Prop`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Obstacle {
+	/// a straight segment of wall, or of a maze
+	Wall { from: (f32, f32), to: (f32, f32), thickness: f32 },
+	Rock { x: f32, y: f32, radius: f32 },
+	/// a regular polygon with the given number of sides, from 3 to 8
+	Boulder { x: f32, y: f32, radius: f32, sides: u8 },
+	/// a rectangle, turned counter-clockwise by `angle` radians
+	Block { x: f32, y: f32, width: f32, height: f32, angle: f32 },
+}
+
+impl Obstacle {
+	pub fn shape(&self) -> Shape {
+		match *self {
+			Obstacle::Wall { from, to, thickness } => {
+				let length = (Position::new(to.0, to.1) - Position::new(from.0, from.1)).magnitude();
+				let radius = (length * 0.5).max(thickness * 0.5);
+				Shape::new_box(radius, thickness * 0.5 / radius)
+			}
+			Obstacle::Rock { radius, .. } => Shape::new_ball(radius),
+			Obstacle::Boulder { radius, sides, .. } => Shape::new_poly(sides.max(3).min(8) as i8, radius),
+			Obstacle::Block { width, height, .. } => {
+				let radius = height * 0.5;
+				Shape::new_box(radius, width * 0.5 / radius)
+			}
+		}
+	}
+
+	/// Where the center of the obstacle is, and which way its shape is turned
+	pub fn transform(&self) -> Transform {
+		match *self {
+			Obstacle::Wall { from, to, .. } => {
+				let (p0, p1) = (Position::new(from.0, from.1), Position::new(to.0, to.1));
+				let d = p1 - p0;
+				// boxes are long along their y axis
+				Transform::new((p0 + p1) * 0.5, f32::atan2(-d.x, d.y))
+			}
+			Obstacle::Rock { x, y, .. } | Obstacle::Boulder { x, y, .. } =>
+				Transform::from_position(Position::new(x, y)),
+			Obstacle::Block { x, y, angle, .. } => Transform::new(Position::new(x, y), angle),
+		}
+	}
+}
+
+/// The static layout of a habitat: walls, rocks and maze segments
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Arena {
+	pub obstacles: Vec<Obstacle>,
+}
+
+impl Arena {
+	pub fn parse(data: &[u8]) -> serde_json::Result<Self> {
+		serde_json::from_slice(data)
+	}
+}
@@ -0,0 +1,214 @@
+use app::constants::*;
+use backend::obj::*;
+use backend::world::gen::*;
+use backend::world::phen::AgentBuilder;
+use backend::world::segment::Flags;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Side {
+	Left,
+	Right,
+	Middle,
+}
+
+/// A segment and everything growing out of it
+struct Limb {
+	shape: Shape,
+	flags: Flags,
+	branches: Vec<(isize, Limb)>,
+}
+
+/// Where a limb grows on its parent. Mirrored limbs are grown on both sides,
+/// with the same shape, role and branches.
+struct Placement {
+	offset: isize,
+	side: Side,
+	mirrored: bool,
+	limb: Limb,
+}
+
+/// The body of a minion, decoded from the genome as a small recursive growth program.
+/// Every choice consumes genes: symmetry, number and position of limbs, their shapes,
+/// roles and branches, so the whole topology is heritable.
+/// The segment budget bounds the size of the body, and only shapes which are known
+/// to be valid for box2d are ever produced.
+pub struct BodyPlan {
+	head: Limb,
+	head_limbs: Vec<Placement>,
+	torso_limbs: Vec<Placement>,
+	belly: Vec<(Shape, Vec<Placement>)>,
+	tail: Limb,
+}
+
+struct Decoder<'a> {
+	gen: &'a mut Genome,
+	symmetric: bool,
+	budget: usize,
+}
+
+impl<'a> Decoder<'a> {
+	fn take(&mut self, cost: usize) -> bool {
+		if self.budget >= cost {
+			self.budget -= cost;
+			true
+		} else {
+			false
+		}
+	}
+
+	fn role(&mut self) -> Flags {
+		let role = match self.gen.next_integer::<u8>(0, 4) {
+			0 => Flags::ARM | Flags::ACTUATOR | Flags::RUDDER,
+			1 => Flags::LEG | Flags::ACTUATOR | Flags::THRUSTER,
+			2 => Flags::ACTUATOR | Flags::BRAKE,
+			3 => Flags::STORAGE,
+			_ => Flags::MOUTH,
+		};
+		if self.gen.next_bool() { role | Flags::JOINT } else { role }
+	}
+
+	fn limb_shape(&mut self) -> Shape {
+		match self.gen.next_integer::<u8>(0, 2) {
+			0 => self.gen.star(),
+			1 => self.gen.triangle(),
+			_ => self.gen.vbar(),
+		}
+	}
+
+	fn limb(&mut self, depth: usize, cost: usize) -> Option<Limb> {
+		if !self.take(cost) {
+			return None;
+		}
+		let shape = self.limb_shape();
+		let flags = self.role();
+		let mut branches = Vec::new();
+		if depth < MAX_LIMB_DEPTH {
+			let n = self.gen.next_integer::<isize>(0, 2);
+			for k in 0..n {
+				let offset = shape.mid() + 2 * k - (n - 1);
+				match self.limb(depth + 1, cost) {
+					Some(branch) => branches.push((offset, branch)),
+					None => break,
+				}
+			}
+		}
+		Some(Limb { shape, flags, branches })
+	}
+
+	fn placement(&mut self, offset: isize) -> Option<Placement> {
+		let (side, mirrored) = if self.symmetric {
+			(Side::Right, true)
+		} else if self.gen.next_bool() {
+			(Side::Left, false)
+		} else {
+			(Side::Right, false)
+		};
+		let cost = if mirrored { 2 } else { 1 };
+		self.limb(0, cost).map(|limb| Placement { offset, side, mirrored, limb })
+	}
+
+	fn placements(&mut self, n: isize, parent: &Shape) -> Vec<Placement> {
+		let mut placements = Vec::new();
+		for _ in 0..n {
+			let offset = self.gen.next_integer::<isize>(1, ::std::cmp::max(1, parent.mid() - 1));
+			match self.placement(offset) {
+				Some(placement) => placements.push(placement),
+				None => break,
+			}
+		}
+		placements
+	}
+}
+
+fn contains_any(limb: &Limb, flags: Flags) -> bool {
+	limb.flags.intersects(flags) || limb.branches.iter().any(|&(_, ref b)| contains_any(b, flags))
+}
+
+impl BodyPlan {
+	pub fn decode(gen: &mut Genome, torso: &Shape) -> Self {
+		let symmetric = gen.next_integer::<u8>(0, 3) != 0;
+		let mut decoder = Decoder {
+			gen,
+			symmetric,
+			// torso, head and tail are always there
+			budget: MAX_BODY_SEGMENTS - 3,
+		};
+
+		let head = Limb {
+			shape: decoder.gen.iso_triangle(),
+			flags: Flags::HEAD | Flags::MOUTH | Flags::SENSOR | Flags::TRACKER,
+			branches: Vec::new(),
+		};
+		let n = decoder.gen.next_integer::<isize>(0, 1);
+		let mut head_limbs = decoder.placements(n, &head.shape);
+		for placement in head_limbs.iter_mut() {
+			placement.limb.flags |= Flags::HEAD;
+		}
+
+		let n = decoder.gen.next_integer::<isize>(0, 2);
+		let torso_limbs = decoder.placements(n, torso);
+
+		let mut belly = Vec::new();
+		while belly.len() < MAX_BELLY_SEGMENTS && decoder.gen.next_integer::<u8>(0, 3) == 0 && decoder.take(1) {
+			let shape = decoder.gen.any_poly();
+			let n = if shape.length() > 6 { decoder.gen.next_integer::<isize>(0, 1) } else { 0 };
+			let limbs = decoder.placements(n, &shape);
+			belly.push((shape, limbs));
+		}
+
+		let mut tail = Limb {
+			shape: decoder.gen.vbar(),
+			flags: Flags::TAIL | Flags::ACTUATOR | decoder.role(),
+			branches: Vec::new(),
+		};
+		// whatever the genes say, a minion must be able to move
+		let motile = head_limbs.iter()
+			.chain(torso_limbs.iter())
+			.chain(belly.iter().flat_map(|&(_, ref limbs)| limbs.iter()))
+			.any(|p| contains_any(&p.limb, Flags::THRUSTER));
+		if !motile {
+			tail.flags |= Flags::THRUSTER;
+		}
+
+		BodyPlan { head, head_limbs, torso_limbs, belly, tail }
+	}
+
+	fn grow(builder: &mut AgentBuilder, parent: SegmentIndex, offset: isize, limb: &Limb, side: Side) {
+		let index = match side {
+			Side::Left => builder.addl(parent, -offset, &limb.shape, limb.flags),
+			Side::Right => builder.addr(parent, offset, &limb.shape, limb.flags),
+			Side::Middle => builder.add(parent, offset, &limb.shape, limb.flags),
+		}.index();
+		for &(branch_offset, ref branch) in &limb.branches {
+			Self::grow(builder, index, branch_offset, branch, side);
+		}
+	}
+
+	fn grow_all(builder: &mut AgentBuilder, parent: SegmentIndex, placements: &[Placement]) {
+		for placement in placements {
+			Self::grow(builder, parent, placement.offset, &placement.limb, placement.side);
+			if placement.mirrored {
+				Self::grow(builder, parent, placement.offset, &placement.limb, Side::Left);
+			}
+		}
+	}
+
+	/// Grows the body on the torso, which must be the last segment added to the builder
+	pub fn build(&self, builder: &mut AgentBuilder, torso_shape: &Shape) {
+		let torso = builder.index();
+		Self::grow_all(builder, torso, &self.torso_limbs);
+
+		Self::grow(builder, torso, 0, &self.head, Side::Middle);
+		let head = builder.index();
+		Self::grow_all(builder, head, &self.head_limbs);
+
+		let mut belly = torso;
+		let mut belly_mid = torso_shape.mid();
+		for &(ref shape, ref limbs) in &self.belly {
+			belly = builder.add(belly, belly_mid, shape, Flags::STORAGE | Flags::JOINT).index();
+			belly_mid = shape.mid();
+			Self::grow_all(builder, belly, limbs);
+		}
+		Self::grow(builder, belly, belly_mid, &self.tail, Side::Middle);
+	}
+}
@@ -0,0 +1,27 @@
+use serde_json;
+use backend::world::flow;
+use backend::world::feeder::FeederSpec;
+use backend::world::species;
+use backend::world::topology::Topology;
+
+/// Settings of the arena, read from `WORLD_CONFIG_FILE` in the resources when a world is created.
+/// Every section is optional, and falls back to the defaults in `app::constants`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct WorldConfig {
+	pub flow: flow::Flow,
+	/// the feeders of the arena, seven on a circle if none are given
+	pub feeders: Option<Vec<FeederSpec>>,
+	/// resource file with the walls and rocks of the arena, open water if none is given
+	pub arena: Option<String>,
+	/// what happens at the edge of the world
+	pub topology: Topology,
+	/// how minions are grouped into species
+	pub species: species::Settings,
+}
+
+impl WorldConfig {
+	pub fn parse(data: &[u8]) -> serde_json::Result<Self> {
+		serde_json::from_slice(data)
+	}
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use serde_json;
+use serialize::base64::{self, ToBase64};
+use backend::obj::*;
+use backend::world::agent;
+use backend::world::gen::*;
+use backend::world::phen;
+use backend::world::segment;
+use backend::world::sensor;
+
+type Matrix = Vec<Vec<f32>>;
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind")]
+pub enum ShapeReport {
+	Ball { radius: f32 },
+	Box { radius: f32, ratio: f32 },
+	Star { radius: f32, n: u8, ratio1: f32, ratio2: f32 },
+	Poly { radius: f32, n: i8 },
+	Triangle { radius: f32, angle1: f32, angle2: f32 },
+}
+
+impl<'a> From<&'a Shape> for ShapeReport {
+	fn from(shape: &'a Shape) -> Self {
+		match shape {
+			&Shape::Ball { radius } => ShapeReport::Ball { radius },
+			&Shape::Box { radius, ratio } => ShapeReport::Box { radius, ratio },
+			&Shape::Star { radius, n, ratio1, ratio2 } => ShapeReport::Star { radius, n, ratio1, ratio2 },
+			&Shape::Poly { radius, n } => ShapeReport::Poly { radius, n },
+			&Shape::Triangle { radius, angle1, angle2 } => ShapeReport::Triangle { radius, angle1, angle2 },
+		}
+	}
+}
+
+impl fmt::Display for ShapeReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&ShapeReport::Ball { radius } =>
+				write!(f, "ball r={:.3}", radius),
+			&ShapeReport::Box { radius, ratio } =>
+				write!(f, "box r={:.3} ratio={:.3}", radius, ratio),
+			&ShapeReport::Star { radius, n, ratio1, ratio2 } =>
+				write!(f, "star n={} r={:.3} ratio1={:.3} ratio2={:.3}", n, radius, ratio1, ratio2),
+			&ShapeReport::Poly { radius, n } =>
+				write!(f, "poly n={} r={:.3}", n, radius),
+			&ShapeReport::Triangle { radius, angle1, angle2 } =>
+				write!(f, "triangle r={:.3} angle1={:.3} angle2={:.3}", radius, angle1, angle2),
+		}
+	}
+}
+
+#[derive(Serialize, Debug)]
+pub struct SegmentReport {
+	pub index: SegmentIndex,
+	pub shape: ShapeReport,
+	pub flags: Vec<String>,
+	/// parent segment and vertex of the parent this segment is attached to
+	pub attached_to: Option<(SegmentIndex, AttachmentIndex)>,
+}
+
+/// What a minion genome develops into, decoded along the same path as `Phenotype::develop`
+#[derive(Serialize, Debug)]
+pub struct GenomeReport {
+	pub dna: String,
+	pub bits: usize,
+	pub gender: u8,
+	pub hue: f32,
+	pub hunger: f32,
+	pub haste: f32,
+	pub prudence: f32,
+	pub fear: f32,
+	pub rest: f32,
+	pub thrust: f32,
+	pub learning_rate: f32,
+	pub vision_rays: usize,
+	pub vision_fov: f32,
+	pub gland_channel: usize,
+	pub gland_rate: f32,
+	pub hidden_units: usize,
+	/// sensor channels, in the order of the columns of `weights_in`
+	pub inputs: Vec<String>,
+	pub weights_in: Matrix,
+	pub weights_hidden: Matrix,
+	pub weights_out: Matrix,
+	pub segments: Vec<SegmentReport>,
+}
+
+const FLAG_NAMES: &'static [(segment::Flags, &'static str)] = &[
+	(segment::Flags::SENSOR, "SENSOR"),
+	(segment::Flags::ACTUATOR, "ACTUATOR"),
+	(segment::Flags::JOINT, "JOINT"),
+	(segment::Flags::MOUTH, "MOUTH"),
+	(segment::Flags::HEAD, "HEAD"),
+	(segment::Flags::LEG, "LEG"),
+	(segment::Flags::ARM, "ARM"),
+	(segment::Flags::CORE, "CORE"),
+	(segment::Flags::STORAGE, "STORAGE"),
+	(segment::Flags::TAIL, "TAIL"),
+	(segment::Flags::TRACKER, "TRACKER"),
+	(segment::Flags::LEFT, "LEFT"),
+	(segment::Flags::RIGHT, "RIGHT"),
+	(segment::Flags::MIDDLE, "MIDDLE"),
+	(segment::Flags::THRUSTER, "THRUSTER"),
+	(segment::Flags::RUDDER, "RUDDER"),
+	(segment::Flags::BRAKE, "BRAKE"),
+];
+
+fn flag_names(flags: segment::Flags) -> Vec<String> {
+	FLAG_NAMES.iter()
+		.filter(|&&(flag, _)| flags.contains(flag))
+		.map(|&(_, name)| name.to_owned())
+		.collect()
+}
+
+pub fn explain_minion(dna: &Dna) -> GenomeReport {
+	let agent = phen::develop_minion(dna, agent::InitialState::default());
+	let brain = agent.brain();
+	GenomeReport {
+		dna: dna.to_base64(base64::STANDARD),
+		bits: dna.len() * 8,
+		gender: agent.gender(),
+		hue: agent.segments()[0].livery.hue,
+		hunger: brain.hunger,
+		haste: brain.haste,
+		prudence: brain.prudence,
+		fear: brain.fear,
+		rest: brain.rest,
+		thrust: brain.thrust,
+		learning_rate: brain.learning_rate,
+		vision_rays: brain.vision.rays,
+		vision_fov: brain.vision.fov,
+		gland_channel: brain.gland.channel,
+		gland_rate: brain.gland.rate,
+		hidden_units: brain.hidden_units(),
+		inputs: sensor::Channel::all().iter().map(|c| c.name()).collect(),
+		weights_in: brain.weights_in.clone(),
+		weights_hidden: brain.weights_hidden.clone(),
+		weights_out: brain.weights_out.clone(),
+		segments: agent.segments()
+			.iter()
+			.map(|segment| SegmentReport {
+				index: segment.index,
+				shape: (&segment.mesh.shape).into(),
+				flags: flag_names(segment.flags),
+				attached_to: segment.attached_to.map(|a| (a.index, a.attachment_point)),
+			})
+			.collect(),
+	}
+}
+
+pub fn to_json(reports: &[GenomeReport]) -> serde_json::Result<String> {
+	serde_json::to_string_pretty(reports)
+}
+
+fn fmt_matrix(f: &mut fmt::Formatter, name: &str, m: &Matrix) -> fmt::Result {
+	writeln!(f, "{}:", name)?;
+	for row in m.iter() {
+		write!(f, "\t")?;
+		for w in row.iter() {
+			write!(f, " {:>7.3}", w)?;
+		}
+		writeln!(f, "")?;
+	}
+	Ok(())
+}
+
+impl fmt::Display for GenomeReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "dna: {} ({} bits)", self.dna, self.bits)?;
+		writeln!(f, "gender: {}", self.gender)?;
+		writeln!(f, "hue: {:.3}", self.hue)?;
+		writeln!(f, "hunger: {:.3}", self.hunger)?;
+		writeln!(f, "haste: {:.3}", self.haste)?;
+		writeln!(f, "prudence: {:.3}", self.prudence)?;
+		writeln!(f, "fear: {:.3}", self.fear)?;
+		writeln!(f, "rest: {:.3}", self.rest)?;
+		writeln!(f, "thrust: {:.3}", self.thrust)?;
+		writeln!(f, "learning rate: {:.4}", self.learning_rate)?;
+		writeln!(f, "vision: {} rays over {:.1} degrees", self.vision_rays, self.vision_fov.to_degrees())?;
+		writeln!(f, "gland: {:.3}/s into channel {}", self.gland_rate, self.gland_channel)?;
+		writeln!(f, "hidden units: {}", self.hidden_units)?;
+		writeln!(f, "inputs: {}", self.inputs.join(" "))?;
+		fmt_matrix(f, "weights_in", &self.weights_in)?;
+		fmt_matrix(f, "weights_hidden", &self.weights_hidden)?;
+		fmt_matrix(f, "weights_out", &self.weights_out)?;
+		writeln!(f, "segments:")?;
+		for segment in &self.segments {
+			write!(f, "\t{:>2} {}", segment.index, segment.shape)?;
+			if let Some((parent, point)) = segment.attached_to {
+				write!(f, " on {}@{}", parent, point)?;
+			}
+			writeln!(f, " [{}]", segment.flags.join(" "))?;
+		}
+		Ok(())
+	}
+}
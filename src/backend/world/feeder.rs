@@ -0,0 +1,243 @@
+use std::f32::consts;
+use std::ops::Range;
+use cgmath::InnerSpace;
+use app::constants::*;
+use backend::obj::Transformable;
+use core::clock::SecondsValue;
+use core::geometry::*;
+use core::geometry::Transform;
+
+pub type FeederId = usize;
+
+/// How a feeder spreads the food around it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Emission {
+	/// each resource is shot at the given angle clockwise from the previous one
+	CW(Angle),
+	/// each resource is shot at the given angle counter-clockwise from the previous one
+	CCW(Angle),
+	Random,
+}
+
+/// A route a feeder travels along, looping through its waypoints
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Path {
+	pub waypoints: Vec<(f32, f32)>,
+	/// in meters per second
+	pub speed: f32,
+}
+
+/// Emits `count` resources at once every `period` seconds, on top of the regular emissions
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Burst {
+	pub period: SecondsValue,
+	pub count: usize,
+}
+
+/// A finite stock of food, one unit for each resource, which grows back over time
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+	pub capacity: f32,
+	/// units regained each second
+	pub regeneration: f32,
+}
+
+/// Definition of a feeder, as found in the world configuration
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct FeederSpec {
+	pub x: f32,
+	pub y: f32,
+	/// seconds between regular emissions
+	pub period: SecondsValue,
+	pub emission: Emission,
+	/// speed and spin of the emitted resources
+	pub velocity: f32,
+	pub spin: f32,
+	pub path: Option<Path>,
+	pub burst: Option<Burst>,
+	pub budget: Option<Budget>,
+}
+
+impl Default for FeederSpec {
+	fn default() -> Self {
+		FeederSpec {
+			x: 0.,
+			y: 0.,
+			period: EMITTER_PERIOD,
+			emission: Emission::Random,
+			velocity: EMITTER_VELOCITY,
+			spin: consts::PI,
+			path: None,
+			burst: None,
+			budget: None,
+		}
+	}
+}
+
+impl FeederSpec {
+	/// A plain feeder at the given position
+	pub fn at(position: Position) -> Self {
+		FeederSpec {
+			x: position.x,
+			y: position.y,
+			..Default::default()
+		}
+	}
+
+	/// The original arena: feeders evenly spaced on a circle, emitting at random
+	pub fn ring(n: usize, radius: f32) -> Vec<FeederSpec> {
+		(0..n).map(|i| {
+			let (s, c) = (consts::PI * 2. * (i as f32 / n as f32)).sin_cos();
+			FeederSpec::at(Position::new(c * radius, s * radius))
+		}).collect()
+	}
+}
+
+/// What changes in a feeder while it runs
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct FeederState {
+	/// seconds until the next regular emission
+	pub countdown: SecondsValue,
+	/// seconds until the next burst
+	pub burst_countdown: SecondsValue,
+	/// resources emitted so far, which sets the angle of `CW` and `CCW` emissions
+	pub emitted: usize,
+	/// food left in the budget
+	pub stock: f32,
+	/// index of the waypoint the feeder is heading to
+	pub waypoint: usize,
+}
+
+#[derive(Clone)]
+pub struct Feeder {
+	id: FeederId,
+	spec: FeederSpec,
+	state: FeederState,
+	transform: Transform,
+}
+
+impl Feeder {
+	pub fn new(id: FeederId, spec: FeederSpec) -> Self {
+		let state = FeederState {
+			countdown: spec.period,
+			burst_countdown: spec.burst.map(|b| b.period).unwrap_or(0.),
+			emitted: 0,
+			stock: spec.budget.map(|b| b.capacity).unwrap_or(0.),
+			waypoint: 0,
+		};
+		let position = Position::new(spec.x, spec.y);
+		Self::restore(id, spec, state, position)
+	}
+
+	/// A feeder in the middle of its life, as saved in a snapshot
+	pub fn restore(id: FeederId, spec: FeederSpec, state: FeederState, position: Position) -> Self {
+		Feeder {
+			id,
+			spec,
+			state,
+			transform: Transform::from_position(position),
+		}
+	}
+
+	pub fn id(&self) -> FeederId { self.id }
+
+	pub fn spec(&self) -> &FeederSpec { &self.spec }
+
+	pub fn state(&self) -> &FeederState { &self.state }
+
+	pub fn emission(&self) -> Emission { self.spec.emission }
+
+	pub fn velocity(&self) -> f32 { self.spec.velocity }
+
+	pub fn spin(&self) -> f32 { self.spec.spin }
+
+	/// How much food is left, from 0 to 1, always full for feeders without a budget
+	pub fn fullness(&self) -> f32 {
+		match self.spec.budget {
+			Some(budget) if budget.capacity > 0. => (self.state.stock / budget.capacity).min(1.),
+			Some(_) => 0.,
+			None => 1.,
+		}
+	}
+
+	fn travel(&mut self, dt: f32) {
+		let path = match self.spec.path {
+			Some(ref path) if !path.waypoints.is_empty() && path.speed > 0. => path,
+			_ => return,
+		};
+		let mut position = self.transform.position;
+		let mut distance = path.speed * dt;
+		// a fast feeder may go past several waypoints in a frame
+		for _ in 0..path.waypoints.len() {
+			let (x, y) = path.waypoints[self.state.waypoint % path.waypoints.len()];
+			let to = Position::new(x, y) - position;
+			let length = to.magnitude();
+			if length > distance {
+				position += to * (distance / length);
+				break;
+			}
+			position = Position::new(x, y);
+			distance -= length;
+			self.state.waypoint = (self.state.waypoint + 1) % path.waypoints.len();
+		}
+		self.transform.position = position;
+	}
+
+	/// Takes a unit of food from the budget, if there is any left
+	fn draw(&mut self) -> bool {
+		match self.spec.budget {
+			None => true,
+			Some(_) if self.state.stock >= 1. => {
+				self.state.stock -= 1.;
+				true
+			}
+			Some(_) => false,
+		}
+	}
+
+	/// Moves the feeder along its path and grows its budget back, then returns the sequence numbers
+	/// of the resources to emit in this frame, which set their angle for `CW` and `CCW` emissions
+	pub fn update(&mut self, dt: SecondsValue) -> Range<usize> {
+		self.travel(dt as f32);
+		if let Some(budget) = self.spec.budget {
+			self.state.stock = (self.state.stock + budget.regeneration * dt as f32).min(budget.capacity);
+		}
+		let mut due = 0;
+		if self.spec.period > 0. {
+			self.state.countdown -= dt;
+			while self.state.countdown <= 0. {
+				self.state.countdown += self.spec.period;
+				due += 1;
+			}
+		}
+		if let Some(burst) = self.spec.burst {
+			if burst.period > 0. {
+				self.state.burst_countdown -= dt;
+				while self.state.burst_countdown <= 0. {
+					self.state.burst_countdown += burst.period;
+					due += burst.count;
+				}
+			}
+		}
+		let first = self.state.emitted;
+		for _ in 0..due {
+			if !self.draw() {
+				break;
+			}
+			self.state.emitted += 1;
+		}
+		first..self.state.emitted
+	}
+}
+
+impl Transformable for Feeder {
+	fn transform(&self) -> &Transform {
+		&self.transform
+	}
+	fn transform_to(&mut self, t: Transform) {
+		self.transform = t;
+	}
+}
@@ -0,0 +1,145 @@
+use cgmath::InnerSpace;
+use app::constants::*;
+use core::geometry::*;
+
+/// A whirl of water around a fixed point, counter-clockwise for a positive strength
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Vortex {
+	pub x: f32,
+	pub y: f32,
+	/// distance from the center at which the flow is fastest
+	pub radius: f32,
+	/// speed of the flow at `radius`
+	pub strength: f32,
+}
+
+impl Vortex {
+	fn velocity_at(&self, p: Position) -> Velocity {
+		vortex_velocity(Position::new(self.x, self.y), self.radius, self.strength, p)
+	}
+}
+
+fn vortex_velocity(center: Position, radius: f32, strength: f32, p: Position) -> Velocity {
+	let d = p - center;
+	let r = d.magnitude();
+	if r <= 0. || radius <= 0. {
+		return Velocity::new(0., 0.);
+	}
+	// grows linearly up to the radius, then fades away smoothly
+	let x = r / radius;
+	let speed = strength * x * (0.5 * (1. - x * x)).exp();
+	Velocity::new(-d.y, d.x) * (speed / r)
+}
+
+/// The currents of the environment, moving every body with the water.
+/// The flow is the sum of a uniform current, vortices around each feeder and around fixed points,
+/// and a time-varying turbulence which is the curl of a noise field, so it never piles bodies up.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Flow {
+	/// uniform current, in meters per second
+	pub current: (f32, f32),
+	/// speed of the whirl around each feeder, negative for clockwise
+	pub feeder_vortex_strength: f32,
+	pub feeder_vortex_radius: f32,
+	/// vortices at fixed points of the arena
+	pub vortices: Vec<Vortex>,
+	/// typical speed of the turbulent eddies, zero for still water
+	pub turbulence: f32,
+	/// typical size of the eddies
+	pub turbulence_scale: f32,
+	/// how fast the eddies change over time
+	pub turbulence_rate: f32,
+	pub turbulence_seed: u32,
+	/// how strongly bodies are dragged along by the water, per unit of mass
+	pub coupling: f32,
+}
+
+/// Still water: the feeders and the turbulence only stir it when the world config gives them a strength
+impl Default for Flow {
+	fn default() -> Self {
+		Flow {
+			current: (0., 0.),
+			feeder_vortex_strength: FLOW_FEEDER_VORTEX_STRENGTH,
+			feeder_vortex_radius: FLOW_FEEDER_VORTEX_RADIUS,
+			vortices: Vec::new(),
+			turbulence: FLOW_TURBULENCE,
+			turbulence_scale: FLOW_TURBULENCE_SCALE,
+			turbulence_rate: FLOW_TURBULENCE_RATE,
+			turbulence_seed: 0,
+			coupling: FLOW_COUPLING,
+		}
+	}
+}
+
+impl Flow {
+	/// Whether the water moves at all, so that physics can skip it entirely
+	pub fn is_still(&self) -> bool {
+		self.coupling == 0. || (self.current == (0., 0.)
+			&& self.feeder_vortex_strength == 0.
+			&& self.vortices.iter().all(|v| v.strength == 0.)
+			&& self.turbulence == 0.)
+	}
+
+	/// Velocity of the water at the given point and time of the simulation
+	pub fn velocity_at(&self, p: Position, t: f32, feeders: &[Position]) -> Velocity {
+		let mut v = Velocity::new(self.current.0, self.current.1);
+		if self.feeder_vortex_strength != 0. {
+			for feeder in feeders {
+				v += vortex_velocity(*feeder, self.feeder_vortex_radius, self.feeder_vortex_strength, p);
+			}
+		}
+		for vortex in &self.vortices {
+			v += vortex.velocity_at(p);
+		}
+		if self.turbulence != 0. && self.turbulence_scale > 0. {
+			v += self.turbulence_at(p, t);
+		}
+		v
+	}
+
+	/// Curl of a noise potential, by central differences
+	fn turbulence_at(&self, p: Position, t: f32) -> Velocity {
+		let s = 1. / self.turbulence_scale;
+		let z = t * self.turbulence_rate;
+		let eps = 0.01;
+		let (x, y) = (p.x * s, p.y * s);
+		let seed = self.turbulence_seed;
+		let dpsi_dx = (noise(x + eps, y, z, seed) - noise(x - eps, y, z, seed)) / (2. * eps);
+		let dpsi_dy = (noise(x, y + eps, z, seed) - noise(x, y - eps, z, seed)) / (2. * eps);
+		Velocity::new(dpsi_dy, -dpsi_dx) * self.turbulence
+	}
+}
+
+/// Pseudo-random value in -1..1 at a lattice point
+fn lattice(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+	let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+		^ (y as u32).wrapping_mul(0xd816_3841)
+		^ (z as u32).wrapping_mul(0xcb1a_b31f)
+		^ seed.wrapping_mul(0x9e37_79b9);
+	h ^= h >> 13;
+	h = h.wrapping_mul(0x5bd1_e995);
+	h ^= h >> 15;
+	(h & 0xffff) as f32 / 32767.5 - 1.
+}
+
+fn smooth(t: f32) -> f32 {
+	t * t * (3. - 2. * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+	a + (b - a) * t
+}
+
+/// Smoothly interpolated value noise in three dimensions
+fn noise(x: f32, y: f32, z: f32, seed: u32) -> f32 {
+	let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+	let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+	let (tx, ty, tz) = (smooth(x - x0), smooth(y - y0), smooth(z - z0));
+	let plane = |iz: i32| {
+		let a = lerp(lattice(ix, iy, iz, seed), lattice(ix + 1, iy, iz, seed), tx);
+		let b = lerp(lattice(ix, iy + 1, iz, seed), lattice(ix + 1, iy + 1, iz, seed), tx);
+		lerp(a, b, ty)
+	};
+	lerp(plane(iz), plane(iz + 1), tz)
+}
@@ -1,11 +1,12 @@
 use std::fmt;
 use std::f32::consts;
 use num;
-use csv;
 use std::cmp;
 use rand;
 use rand::Rng;
+use app::constants::GENOME_BYTES;
 use backend::obj::*;
+use backend::world::pool;
 use std::slice::Iter;
 use serialize::base64::{self, ToBase64, FromBase64};
 
@@ -21,6 +22,20 @@ fn split_bit(p: usize) -> (usize, u8) {
 	(p >> 3, (p & 0x7) as u8)
 }
 
+/// Repeats a genome shorter than `GENOME_BYTES` up to that length. Genes used to be read again
+/// from the start once the end was reached, so the padded genome develops exactly as before,
+/// but from now on its copies mutate independently.
+fn padded(mut dna: Vec<u8>) -> Vec<u8> {
+	let len = dna.len();
+	if len > 0 && len < GENOME_BYTES {
+		for i in len..GENOME_BYTES {
+			let gene = dna[i % len];
+			dna.push(gene);
+		}
+	}
+	dna
+}
+
 pub struct GenePool {
 	gene_pool: Box<[Dna]>,
 	round_robin: usize,
@@ -54,17 +69,20 @@ impl GenePool {
 		}
 	}
 
-	pub fn parse_from_resource(data: &[u8]) -> Self {
-		let mut gene_pool = Vec::new();
-		let mut csv = csv::Reader::from_bytes(data).has_headers(false);
-		for row in csv.records() {
-			let fields = row.unwrap();
-			gene_pool.push(fields[0].from_base64().unwrap().into_boxed_slice());
+	/// Loads a gene pool file in any known format version, skipping malformed rows
+	pub fn parse_from_resource(data: &[u8]) -> Result<Self, pool::ParseError> {
+		let parsed = pool::parse(data)?;
+		for e in &parsed.errors {
+			warn!("Skipping gene pool entry at {}", e);
 		}
-		GenePool {
-			gene_pool: gene_pool.to_vec().into_boxed_slice(),
-			round_robin: 0,
+		if parsed.records.is_empty() {
+			return Err(pool::ParseError { line: 1, message: "no valid genomes".to_owned() });
 		}
+		debug!("Gene pool format version {}, {} genomes", parsed.version, parsed.records.len());
+		Ok(GenePool {
+			gene_pool: parsed.records.into_iter().map(|record| record.dna).collect::<Vec<_>>().into_boxed_slice(),
+			round_robin: 0,
+		})
 	}
 
 	pub fn len(&self) -> usize {
@@ -79,14 +97,23 @@ impl GenePool {
 		}
 	}
 
-	pub fn randomize(&mut self) {
-		let mut rnd = Randomizer::new();
+	/// A pool of `n` random genomes
+	pub fn random<R: rand::Rng>(n: usize, rng: &mut R) -> Self {
+		let mut rnd = Randomizer::new(rng);
+		GenePool {
+			gene_pool: (0..n).map(|_| rnd.seed().dna().clone()).collect::<Vec<_>>().into_boxed_slice(),
+			round_robin: 0,
+		}
+	}
+
+	pub fn randomize<R: rand::Rng>(&mut self, rng: &mut R) {
+		let mut rnd = Randomizer::new(rng);
 		self.gene_pool[self.round_robin] = rnd.seed().dna().clone();
 	}
 
-	pub fn next(&mut self) -> Genome {
+	pub fn next<R: rand::Rng>(&mut self, rng: &mut R) -> Genome {
 		let gen = Genome::copy_from(&self.gene_pool[self.round_robin].clone());
-		let mutated = gen.mutate(&mut rand::thread_rng());
+		let mutated = gen.mutate(rng);
 		self.gene_pool[self.round_robin] = mutated.dna().clone();
 		self.round_robin = (self.round_robin + 1) % self.gene_pool.len();
 		gen
@@ -195,13 +222,19 @@ pub struct Randomizer<R>
 }
 
 #[allow(dead_code)]
-impl Randomizer<rand::ThreadRng> {
-	pub fn new() -> Randomizer<rand::ThreadRng> {
-		Randomizer { rng: rand::thread_rng() }
+impl<R> Randomizer<R>
+	where
+		R: rand::Rng,
+{
+	pub fn new(rng: R) -> Randomizer<R> {
+		Randomizer { rng }
 	}
 }
 
-impl Generator for Randomizer<rand::ThreadRng> {
+impl<R> Generator for Randomizer<R>
+	where
+		R: rand::Rng,
+{
 	fn next_float<T>(&mut self, min: T, max: T) -> T
 		where
 			T: rand::Rand + num::Float, {
@@ -224,7 +257,7 @@ impl<R> Seeder for Randomizer<R>
 		R: rand::Rng,
 {
 	fn seed(&mut self) -> Genome {
-		let mut dna = vec![0u8; 72];
+		let mut dna = vec![0u8; GENOME_BYTES];
 		self.rng.fill_bytes(dna.as_mut_slice());
 		Genome::new(dna)
 	}
@@ -235,21 +268,21 @@ pub struct Genome {
 	dna: Box<[u8]>,
 	ptr: usize,
 	bit_count: usize,
+	/// bits read so far, which should never be more than `bit_count`
+	bits_read: usize,
 }
 
 impl Genome {
 	pub fn copy_from(dna: &[u8]) -> Self {
-		Genome {
-			ptr: 0,
-			bit_count: bit_count(dna.len()),
-			dna: dna.to_owned().into_boxed_slice(),
-		}
+		Self::new(dna.to_owned())
 	}
 
 	pub fn new(dna: Vec<u8>) -> Self {
+		let dna = padded(dna);
 		Genome {
 			ptr: 0,
 			bit_count: bit_count(dna.len()),
+			bits_read: 0,
 			dna: dna.into_boxed_slice(),
 		}
 	}
@@ -259,9 +292,18 @@ impl Genome {
 		let (byte, bit) = split_bit(self.ptr);
 		let next = (self.dna[byte] & (1 << bit)) >> bit;
 		self.ptr = (self.ptr + 1) % self.bit_count;
+		self.bits_read += 1;
 		next
 	}
 
+	pub fn bits_read(&self) -> usize {
+		self.bits_read
+	}
+
+	pub fn bit_count(&self) -> usize {
+		self.bit_count
+	}
+
 	#[inline]
 	fn next_bits(&mut self, n: u8) -> i64 {
 		let bytes = (0..n).fold(0, |a, _| a << 1 | self.next_bit() as i64);
@@ -355,4 +397,39 @@ impl Generator for Genome {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+	use super::*;
+	use backend::world::agent;
+	use backend::world::agent::AgentType;
+	use backend::world::phen;
+	use core::clock::SimulationTimer;
+	use core::rng::SimulationRng;
+
+	fn bits_read_by(agent_type: AgentType, dna: &[u8]) -> (usize, usize) {
+		let mut gen = Genome::copy_from(dna);
+		phen::phenotype_of(&agent_type).develop(&mut gen, 0, agent::InitialState::default(), &SimulationTimer::new());
+		(gen.bits_read(), gen.bit_count())
+	}
+
+	#[test]
+	fn development_never_reads_a_gene_twice() {
+		let mut rng = SimulationRng::with_seed(3);
+		let mut rnd = Randomizer::new(&mut rng);
+		let mut genomes: Vec<Dna> = (0..500).map(|_| rnd.seed().dna().clone()).collect();
+		genomes.push(vec![0x00u8; GENOME_BYTES].into_boxed_slice());
+		genomes.push(vec![0xffu8; GENOME_BYTES].into_boxed_slice());
+		for dna in &genomes {
+			for agent_type in &[AgentType::Minion, AgentType::Enemy] {
+				let (read, count) = bits_read_by(*agent_type, dna);
+				assert!(read <= count, "{:?} read {} of {} bits", agent_type, read, count);
+			}
+		}
+	}
+
+	#[test]
+	fn short_genomes_are_padded_by_repeating_them() {
+		let gen = Genome::copy_from(&[1, 2, 3]);
+		assert_eq!(GENOME_BYTES, gen.dna().len());
+		assert_eq!(&[1, 2, 3, 1, 2, 3, 1], &gen.dna()[..7]);
+	}
+}
@@ -0,0 +1,255 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use serde_json;
+use backend::obj::Id;
+use backend::world::agent::AgentType;
+use core::clock::SecondsValue;
+
+/// Why a creature died
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+	/// ran out of energy
+	Starvation,
+	/// ran out of energy while being bitten by a predator
+	Predation,
+	/// crossed the lethal border of the world
+	Border,
+}
+
+/// Something that happened in the simulation. Positions are in world coordinates,
+/// and the kind of each agent can also be recovered from its id with `TypedAgent::type_of`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Event {
+	/// a spore was laid, or hatched into a creature
+	Birth {
+		id: Id,
+		agent_type: AgentType,
+		mother: Option<Id>,
+		father: Option<Id>,
+		generation: usize,
+		position: (f32, f32),
+	},
+	Death {
+		id: Id,
+		agent_type: AgentType,
+		cause: Cause,
+		/// seconds since birth
+		age: SecondsValue,
+		position: (f32, f32),
+	},
+	/// a spore was fertilised by a creature of the other gender
+	Fertilisation { spore: Id, father: Id, position: (f32, f32) },
+	/// a resource was eaten by the minion touching it with a mouth
+	Eaten { resource: Id, eater: Id, position: (f32, f32) },
+	/// every minion died, and the population starts over from the gene pool; `count` extinctions so far
+	Extinction { count: usize },
+	/// the player fired a bullet
+	PlayerShot { bullet: Id, position: (f32, f32) },
+}
+
+/// An entry of the journal, stamped with the simulation time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Record {
+	pub seconds: SecondsValue,
+	pub event: Event,
+}
+
+impl Record {
+	pub fn new(seconds: SecondsValue, event: Event) -> Self {
+		Record { seconds, event }
+	}
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn segment_path(prefix: &str, n: usize) -> PathBuf {
+	PathBuf::from(format!("{}.{:04}.jsonl", prefix, n))
+}
+
+/// Numbers of the existing segments of the journal with the given prefix, in order
+fn segment_numbers(prefix: &str) -> io::Result<Vec<usize>> {
+	let path = Path::new(prefix);
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+		_ => PathBuf::from("."),
+	};
+	let stem = match path.file_name().and_then(|s| s.to_str()) {
+		Some(stem) => format!("{}.", stem),
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Not a journal prefix: {}", prefix))),
+	};
+	let mut numbers = Vec::new();
+	if !dir.is_dir() {
+		return Ok(numbers);
+	}
+	for entry in fs::read_dir(&dir)? {
+		let name = entry?.file_name();
+		let name = match name.to_str() {
+			Some(name) => name,
+			None => continue,
+		};
+		if name.starts_with(&stem) && name.ends_with(".jsonl") {
+			if let Ok(n) = name[stem.len()..name.len() - ".jsonl".len()].parse::<usize>() {
+				numbers.push(n);
+			}
+		}
+	}
+	numbers.sort();
+	Ok(numbers)
+}
+
+/// Files of the journal with the given prefix, oldest first
+pub fn segments(prefix: &str) -> io::Result<Vec<PathBuf>> {
+	Ok(segment_numbers(prefix)?.into_iter().map(|n| segment_path(prefix, n)).collect())
+}
+
+/// Appends records to `<prefix>.NNNN.jsonl`, one JSON object per line,
+/// and moves on to the next file once the current one reaches `max_bytes`.
+/// An existing journal is carried on from its last file.
+pub struct Writer {
+	prefix: String,
+	max_bytes: u64,
+	segment: usize,
+	written: u64,
+	out: io::BufWriter<fs::File>,
+}
+
+impl Writer {
+	pub fn create(prefix: &str, max_bytes: u64) -> io::Result<Self> {
+		if let Some(dir) = Path::new(prefix).parent() {
+			if !dir.as_os_str().is_empty() {
+				fs::create_dir_all(dir)?;
+			}
+		}
+		let segment = segment_numbers(prefix)?.last().cloned().unwrap_or(0);
+		let (out, written) = Self::open(prefix, segment)?;
+		Ok(Writer {
+			prefix: prefix.to_owned(),
+			max_bytes,
+			segment,
+			written,
+			out,
+		})
+	}
+
+	fn open(prefix: &str, segment: usize) -> io::Result<(io::BufWriter<fs::File>, u64)> {
+		let file = fs::OpenOptions::new().create(true).append(true).open(segment_path(prefix, segment))?;
+		let written = file.metadata()?.len();
+		Ok((io::BufWriter::new(file), written))
+	}
+
+	/// The file being written
+	pub fn path(&self) -> PathBuf {
+		segment_path(&self.prefix, self.segment)
+	}
+
+	pub fn append(&mut self, record: &Record) -> io::Result<()> {
+		if self.written > 0 && self.written >= self.max_bytes {
+			self.out.flush()?;
+			let (out, written) = Self::open(&self.prefix, self.segment + 1)?;
+			self.segment += 1;
+			self.out = out;
+			self.written = written;
+		}
+		let mut line = serde_json::to_vec(record).map_err(invalid_data)?;
+		line.push(b'\n');
+		self.out.write_all(&line)?;
+		self.written += line.len() as u64;
+		Ok(())
+	}
+
+	pub fn flush(&mut self) -> io::Result<()> {
+		self.out.flush()
+	}
+}
+
+/// Reads back all the records of a journal, across its files, in the order they were written
+pub struct Reader {
+	paths: VecDeque<PathBuf>,
+	lines: Option<io::Lines<io::BufReader<fs::File>>>,
+}
+
+impl Reader {
+	/// Opens the journal with the given prefix, which is empty if there is none
+	pub fn open(prefix: &str) -> io::Result<Self> {
+		Ok(Reader {
+			paths: segments(prefix)?.into_iter().collect(),
+			lines: None,
+		})
+	}
+
+	/// Reads the whole journal, failing on the first unreadable record
+	pub fn read_all(prefix: &str) -> io::Result<Vec<Record>> {
+		Self::open(prefix)?.collect()
+	}
+}
+
+impl Iterator for Reader {
+	type Item = io::Result<Record>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if self.lines.is_none() {
+				let path = match self.paths.pop_front() {
+					Some(path) => path,
+					None => return None,
+				};
+				match fs::File::open(&path) {
+					Ok(file) => self.lines = Some(io::BufReader::new(file).lines()),
+					Err(e) => return Some(Err(e)),
+				}
+			}
+			let line = match self.lines.as_mut().and_then(|lines| lines.next()) {
+				Some(line) => line,
+				None => {
+					self.lines = None;
+					continue;
+				}
+			};
+			match line {
+				Ok(ref line) if line.trim().is_empty() => continue,
+				Ok(line) => return Some(serde_json::from_str(&line).map_err(invalid_data)),
+				Err(e) => return Some(Err(e)),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::process;
+
+	#[test]
+	fn records_are_read_back_in_order_across_rotations() {
+		let dir = env::temp_dir().join(format!("rust-oids-journal-{}", process::id()));
+		let prefix = dir.join("journal").to_str().unwrap().to_owned();
+		let records: Vec<_> = (0..50).map(|i| Record::new(i as SecondsValue * 0.5, if i % 2 == 0 {
+			Event::Fertilisation { spore: i, father: i + 1, position: (i as f32, -1.) }
+		} else {
+			Event::Death { id: i, agent_type: AgentType::Minion, cause: Cause::Starvation, age: 3., position: (0., 0.) }
+		})).collect();
+		{
+			let mut writer = Writer::create(&prefix, 512).unwrap();
+			for record in &records[..30] {
+				writer.append(record).unwrap();
+			}
+		}
+		// a second run carries on the same journal
+		{
+			let mut writer = Writer::create(&prefix, 512).unwrap();
+			for record in &records[30..] {
+				writer.append(record).unwrap();
+			}
+		}
+		assert!(segments(&prefix).unwrap().len() > 1);
+		let read = Reader::read_all(&prefix).unwrap();
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(records, read);
+	}
+}
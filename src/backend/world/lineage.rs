@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use serde_json;
+use serialize::base64::{self, ToBase64};
+use backend::obj::Id;
+use backend::world::agent::Lineage;
+use backend::world::gen::Dna;
+use core::clock::{Seconds, SecondsValue};
+
+/// One minion in the family tree
+#[derive(Clone, Debug)]
+pub struct Node {
+	pub id: Id,
+	pub mother: Option<Id>,
+	pub father: Option<Id>,
+	pub generation: usize,
+	pub birth: SecondsValue,
+	pub death: Option<SecondsValue>,
+	/// genome of the founder, for the roots of the tree only
+	pub founder: Option<Dna>,
+	/// spores laid and not yet hatched
+	pub pending: usize,
+	children: usize,
+}
+
+impl Node {
+	pub fn new(id: Id, mother: Option<Id>, father: Option<Id>, generation: usize, birth: SecondsValue) -> Self {
+		Node {
+			id,
+			mother,
+			father,
+			generation,
+			birth,
+			death: None,
+			founder: None,
+			pending: 0,
+			children: 0,
+		}
+	}
+
+	pub fn is_alive(&self) -> bool {
+		self.death.is_none()
+	}
+}
+
+/// Flat, serializable view of a node, children are listed by id
+#[derive(Serialize, Debug)]
+struct Clade {
+	id: Id,
+	mother: Option<Id>,
+	father: Option<Id>,
+	generation: usize,
+	birth: SecondsValue,
+	death: Option<SecondsValue>,
+	founder: Option<String>,
+	children: Vec<Id>,
+}
+
+#[derive(Serialize, Debug)]
+struct Tree {
+	roots: Vec<Id>,
+	nodes: Vec<Clade>,
+}
+
+/// Registry of the ancestry of every minion which is alive or has living descendants.
+/// Dead branches are pruned as soon as they can't lead to any living minion.
+#[derive(Clone, Default)]
+pub struct Phylogeny {
+	nodes: BTreeMap<Id, Node>,
+}
+
+impl Phylogeny {
+	pub fn new() -> Self {
+		Phylogeny { nodes: BTreeMap::new() }
+	}
+
+	pub fn clear(&mut self) {
+		self.nodes.clear();
+	}
+
+	pub fn nodes(&self) -> Vec<&Node> {
+		self.nodes.values().collect()
+	}
+
+	/// A minion from the gene pool, with no known ancestors
+	pub fn found(&mut self, id: Id, birth: Seconds, dna: &Dna) {
+		self.nodes.insert(id, Node {
+			founder: Some(dna.clone()),
+			..Node::new(id, None, None, 0, birth.get())
+		});
+	}
+
+	/// A spore has been laid, keeps the mother's branch alive until it hatches
+	pub fn conceive(&mut self, mother: Id) {
+		if let Some(node) = self.nodes.get_mut(&mother) {
+			node.pending += 1;
+		}
+	}
+
+	/// The spore is gone, hatched or otherwise
+	pub fn discard(&mut self, mother: Id) {
+		let prune = match self.nodes.get_mut(&mother) {
+			Some(node) => {
+				node.pending = node.pending.saturating_sub(1);
+				true
+			}
+			None => false,
+		};
+		if prune {
+			self.prune(mother);
+		}
+	}
+
+	pub fn hatch(&mut self, id: Id, lineage: &Lineage) {
+		if let Some(mother) = lineage.mother.and_then(|mother| self.nodes.get_mut(&mother)) {
+			mother.children += 1;
+		}
+		self.nodes.insert(id, Node::new(id, lineage.mother, lineage.father, lineage.generation, lineage.birth.get()));
+	}
+
+	pub fn die(&mut self, id: Id, when: Seconds) {
+		let found = match self.nodes.get_mut(&id) {
+			Some(node) => {
+				node.death = Some(when.get());
+				true
+			}
+			None => false,
+		};
+		if found {
+			self.prune(id);
+		}
+	}
+
+	/// Nodes must be restored all together, followed by a call to `rebuild_children`
+	pub fn restore(&mut self, node: Node) {
+		self.nodes.insert(node.id, node);
+	}
+
+	pub fn rebuild_children(&mut self) {
+		let mut counts = BTreeMap::new();
+		for node in self.nodes.values() {
+			if let Some(mother) = node.mother {
+				*counts.entry(mother).or_insert(0usize) += 1;
+			}
+		}
+		for (id, node) in self.nodes.iter_mut() {
+			node.children = counts.get(id).cloned().unwrap_or(0);
+		}
+	}
+
+	fn prune(&mut self, id: Id) {
+		let mut next = Some(id);
+		while let Some(id) = next {
+			next = None;
+			let removable = self.nodes.get(&id)
+				.map(|node| !node.is_alive() && node.children == 0 && node.pending == 0)
+				.unwrap_or(false);
+			if removable {
+				if let Some(node) = self.nodes.remove(&id) {
+					if let Some(mother) = node.mother.and_then(|mother| self.nodes.get_mut(&mother)) {
+						mother.children = mother.children.saturating_sub(1);
+						next = Some(mother.id);
+					}
+				}
+			}
+		}
+	}
+
+	fn children_map(&self) -> (Vec<Id>, BTreeMap<Id, Vec<Id>>) {
+		let mut roots = Vec::new();
+		let mut children = BTreeMap::new();
+		for node in self.nodes.values() {
+			match node.mother.and_then(|mother| if self.nodes.contains_key(&mother) { Some(mother) } else { None }) {
+				Some(mother) => children.entry(mother).or_insert_with(Vec::new).push(node.id),
+				None => roots.push(node.id),
+			}
+		}
+		(roots, children)
+	}
+
+	/// Newick representation of the tree, branch lengths are in simulation seconds.
+	/// Founders are labelled with an F prefix
+	pub fn to_newick(&self) -> String {
+		enum Visit {
+			Enter(Id),
+			Sibling,
+			Exit(Id),
+		}
+		let (roots, children) = self.children_map();
+		let mut out = String::new();
+		let mut stack = Vec::new();
+		// a forest is presented as the children of an anonymous root
+		out.push('(');
+		for (i, root) in roots.iter().enumerate().rev() {
+			stack.push(Visit::Enter(*root));
+			if i > 0 {
+				stack.push(Visit::Sibling);
+			}
+		}
+		while let Some(visit) = stack.pop() {
+			match visit {
+				Visit::Sibling => out.push(','),
+				Visit::Enter(id) => {
+					stack.push(Visit::Exit(id));
+					if let Some(descendants) = children.get(&id) {
+						out.push('(');
+						for (i, child) in descendants.iter().enumerate().rev() {
+							stack.push(Visit::Enter(*child));
+							if i > 0 {
+								stack.push(Visit::Sibling);
+							}
+						}
+					}
+				}
+				Visit::Exit(id) => {
+					if children.contains_key(&id) {
+						out.push(')');
+					}
+					if let Some(node) = self.nodes.get(&id) {
+						let prefix = if node.founder.is_some() { "F" } else { "M" };
+						let parent_birth = node.mother
+							.and_then(|mother| self.nodes.get(&mother))
+							.map(|mother| mother.birth)
+							.unwrap_or(node.birth);
+						write!(out, "{}{}:{:.3}", prefix, id, node.birth - parent_birth).is_ok();
+					}
+				}
+			}
+		}
+		out.push_str(");");
+		out
+	}
+
+	pub fn to_json(&self) -> serde_json::Result<String> {
+		let (roots, children) = self.children_map();
+		let tree = Tree {
+			roots,
+			nodes: self.nodes
+				.values()
+				.map(|node| Clade {
+					id: node.id,
+					mother: node.mother,
+					father: node.father,
+					generation: node.generation,
+					birth: node.birth,
+					death: node.death,
+					founder: node.founder.as_ref().map(|dna| dna.to_base64(base64::STANDARD)),
+					children: children.get(&node.id).cloned().unwrap_or_else(Vec::new),
+				})
+				.collect(),
+		};
+		serde_json::to_string_pretty(&tree)
+	}
+}
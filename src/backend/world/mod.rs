@@ -6,15 +6,28 @@ pub mod gen;
 pub mod phen;
 pub mod particle;
 pub mod persist;
+pub mod lineage;
+pub mod explain;
+pub mod pool;
+pub mod species;
+pub mod body;
+pub mod sensor;
+pub mod spatial;
+pub mod pheromone;
+pub mod flow;
+pub mod config;
+pub mod feeder;
+pub mod arena;
+pub mod topology;
+pub mod journal;
+pub mod svg;
 
 use backend::obj;
 use backend::obj::*;
-use rand;
 use chrono::Utc;
 use chrono::DateTime;
+use cgmath::InnerSpace;
 use std::f32::consts;
-use std::collections::HashMap;
-use std::collections::HashSet;
 use std::io;
 use std::io::Write;
 use std::fs;
@@ -25,6 +38,7 @@ use core::color::Rgba;
 use core::geometry::*;
 use core::geometry::Transform;
 use core::resource::ResourceLoader;
+use core::rng::SimulationRng;
 use serialize::base64::{self, ToBase64};
 use backend::messagebus::{Outbox, Message};
 use self::agent::Agent;
@@ -34,22 +48,45 @@ use self::swarm::*;
 use self::particle::Particle;
 
 pub use self::alert::Alert;
+pub use self::feeder::{Feeder, FeederId, FeederSpec, Emission};
 
+/// Read-only access to the agents, and neighbour queries backed by the spatial index,
+/// which reflects the positions of the agents at the beginning of the frame
 pub trait AgentState {
 	fn agent(&self, id: obj::Id) -> Option<&Agent>;
+	/// Agents of the given type whose body reaches within `radius` of `center`, in no particular order
+	fn within(&self, agent_type: AgentType, center: Position, radius: f32) -> Vec<(obj::Id, Position)>;
+	/// Up to `k` agents of the given type, nearest to `center` first
+	fn nearest(&self, agent_type: AgentType, center: Position, k: usize) -> Vec<(obj::Id, Position)>;
+	/// First agent along the segment from `p0` to `p1`, ignoring `exclude`, and the fraction of the segment to it
+	fn cast(&self, p0: Position, p1: Position, agent_type: Option<AgentType>, exclude: Option<obj::Id>) -> Option<(obj::Id, f32)>;
 }
 
 pub struct World {
 	pub extent: Rect,
+	topology: topology::Topology,
 	phase: Rgba,
-	swarms: HashMap<AgentType, Swarm>,
+	swarms: SwarmMap,
 	feeders: Vec<Feeder>,
-	registered: HashSet<Id>,
+	feeder_seq: FeederId,
+	obstacles: Vec<arena::Obstacle>,
+	registered: Vec<Id>,
 	registered_player_id: Option<Id>,
 	regenerations: usize,
 	minion_gene_pool: gen::GenePool,
+	enemy_gene_pool: gen::GenePool,
 	resource_gene_pool: gen::GenePool,
 	clock: SimulationTimer,
+	rng: SimulationRng,
+	/// a stream of its own for the particle effects, so that they don't change the course of the simulation
+	particle_rng: SimulationRng,
+	phylogeny: lineage::Phylogeny,
+	species: species::Taxonomy,
+	species_due: SecondsValue,
+	plasticity: agent::Plasticity,
+	index: spatial::Grid,
+	pheromones: pheromone::Field,
+	flow: flow::Flow,
 	particles: Vec<Particle>,
 }
 
@@ -57,92 +94,164 @@ impl AgentState for World {
 	fn agent(&self, id: obj::Id) -> Option<&Agent> {
 		self.swarms.get(&id.type_of()).and_then(|m| m.get(id))
 	}
-}
-
-#[derive(Clone)]
-pub enum Emission {
-	CW(Angle),
-	CCW(Angle),
-	Random,
-}
 
-#[derive(Clone)]
-pub struct Feeder {
-	transform: Transform,
-	rate: Seconds,
-	emission: Emission,
-}
-
-impl Feeder {
-	pub fn new(x: f32, y: f32, rate: Seconds, emission: Emission) -> Self {
-		Feeder {
-			transform: Transform::from_position(Position::new(x, y)),
-			rate,
-			emission,
-		}
+	fn within(&self, agent_type: AgentType, center: Position, radius: f32) -> Vec<(obj::Id, Position)> {
+		self.index.within(agent_type, center, radius)
 	}
-	pub fn rate(&self) -> Seconds {
-		self.rate
-	}
-	pub fn emission(&self) -> Emission {
-		self.emission.clone()
-	}
-}
 
-impl Transformable for Feeder {
-	fn transform(&self) -> &Transform {
-		&self.transform
+	fn nearest(&self, agent_type: AgentType, center: Position, k: usize) -> Vec<(obj::Id, Position)> {
+		self.index.nearest(agent_type, center, k)
 	}
-	fn transform_to(&mut self, t: Transform) {
-		self.transform = t;
+
+	fn cast(&self, p0: Position, p1: Position, agent_type: Option<AgentType>, exclude: Option<obj::Id>) -> Option<(obj::Id, f32)> {
+		self.index.cast(p0, p1, agent_type, exclude)
 	}
 }
 
 impl World {
-	pub fn new<R>(res: &R, minion_gene_pool: &str) -> Self
+	pub fn new<R>(res: &R, minion_gene_pool: &str, seed: u64) -> Self
 		where
 			R: ResourceLoader<u8>, {
-		let mut swarms = HashMap::new();
+		let mut swarms = SwarmMap::new();
 		let types = AgentType::all();
 		let clock = SimulationTimer::new();
 		for t in types {
 			swarms.insert(*t, Swarm::new(*t, phen::phenotype_of(t)));
 		}
-		fn default_gene_pool(_: io::Error) -> gen::GenePool {
+		fn default_gene_pool(e: io::Error) -> gen::GenePool {
+			warn!("Using the default gene pool: {}", e);
 			gen::GenePool::parse_from_base64(DEFAULT_MINION_GENE_POOL)
 		}
-		let emitter_rate = Seconds::new(EMITTER_PERIOD);
-		let num_emitters: usize = 7;
-		let feeders = (0..num_emitters).map(|i| {
-			let (s, c) = (consts::PI * 2. * (i as f32 / num_emitters as f32)).sin_cos();
-			Feeder::new(c * EMITTER_DISTANCE, s * EMITTER_DISTANCE, emitter_rate, Emission::Random)
-		}).collect::<Vec<_>>();
-		World {
-			extent: Rect::new(-WORLD_RADIUS, -WORLD_RADIUS, WORLD_RADIUS, WORLD_RADIUS),
+		let mut rng = SimulationRng::with_seed(seed);
+		let particle_rng = rng.fork();
+		let enemy_gene_pool = gen::GenePool::random(ENEMY_GENE_POOL_SIZE, &mut rng);
+		let extent = Rect::new(-WORLD_RADIUS, -WORLD_RADIUS, WORLD_RADIUS, WORLD_RADIUS);
+		let config = match res.load(WORLD_CONFIG_FILE) {
+			Ok(data) => config::WorldConfig::parse(&data).unwrap_or_else(|e| {
+				warn!("Using the default world configuration, {} is malformed: {}", WORLD_CONFIG_FILE, e);
+				config::WorldConfig::default()
+			}),
+			Err(_) => config::WorldConfig::default(),
+		};
+		let feeders = config.feeders
+			.unwrap_or_else(|| FeederSpec::ring(EMITTER_COUNT, EMITTER_DISTANCE))
+			.into_iter()
+			.enumerate()
+			.map(|(id, spec)| Feeder::new(id, spec))
+			.collect::<Vec<_>>();
+		let obstacles = match config.arena {
+			Some(ref arena_file) => res.load(arena_file)
+				.map_err(|e| e.to_string())
+				.and_then(|data| arena::Arena::parse(&data).map_err(|e| e.to_string()))
+				.map(|arena| arena.obstacles)
+				.unwrap_or_else(|e| {
+					warn!("Using an empty arena, could not load {}: {}", arena_file, e);
+					Vec::new()
+				}),
+			None => Vec::new(),
+		};
+		let mut world = World {
+			extent,
+			topology: topology::Topology::default(),
 			phase: COLOR_TRANSPARENT,
 			swarms,
+			feeder_seq: feeders.len(),
 			feeders,
+			obstacles,
 			minion_gene_pool: res.load(minion_gene_pool)
-				.map(|data| gen::GenePool::parse_from_resource(&data))
+				.and_then(|data| gen::GenePool::parse_from_resource(&data).map_err(io::Error::from))
 				.unwrap_or_else(default_gene_pool),
+			enemy_gene_pool,
 			resource_gene_pool: gen::GenePool::parse_from_base64(DEFAULT_RESOURCE_GENE_POOL),
-			registered: HashSet::new(),
+			registered: Vec::new(),
 			registered_player_id: None,
 			regenerations: 0usize,
 			clock,
+			rng,
+			particle_rng,
+			phylogeny: lineage::Phylogeny::new(),
+			species: species::Taxonomy::new(config.species),
+			species_due: 0.,
+			plasticity: agent::Plasticity::default(),
+			index: spatial::Grid::new(&extent, SPATIAL_CELL_SIZE),
+			pheromones: pheromone::Field::new(&extent, PHEROMONE_CELL_SIZE, PHEROMONE_CHANNELS),
+			flow: config.flow,
 			particles: Vec::with_capacity(10000),
-		}
+		};
+		world.set_topology(config.topology);
+		world
+	}
+
+	pub fn reseed(&mut self, seed: u64) {
+		self.rng = SimulationRng::with_seed(seed);
+		self.particle_rng = self.rng.fork();
+	}
+
+	pub fn rng(&self) -> &SimulationRng { &self.rng }
+
+	pub fn rng_mut(&mut self) -> &mut SimulationRng { &mut self.rng }
+
+	pub fn particle_rng(&self) -> &SimulationRng { &self.particle_rng }
+
+	pub fn particle_rng_mut(&mut self) -> &mut SimulationRng { &mut self.particle_rng }
+
+	pub fn plasticity(&self) -> agent::Plasticity { self.plasticity }
+
+	pub fn set_plasticity(&mut self, plasticity: agent::Plasticity) {
+		self.plasticity = plasticity;
+	}
+
+	pub fn pheromones(&self) -> &pheromone::Field { &self.pheromones }
+
+	pub fn pheromones_mut(&mut self) -> &mut pheromone::Field { &mut self.pheromones }
+
+	pub fn set_pheromones(&mut self, pheromones: pheromone::Field) {
+		self.pheromones = pheromones;
+	}
+
+	pub fn topology(&self) -> topology::Topology { self.topology }
+
+	/// Changes what happens at the edge of the world, and whether neighbour queries wrap around
+	pub fn set_topology(&mut self, topology: topology::Topology) {
+		self.topology = topology;
+		self.index.set_topology(topology);
+		self.pheromones.set_wrap(topology.wraps());
+	}
+
+	pub fn flow(&self) -> &flow::Flow { &self.flow }
+
+	pub fn set_flow(&mut self, flow: flow::Flow) {
+		self.flow = flow;
 	}
 
 	pub fn clear(&mut self) {
 		for (_, swarm) in self.swarms.iter_mut() { swarm.clear(); }
 		self.registered.clear();
 		self.registered_player_id = None;
+		self.phylogeny.clear();
+		self.species.clear();
+		self.species_due = 0.;
+		self.pheromones.clear();
 		self.particles.clear();
 	}
 
 	pub fn tick(&mut self, dt: Seconds) {
 		self.clock.tick(dt);
+		if self.clock.seconds().get() >= self.species_due {
+			self.classify_species();
+		}
+	}
+
+	/// Regroups the living minions into species, ids of the surviving species are preserved
+	pub fn classify_species(&mut self) {
+		let now = self.clock.seconds().get();
+		self.species_due = now + SPECIES_CLASSIFY_PERIOD;
+		let minions = self.swarms.get(&AgentType::Minion).unwrap().agents();
+		self.species.classify(minions, now);
+	}
+
+	pub fn species(&self) -> &species::Taxonomy {
+		&self.species
 	}
 
 	pub fn seconds(&self) -> Seconds { self.clock.seconds() }
@@ -151,8 +260,27 @@ impl World {
 		if self.regenerations > 1 { self.regenerations - 1 } else { 0usize }
 	}
 
+	/// Posts an entry for the event journal, stamped with the current simulation time
+	pub fn record(&self, outbox: &Outbox, event: journal::Event) {
+		outbox.post(journal::Record::new(self.clock.seconds().get(), event).into());
+	}
+
+	fn record_birth(&self, outbox: &Outbox, id: obj::Id) {
+		if let Some(agent) = self.agent(id) {
+			let position = agent.transform().position;
+			self.record(outbox, journal::Event::Birth {
+				id,
+				agent_type: id.type_of(),
+				mother: agent.lineage.mother,
+				father: agent.lineage.father,
+				generation: agent.lineage.generation,
+				position: (position.x, position.y),
+			});
+		}
+	}
+
 	pub fn new_resource(&mut self, transform: Transform, motion: Motion) -> obj::Id {
-		let mut gen = &mut self.resource_gene_pool.next();
+		let mut gen = &mut self.resource_gene_pool.next(&mut self.rng);
 		let clock = self.clock.clone();
 		let id = self.swarm_mut(&AgentType::Resource).spawn(
 			&mut gen,
@@ -160,6 +288,7 @@ impl World {
 				transform,
 				motion,
 				charge: DEFAULT_RESOURCE_CHARGE,
+				lineage: agent::Lineage::founder(clock.seconds()),
 				..Default::default()
 			},
 			&clock);
@@ -173,6 +302,7 @@ impl World {
 			agent::InitialState {
 				transform: transform.clone(),
 				charge: DEFAULT_RESOURCE_CHARGE,
+				lineage: agent::Lineage::founder(clock.seconds()),
 				..Default::default()
 			},
 			&clock);
@@ -186,17 +316,21 @@ impl World {
 		self.register(id)
 	}
 
-	pub fn new_spore(&mut self, outbox: &Outbox, transform: Transform, dna: &gen::Dna) -> obj::Id {
+	pub fn new_spore(&mut self, outbox: &Outbox, transform: Transform, dna: &gen::Dna, mother: obj::Id, lineage: &agent::Lineage) -> obj::Id {
 		let clock = self.clock.clone();
+		let mut gen = gen::Genome::copy_from(dna).mutate(&mut self.rng);
 		let id = self.swarm_mut(&AgentType::Spore).spawn(
-			&mut gen::Genome::copy_from(dna).mutate(&mut rand::thread_rng()),
+			&mut gen,
 			agent::InitialState {
 				transform: transform.clone(),
 				charge: DEFAULT_SPORE_CHARGE,
+				lineage: lineage.offspring(mother, clock.seconds()),
 				..Default::default()
 			},
 			&clock,
 		);
+		self.phylogeny.conceive(mother);
+		self.record_birth(outbox, id);
 		let livery_color = self.agent(id).unwrap()
 			.segment(0).unwrap()
 			.livery.albedo;
@@ -208,17 +342,29 @@ impl World {
 		self.register(id)
 	}
 
-	pub fn hatch_spore(&mut self, outbox: &Outbox, transform: Transform, dna: &gen::Dna) -> obj::Id {
+	pub fn hatch_spore(&mut self, outbox: &Outbox, transform: Transform, dna: &gen::Dna, spore_lineage: &agent::Lineage) -> obj::Id {
 		let clock = self.clock.clone();
-		let id = self.swarm_mut(&AgentType::Minion).spawn(
+		let lineage = agent::Lineage {
+			birth: clock.seconds(),
+			..spore_lineage.clone()
+		};
+		// spores hatch into the same kind of creature that laid them
+		let agent_type = match spore_lineage.mother.map(|mother| mother.type_of()) {
+			Some(AgentType::Enemy) => AgentType::Enemy,
+			_ => AgentType::Minion,
+		};
+		let id = self.swarm_mut(&agent_type).spawn(
 			&mut gen::Genome::copy_from(dna),
 			agent::InitialState {
 				transform: transform.clone(),
 				charge: DEFAULT_MINION_CHARGE,
+				lineage: lineage.clone(),
 				..Default::default()
 			},
 			&clock,
 		);
+		self.phylogeny.hatch(id, &lineage);
+		self.record_birth(outbox, id);
 		let livery_color = self.agent(id).unwrap()
 			.segment(0).unwrap()
 			.livery.albedo;
@@ -230,7 +376,7 @@ impl World {
 	}
 
 	pub fn randomize_minion(&mut self, pos: Position, motion: Motion) -> obj::Id {
-		self.minion_gene_pool.randomize();
+		self.minion_gene_pool.randomize(&mut self.rng);
 		self.new_minion(pos, motion)
 	}
 
@@ -243,22 +389,79 @@ impl World {
 		let angle_delta = consts::PI * 2. / INITIAL_SPAWN_RADIUS_SLICES as f32;
 		for _ in 0..n {
 			let pos = Position::new(r * angle.cos(), r * angle.sin());
-			let mut gen = self.minion_gene_pool.next();
+			let mut gen = self.minion_gene_pool.next(&mut self.rng);
 			let id = self.swarm_mut(&AgentType::Minion).spawn(
 				&mut gen,
 				agent::InitialState {
 					transform: Transform::new(pos, angle + consts::PI / 2.),
 					charge: DEFAULT_MINION_CHARGE,
+					lineage: agent::Lineage::founder(clock.seconds()),
 					..Default::default()
 				},
 				&clock,
 			);
+			self.phylogeny.found(id, clock.seconds(), gen.dna());
 			self.register(id);
 			angle += angle_delta;
 			r += INITIAL_SPAWN_RADIUS_INCREMENT;
 		}
 	}
 
+	pub fn init_enemies(&mut self) {
+		let n = self.enemy_gene_pool.len();
+		let clock = self.clock.clone();
+		let r = self.extent.top_right().x * ENEMY_SPAWN_RADIUS_RATIO;
+		let angle_delta = consts::PI * 2. / n as f32;
+		for i in 0..n {
+			let angle = angle_delta * i as f32;
+			let pos = Position::new(r * angle.cos(), r * angle.sin());
+			let mut gen = self.enemy_gene_pool.next(&mut self.rng);
+			let id = self.swarm_mut(&AgentType::Enemy).spawn(
+				&mut gen,
+				agent::InitialState {
+					transform: Transform::new(pos, angle + consts::PI / 2.),
+					charge: DEFAULT_MINION_CHARGE,
+					lineage: agent::Lineage::founder(clock.seconds()),
+					..Default::default()
+				},
+				&clock,
+			);
+			self.phylogeny.found(id, clock.seconds(), gen.dna());
+			self.register(id);
+		}
+	}
+
+	/// Spawns a static prop for each obstacle of the arena
+	pub fn init_props(&mut self) {
+		let clock = self.clock.clone();
+		for (i, obstacle) in self.obstacles.clone().iter().enumerate() {
+			// props only take their shade from the genes
+			let mut gen = gen::Genome::copy_from(&[i as u8, (i >> 8) as u8, 0x5a, 0xa5]);
+			let id = self.swarm_mut(&AgentType::Prop).spawn(
+				&mut gen,
+				agent::InitialState {
+					transform: obstacle.transform(),
+					shape: Some(obstacle.shape()),
+					..Default::default()
+				},
+				&clock,
+			);
+			self.register(id);
+		}
+	}
+
+	pub fn obstacles(&self) -> &[arena::Obstacle] {
+		&self.obstacles
+	}
+
+	/// Replaces the layout of the arena, the props are spawned again on the next frame
+	pub fn set_obstacles(&mut self, obstacles: Vec<arena::Obstacle>) {
+		for (_, agent) in self.swarm_mut(&AgentType::Prop).agents_mut().iter_mut() {
+			agent.state.die();
+		}
+		self.obstacles = obstacles;
+	}
+
 	pub fn init_players(&mut self) {
 		self.registered_player_id = Some(self.spawn_player(Position::new(0., 0.), Motion::default()))
 	}
@@ -311,7 +514,9 @@ impl World {
 		})
 			.map(|(t, v)| {
 				outbox.post(Alert::NewBullet(0).into());
-				self.new_resource(t, v);
+				let position = t.position;
+				let bullet = self.new_resource(t, v);
+				self.record(outbox, journal::Event::PlayerShot { bullet, position: (position.x, position.y) });
 			});
 	}
 
@@ -323,7 +528,7 @@ impl World {
 
 	pub fn new_minion(&mut self, pos: Position, motion: Motion) -> obj::Id {
 		let angle = consts::PI / 2. + f32::atan2(pos.y, pos.x);
-		let mut gen = self.minion_gene_pool.next();
+		let mut gen = self.minion_gene_pool.next(&mut self.rng);
 		let clock = self.clock.clone();
 		let id = self.swarm_mut(&AgentType::Minion).spawn(
 			&mut gen,
@@ -331,21 +536,26 @@ impl World {
 				transform: Transform::new(pos, angle),
 				motion,
 				charge: 0.3,
+				lineage: agent::Lineage::founder(clock.seconds()),
 				..Default::default()
 			},
 			&clock,
 		);
+		self.phylogeny.found(id, clock.seconds(), gen.dna());
 		self.register(id)
 	}
 
 	pub fn register(&mut self, id: obj::Id) -> obj::Id {
-		self.registered.insert(id);
+		// registration order is preserved, so that physics bodies are always created in the same sequence
+		if !self.registered.contains(&id) {
+			self.registered.push(id);
+		}
 		id
 	}
 
 	pub fn registered(&mut self) -> Box<[Id]> {
 		self.registered
-			.drain()
+			.drain(..)
 			.collect::<Vec<_>>()
 			.into_boxed_slice()
 	}
@@ -387,6 +597,44 @@ impl World {
 		self.feeders.as_slice()
 	}
 
+	/// Adds a feeder to the arena, returning its id
+	pub fn add_feeder(&mut self, spec: FeederSpec) -> FeederId {
+		let id = self.feeder_seq;
+		self.feeder_seq += 1;
+		self.feeders.push(Feeder::new(id, spec));
+		id
+	}
+
+	pub fn remove_feeder(&mut self, id: FeederId) {
+		self.feeders.retain(|f| f.id() != id);
+	}
+
+	/// The feeder nearest to the given position, if any is within `radius`
+	pub fn feeder_near(&self, pos: Position, radius: f32) -> Option<FeederId> {
+		self.feeders
+			.iter()
+			.map(|f| (f.id(), (f.transform().position - pos).magnitude2()))
+			.filter(|&(_, d2)| d2 <= radius * radius)
+			.fold(None, |nearest: Option<(FeederId, f32)>, (id, d2)| match nearest {
+				Some((_, n2)) if n2 <= d2 => nearest,
+				_ => Some((id, d2)),
+			})
+			.map(|(id, _)| id)
+	}
+
+	/// Writes back a feeder after an update, unless it was removed in the meantime
+	pub fn update_feeder(&mut self, feeder: Feeder) {
+		if let Some(dest) = self.feeders.iter_mut().find(|f| f.id() == feeder.id()) {
+			*dest = feeder;
+		}
+	}
+
+	/// Replaces all the feeders, as restored from a snapshot
+	pub fn restore_feeders(&mut self, feeders: Vec<Feeder>) {
+		self.feeder_seq = feeders.iter().map(|f| f.id() + 1).max().unwrap_or(0);
+		self.feeders = feeders;
+	}
+
 	pub fn swarms(&self) -> &SwarmMap {
 		&self.swarms
 	}
@@ -411,32 +659,85 @@ impl World {
 		self.clear_particles();
 	}
 
+	/// Rebuilds the spatial index from the current position of all the active agents
+	pub fn reindex(&mut self) {
+		let extent = self.extent;
+		self.index.reset(&extent);
+		for (_, swarm) in self.swarms.iter() {
+			for (id, agent) in swarm.agents().iter() {
+				if !agent.state.is_active() {
+					continue;
+				}
+				let center = agent.segments[0].transform.position;
+				let radius = agent.segments.iter().fold(0., |r: f32, segment| {
+					r.max((segment.transform.position - center).magnitude() + segment.growing_radius())
+				});
+				self.index.insert(*id, center, radius);
+			}
+		}
+	}
+
 	pub fn sweep(&mut self) -> Box<[Agent]> {
 		let mut v = Vec::new();
 		for (_, agents) in self.swarms.iter_mut() {
 			agents.free_resources(&mut v);
 		}
+		let now = self.clock.seconds();
+		for agent in &v {
+			match agent.id().type_of() {
+				AgentType::Minion | AgentType::Enemy => self.phylogeny.die(agent.id(), now),
+				AgentType::Spore => if let Some(mother) = agent.lineage.mother {
+					self.phylogeny.discard(mother)
+				},
+				_ => {}
+			}
+		}
 		v.into_boxed_slice()
 	}
 
-	pub fn serialize(&self) -> io::Result<String> {
+	pub fn serialize(&self, format: persist::Format) -> io::Result<String> {
 		let now: DateTime<Utc> = Utc::now();
-		let file_name = now.format(DUMP_FILE_PATTERN_JSON).to_string();
+		let file_name = now.format(match format {
+			persist::Format::Json => DUMP_FILE_PATTERN_JSON,
+			persist::Format::Binary => DUMP_FILE_PATTERN_BINARY,
+		}).to_string();
 		persist::Serializer::save(&file_name, self)?;
 		Ok(file_name)
 	}
 
-	pub fn dump(&self) -> io::Result<String> {
+	pub fn dump_lineage(&self) -> io::Result<String> {
+		let now: DateTime<Utc> = Utc::now();
+		let newick_file_name = now.format(DUMP_FILE_PATTERN_NEWICK).to_string();
+		let json_file_name = now.format(DUMP_FILE_PATTERN_LINEAGE).to_string();
+		fs::create_dir_all(DUMP_LINEAGE_DIR)?;
+		let mut f = fs::File::create(&newick_file_name)?;
+		f.write_all(self.phylogeny.to_newick().as_bytes())?;
+		let mut f = fs::File::create(&json_file_name)?;
+		f.write_all(self.phylogeny.to_json()?.as_bytes())?;
+		Ok(newick_file_name)
+	}
+
+	/// Writes the living minions as a gene pool file, `source` is the snapshot the run was resumed from
+	pub fn dump(&self, source: Option<&str>) -> io::Result<String> {
 		let now: DateTime<Utc> = Utc::now();
 		let file_name = now.format(DUMP_FILE_PATTERN_CSV).to_string();
 		let mut f = fs::File::create(&file_name)?;
-		for (_, agent) in self.agents(agent::AgentType::Minion).iter() {
-			info!("{}", agent.dna().to_base64(base64::STANDARD));
-			f.write_fmt(format_args!(
-				"{}\n",
-				agent.dna().to_base64(base64::STANDARD)
-			))?;
-		}
+		let records = self.agents(agent::AgentType::Minion)
+			.iter()
+			.map(|(id, agent)| {
+				info!("{}", agent.dna().to_base64(base64::STANDARD));
+				pool::Record {
+					generation: Some(agent.lineage.generation),
+					lineage: Some(*id),
+					species: self.species.species_of(*id),
+					energy: Some(agent.state.energy()),
+					age: Some(agent.segments[0].state.age_seconds().get()),
+					source: source.map(|s| s.to_owned()),
+					..pool::Record::new(agent.dna().clone())
+				}
+			})
+			.collect::<Vec<_>>();
+		pool::write(&mut f, &records)?;
 		Ok(file_name)
 	}
 }
@@ -86,16 +86,20 @@ impl EmitterStyle {
 	}
 }
 
+/// A particle as drawn in the current frame, with enough of its motion to carry on after a reload
 #[allow(unused)]
+#[derive(Clone)]
 pub struct Particle {
 	transform: Transform,
 	direction: Velocity,
+	speed: f32,
 	tag: isize,
 	trail: Box<[Position]>,
 	faders: [f32; 4],
 	color: (Rgba<f32>, Rgba<f32>),
 	effect: (Rgba<f32>, Rgba<f32>),
 	age: Seconds,
+	lifespan: Seconds,
 }
 
 #[derive(Default, Clone)]
@@ -135,21 +139,25 @@ impl Emitter {
 impl Particle {
 	pub fn new(transform: Transform,
 			   direction: Velocity,
+			   speed: f32,
 			   tag: isize,
 			   trail: Box<[Position]>,
 			   faders: [f32; 4],
 			   color: (Rgba<f32>, Rgba<f32>),
 			   effect: (Rgba<f32>, Rgba<f32>),
-			   age: Seconds) -> Particle {
+			   age: Seconds,
+			   lifespan: Seconds) -> Particle {
 		Particle {
 			transform,
 			direction,
+			speed,
 			tag,
 			trail,
 			faders,
 			color,
 			effect,
 			age,
+			lifespan,
 		}
 	}
 
@@ -157,6 +165,21 @@ impl Particle {
 		self.transform.clone()
 	}
 
+	pub fn direction(&self) -> Velocity { self.direction }
+
+	pub fn speed(&self) -> f32 { self.speed }
+
+	pub fn tag(&self) -> isize { self.tag }
+
+	pub fn faders(&self) -> [f32; 4] { self.faders }
+
+	/// Start and end colors, and start and end effects
+	pub fn palette(&self) -> ((Rgba<f32>, Rgba<f32>), (Rgba<f32>, Rgba<f32>)) { (self.color, self.effect) }
+
+	pub fn age(&self) -> Seconds { self.age }
+
+	pub fn lifespan(&self) -> Seconds { self.lifespan }
+
 	pub fn trail(&self) -> &[Position] {
 		&self.trail
 	}
@@ -1,18 +1,276 @@
 use std::io;
+use std::io::{Read, Write};
 use std::fs;
+use std::fmt;
+use std::path::Path;
+use std::collections::HashMap;
+use backend::obj::Transformable;
 use backend::world;
 use backend::world::agent;
+use backend::world::flow;
+use backend::world::feeder;
+use backend::world::arena;
+use backend::world::topology::Topology;
 use backend::world::gen;
+use backend::world::lineage;
+use backend::world::pool;
+use backend::world::particle;
+use backend::world::segment;
+use backend::world::species;
 use num_traits::FromPrimitive;
 use core::geometry;
 use core::clock;
+use core::rng;
 use serde_json;
+use serde_json::Value;
+use rmp_serde;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use serialize::base64::{self, ToBase64, FromBase64};
 
+/// Version of the snapshot schema written by this build.
+/// Bump it whenever the schema changes in a way `#[serde(default)]` cannot absorb, and add a migration.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Binary snapshots start with these bytes and the format version, then a compressed stream
+const BINARY_MAGIC: &[u8; 4] = b"OIDS";
+
+/// How a snapshot is encoded on disk, chosen by the extension of the file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	/// pretty-printed JSON, `.json` or anything else
+	Json,
+	/// MessagePack with named fields, gzipped, `.oids`
+	Binary,
+}
+
+impl Default for Format {
+	fn default() -> Self {
+		Format::Json
+	}
+}
+
+impl Format {
+	pub fn of(file_path: &str) -> Self {
+		match Path::new(file_path).extension().and_then(|e| e.to_str()) {
+			Some("oids") => Format::Binary,
+			_ => Format::Json,
+		}
+	}
+
+	/// Whether the file looks like a snapshot in any of the formats
+	pub fn is_snapshot(file_path: &str) -> bool {
+		match Path::new(file_path).extension().and_then(|e| e.to_str()) {
+			Some("json") | Some("oids") => true,
+			_ => false,
+		}
+	}
+}
+
+fn invalid_data<E: fmt::Display>(e: E) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Upgrades a snapshot from the version at its index to the next one
+type Migration = fn(&mut Value, &mut LoadReport);
+
+const MIGRATIONS: &[Migration] = &[
+	migrate_v0_to_v1,
+	migrate_v1_to_v2,
+];
+
+/// Snapshots written before versioning: every section added since is optional,
+/// so this only records which ones are missing and will keep what the world was created with
+fn migrate_v0_to_v1(src: &mut Value, report: &mut LoadReport) {
+	for &(section, fallback) in &[
+		("rng_state", "a fresh random generator"),
+		("lineage", "living creatures as founders"),
+		("pheromones", "an empty pheromone field"),
+		("flow", "the currents of the configuration"),
+		("feeders", "the feeders of the configuration"),
+		("obstacles", "the arena of the configuration"),
+		("topology", "the topology of the configuration"),
+	] {
+		if src.get(section).map(|v| v.is_null()).unwrap_or(true) {
+			report.repaired(format!("no {} in the snapshot, using {}", section, fallback));
+		}
+	}
+}
+
+/// Snapshots which kept only the pose of the first segment of each agent, and none of its memory:
+/// the other segments are rebuilt from the genome, and the rest starts afresh
+fn migrate_v1_to_v2(src: &mut Value, report: &mut LoadReport) {
+	let agents = src.get("swarms")
+		.and_then(|swarms| swarms.as_array())
+		.map(|swarms| swarms.iter()
+			.filter_map(|swarm| swarm.get("agents").and_then(|agents| agents.as_array()))
+			.map(|agents| agents.len())
+			.sum::<usize>())
+		.unwrap_or(0);
+	if agents > 0 {
+		report.repaired(format!("{} agents saved without segment poses, lifecycle or memory, rebuilt from their genome", agents));
+	}
+	if src.get("seconds").map(|v| v.is_null()).unwrap_or(true) {
+		report.repaired("no simulation time in the snapshot, starting from zero".to_owned());
+	}
+}
+
+/// Brings a snapshot of any older version up to `FORMAT_VERSION`, one migration at a time
+fn migrate(src: &mut Value, report: &mut LoadReport) -> Result<(), String> {
+	if !src.is_object() {
+		return Err("the snapshot is not a JSON object".to_owned());
+	}
+	let version = match src.get("format_version") {
+		None | Some(&Value::Null) => 0,
+		Some(v) => v.as_u64().ok_or_else(|| format!("invalid format_version: {}", v))? as u32,
+	};
+	if version > FORMAT_VERSION {
+		return Err(format!("format version {} is newer than the supported {}", version, FORMAT_VERSION));
+	}
+	if version < FORMAT_VERSION {
+		report.migrated_from = Some(version);
+	}
+	for migration in &MIGRATIONS[version as usize..] {
+		migration(src, report);
+	}
+	if let Some(map) = src.as_object_mut() {
+		map.insert("format_version".to_owned(), Value::from(FORMAT_VERSION));
+	}
+	Ok(())
+}
+
+/// What happened while loading a snapshot: which version it was upgraded from,
+/// and every item which had to be dropped or patched up
+#[derive(Clone, Debug, Default)]
+pub struct LoadReport {
+	pub migrated_from: Option<u32>,
+	pub skipped: Vec<String>,
+	pub repaired: Vec<String>,
+}
+
+impl LoadReport {
+	fn skipped(&mut self, item: String) {
+		self.skipped.push(item);
+	}
+
+	fn repaired(&mut self, item: String) {
+		self.repaired.push(item);
+	}
+
+	fn append(&mut self, other: LoadReport) {
+		self.migrated_from = self.migrated_from.or(other.migrated_from);
+		self.skipped.extend(other.skipped);
+		self.repaired.extend(other.repaired);
+	}
+
+	/// Whether the snapshot was loaded exactly as it was saved
+	pub fn is_clean(&self) -> bool {
+		self.migrated_from.is_none() && self.skipped.is_empty() && self.repaired.is_empty()
+	}
+
+	/// Logs the report, one line per item
+	pub fn log(&self, file_path: &str) {
+		if self.is_clean() {
+			info!("Loaded {}", file_path);
+			return;
+		}
+		if let Some(version) = self.migrated_from {
+			info!("Loaded {}, upgraded from format version {} to {}", file_path, version, FORMAT_VERSION);
+		} else {
+			info!("Loaded {}", file_path);
+		}
+		for item in &self.skipped {
+			warn!("Skipped {}", item);
+		}
+		for item in &self.repaired {
+			warn!("Repaired {}", item);
+		}
+	}
+}
+
+impl fmt::Display for LoadReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if let Some(version) = self.migrated_from {
+			writeln!(f, "upgraded from format version {} to {}", version, FORMAT_VERSION)?;
+		}
+		for item in &self.skipped {
+			writeln!(f, "skipped {}", item)?;
+		}
+		for item in &self.repaired {
+			writeln!(f, "repaired {}", item)?;
+		}
+		Ok(())
+	}
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Segment {
 	charge: f32,
 	target_charge: f32,
+	#[serde(default)]
+	pose: Option<Pose>,
+	#[serde(default)]
+	intent: Option<Intent>,
+}
+
+/// Where a segment is and how it moves, which sets the angles of the joints too
+#[derive(Serialize, Deserialize, Debug)]
+struct Pose {
+	x: f32,
+	y: f32,
+	angle: f32,
+	vx: f32,
+	vy: f32,
+	spin: f32,
+	maturity: f32,
+	age_seconds: f64,
+	age_frames: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Rotation {
+	None,
+	Orientation(f32, f32),
+	LookAt(f32, f32),
+	Turn(f32),
+	FromVelocity,
+}
+
+/// What the brain told the segment to do, which physics acts upon in the next frame
+#[derive(Serialize, Deserialize, Debug)]
+enum Intent {
+	Idle,
+	Move(f32, f32),
+	Brake(f32, f32),
+	RunAway(f32, f32),
+	PilotTo(Option<(f32, f32)>, Rotation),
+}
+
+/// An hourglass, as seconds of the simulation clock
+#[derive(Serialize, Deserialize, Debug)]
+struct Lifecycle {
+	start: f64,
+	capacity: f64,
+	timeout: f64,
+}
+
+/// A particle on its way, along with its trail
+#[derive(Serialize, Deserialize, Debug)]
+struct Particle {
+	x: f32,
+	y: f32,
+	angle: f32,
+	dx: f32,
+	dy: f32,
+	speed: f32,
+	tag: isize,
+	trail: Vec<(f32, f32)>,
+	faders: [f32; 4],
+	color: ([f32; 4], [f32; 4]),
+	effect: ([f32; 4], [f32; 4]),
+	age: f64,
+	lifespan: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -32,6 +290,77 @@ struct Agent {
 	phase: f32,
 	energy: f32,
 	segments: Vec<Segment>,
+	#[serde(default)]
+	mother: Option<usize>,
+	#[serde(default)]
+	father: Option<usize>,
+	#[serde(default)]
+	generation: usize,
+	#[serde(default)]
+	birth: f64,
+	#[serde(default)]
+	brain: Option<Brain>,
+	#[serde(default)]
+	lifecycle: Option<Lifecycle>,
+	#[serde(default)]
+	target: Option<usize>,
+	#[serde(default)]
+	target_position: Option<(f32, f32)>,
+	/// the genome a spore was fertilised with
+	#[serde(default)]
+	foreign_dna: Option<String>,
+	/// newest first
+	#[serde(default)]
+	trajectory: Vec<(f32, f32)>,
+	#[serde(default)]
+	fasting: f64,
+	#[serde(default)]
+	reward: f32,
+}
+
+/// Weights as they were at the time of the snapshot, which may have been learned
+#[derive(Serialize, Deserialize, Debug)]
+struct Brain {
+	weights_in: Vec<Vec<f32>>,
+	weights_hidden: Vec<Vec<f32>>,
+	weights_out: Vec<Vec<f32>>,
+}
+
+/// A feeder, with its definition and where it is in its cycle
+#[derive(Serialize, Deserialize, Debug)]
+struct Feeder {
+	id: usize,
+	x: f32,
+	y: f32,
+	spec: feeder::FeederSpec,
+	state: feeder::FeederState,
+}
+
+/// Concentrations of every pheromone channel, by row
+#[derive(Serialize, Deserialize, Debug)]
+struct Pheromones {
+	cols: usize,
+	rows: usize,
+	channels: Vec<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LineageNode {
+	id: usize,
+	mother: Option<usize>,
+	father: Option<usize>,
+	generation: usize,
+	birth: f64,
+	death: Option<f64>,
+	founder: Option<String>,
+	pending: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Species {
+	id: usize,
+	representative: String,
+	founded: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -43,6 +372,8 @@ pub struct Swarm {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct World {
+	#[serde(default)]
+	format_version: u32,
 	left: f32,
 	bottom: f32,
 	right: f32,
@@ -53,6 +384,43 @@ pub struct World {
 	minion_gene_pool_index: usize,
 	resource_gene_pool: Vec<String>,
 	resource_gene_pool_index: usize,
+	#[serde(default)]
+	enemy_gene_pool: Vec<String>,
+	#[serde(default)]
+	enemy_gene_pool_index: usize,
+	#[serde(default)]
+	rng_state: Option<rng::RngState>,
+	#[serde(default)]
+	lineage: Vec<LineageNode>,
+	#[serde(default)]
+	species_seq: usize,
+	#[serde(default)]
+	species: Vec<Species>,
+	#[serde(default)]
+	pheromones: Option<Pheromones>,
+	#[serde(default)]
+	flow: Option<flow::Flow>,
+	#[serde(default)]
+	feeders: Option<Vec<Feeder>>,
+	#[serde(default)]
+	obstacles: Option<Vec<arena::Obstacle>>,
+	#[serde(default)]
+	topology: Option<Topology>,
+	/// simulation time, which all the lifecycles and ages count from
+	#[serde(default)]
+	seconds: Option<f64>,
+	#[serde(default)]
+	phase: Option<[f32; 4]>,
+	#[serde(default)]
+	species_due: Option<f64>,
+	/// minion and species ids, as of the last classification
+	#[serde(default)]
+	species_members: Option<Vec<(usize, usize)>>,
+	#[serde(default)]
+	particles: Vec<Particle>,
+	/// the stream of the particle effects, forked from `rng_state` if missing
+	#[serde(default)]
+	particle_rng_state: Option<rng::RngState>,
 }
 
 pub struct Serializer;
@@ -86,6 +454,108 @@ impl Serializer {
 				phase: src.state.phase(),
 				energy: src.state.energy(),
 				segments: src.segments().iter().map(|s| serialize_segment(s)).collect(),
+				mother: src.lineage.mother,
+				father: src.lineage.father,
+				generation: src.lineage.generation,
+				birth: src.lineage.birth.into(),
+				brain: serialize_brain(src.brain()),
+				lifecycle: Some(Lifecycle {
+					start: src.state.lifecycle().start().get(),
+					capacity: src.state.lifecycle().capacity().get(),
+					timeout: src.state.lifecycle().timeout().get(),
+				}),
+				target: *src.state.target(),
+				target_position: Some((src.state.target_position().x, src.state.target_position().y)),
+				foreign_dna: src.state.foreign_dna().as_ref().map(|dna| dna.to_base64(base64::STANDARD)),
+				trajectory: src.state.trajectory().iter().map(|p| (p.x, p.y)).collect(),
+				fasting: src.state.fasting(),
+				reward: src.state.reward(),
+			}
+		}
+
+		fn serialize_intent(src: &segment::Intent) -> Intent {
+			fn pair(p: &geometry::Position) -> (f32, f32) { (p.x, p.y) }
+			match *src {
+				segment::Intent::Idle => Intent::Idle,
+				segment::Intent::Move(ref f) => Intent::Move(f.x, f.y),
+				segment::Intent::Brake(ref f) => Intent::Brake(f.x, f.y),
+				segment::Intent::RunAway(ref f) => Intent::RunAway(f.x, f.y),
+				segment::Intent::PilotTo(ref f, ref rotation) => Intent::PilotTo(f.as_ref().map(pair), match *rotation {
+					segment::PilotRotation::None => Rotation::None,
+					segment::PilotRotation::Orientation(ref d) => Rotation::Orientation(d.x, d.y),
+					segment::PilotRotation::LookAt(ref t) => Rotation::LookAt(t.x, t.y),
+					segment::PilotRotation::Turn(angle) => Rotation::Turn(angle),
+					segment::PilotRotation::FromVelocity => Rotation::FromVelocity,
+				}),
+			}
+		}
+
+		// a particle at rest has no direction, and JSON has no NaN
+		fn finite(v: f32) -> f32 {
+			if v.is_finite() { v } else { 0. }
+		}
+
+		fn serialize_particle(src: &particle::Particle) -> Particle {
+			let transform = src.transform();
+			let (color, effect) = src.palette();
+			Particle {
+				x: transform.position.x,
+				y: transform.position.y,
+				angle: transform.angle,
+				dx: finite(src.direction().x),
+				dy: finite(src.direction().y),
+				speed: finite(src.speed()),
+				tag: src.tag(),
+				trail: src.trail().iter().map(|p| (p.x, p.y)).collect(),
+				faders: src.faders(),
+				color,
+				effect,
+				age: src.age().get(),
+				lifespan: src.lifespan().get(),
+			}
+		}
+
+		fn serialize_brain(src: &agent::Brain) -> Option<Brain> {
+			if src.hidden_units() == 0 {
+				None
+			} else {
+				Some(Brain {
+					weights_in: src.weights_in.clone(),
+					weights_hidden: src.weights_hidden.clone(),
+					weights_out: src.weights_out.clone(),
+				})
+			}
+		}
+
+		fn serialize_lineage_node(src: &lineage::Node) -> LineageNode {
+			LineageNode {
+				id: src.id,
+				mother: src.mother,
+				father: src.father,
+				generation: src.generation,
+				birth: src.birth,
+				death: src.death,
+				founder: src.founder.as_ref().map(|dna| dna.to_base64(base64::STANDARD)),
+				pending: src.pending,
+			}
+		}
+
+		fn serialize_species(src: &species::Species) -> Species {
+			Species {
+				id: src.id,
+				representative: src.representative.to_base64(base64::STANDARD),
+				founded: src.founded,
+			}
+		}
+
+		fn serialize_feeder(src: &world::Feeder) -> Feeder {
+			let position = src.transform().position;
+			Feeder {
+				id: src.id(),
+				x: position.x,
+				y: position.y,
+				spec: src.spec().clone(),
+				state: src.state().clone(),
 			}
 		}
 
@@ -93,22 +563,41 @@ impl Serializer {
 			Segment {
 				charge: src.state.get_charge(),
 				target_charge: src.state.target_charge(),
+				pose: Some(Pose {
+					x: src.transform.position.x,
+					y: src.transform.position.y,
+					angle: src.transform.angle,
+					vx: src.motion.velocity.x,
+					vy: src.motion.velocity.y,
+					spin: src.motion.spin,
+					maturity: src.state.maturity(),
+					age_seconds: src.state.age_seconds().get(),
+					age_frames: src.state.age_frames(),
+				}),
+				intent: Some(serialize_intent(&src.state.intent)),
 			}
 		}
 
+		// props are rebuilt from the obstacles, their shape is not in their genes
 		let swarms = world.swarms()
 			.iter()
+			.filter(|&(k, _v)| *k != agent::AgentType::Prop)
 			.map(|(_k, v)| serialize_swarm(v))
 			.collect();
 		let minion_gene_pool: Vec<_> = world.minion_gene_pool
 			.gene_pool_iter()
 			.map(|dna| dna.to_base64(base64::STANDARD))
 			.collect();
+		let enemy_gene_pool: Vec<_> = world.enemy_gene_pool
+			.gene_pool_iter()
+			.map(|dna| dna.to_base64(base64::STANDARD))
+			.collect();
 		let resource_gene_pool: Vec<_> = world.resource_gene_pool
 			.gene_pool_iter()
 			.map(|dna| dna.to_base64(base64::STANDARD))
 			.collect();
 		World {
+			format_version: FORMAT_VERSION,
 			left: world.extent.min.x,
 			bottom: world.extent.min.y,
 			right: world.extent.max.x,
@@ -119,44 +608,222 @@ impl Serializer {
 			minion_gene_pool_index: world.minion_gene_pool.gene_pool_index(),
 			resource_gene_pool,
 			resource_gene_pool_index: world.resource_gene_pool.gene_pool_index(),
+			enemy_gene_pool,
+			enemy_gene_pool_index: world.enemy_gene_pool.gene_pool_index(),
+			rng_state: Some(world.rng.state()),
+			lineage: world.phylogeny.nodes().into_iter().map(serialize_lineage_node).collect(),
+			species_seq: world.species.seq(),
+			species: world.species.species().iter().map(serialize_species).collect(),
+			pheromones: Some(Pheromones {
+				cols: world.pheromones().cols(),
+				rows: world.pheromones().rows(),
+				channels: world.pheromones().channels().to_vec(),
+			}),
+			flow: Some(world.flow().clone()),
+			feeders: Some(world.feeders().iter().map(serialize_feeder).collect()),
+			obstacles: Some(world.obstacles().to_vec()),
+			topology: Some(world.topology()),
+			seconds: Some(world.clock.seconds().get()),
+			phase: Some(world.phase),
+			species_due: Some(world.species_due),
+			species_members: Some(world.species.members().iter().map(|(id, species)| (*id, *species)).collect()),
+			particles: world.particles().iter().map(serialize_particle).collect(),
+			particle_rng_state: Some(world.particle_rng.state()),
 		}
 	}
 
-	pub fn restore_snapshot(src: World, world: &mut world::World) {
+	/// Restores the world from a snapshot, dropping or patching up whatever does not fit
+	pub fn restore_snapshot(src: World, world: &mut world::World) -> LoadReport {
+		fn same_shape(a: &[Vec<f32>], b: &[Vec<f32>]) -> bool {
+			a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.len() == y.len())
+		}
+
+		// weights only fit the brain that the same genome develops
+		fn restore_brain(src: &Brain, dest: &mut agent::Brain) -> bool {
+			if same_shape(&src.weights_in, &dest.weights_in)
+				&& same_shape(&src.weights_hidden, &dest.weights_hidden)
+				&& same_shape(&src.weights_out, &dest.weights_out) {
+				dest.weights_in = src.weights_in.clone();
+				dest.weights_hidden = src.weights_hidden.clone();
+				dest.weights_out = src.weights_out.clone();
+				true
+			} else {
+				false
+			}
+		}
+
+		// only well-formed, non-empty genomes make it into a gene pool
+		fn valid_pool(name: &str, src: &[String], report: &mut LoadReport) -> Vec<String> {
+			let mut valid = Vec::with_capacity(src.len());
+			for (i, dna) in src.iter().enumerate() {
+				match dna.from_base64() {
+					Ok(ref bytes) if !bytes.is_empty() => valid.push(dna.clone()),
+					_ => report.skipped(format!("{} gene pool entry {}: malformed DNA", name, i)),
+				}
+			}
+			valid
+		}
+
+		fn restore_intent(src: &Intent) -> segment::Intent {
+			fn position(x: f32, y: f32) -> geometry::Position { geometry::Position::new(x, y) }
+			match *src {
+				Intent::Idle => segment::Intent::Idle,
+				Intent::Move(x, y) => segment::Intent::Move(position(x, y)),
+				Intent::Brake(x, y) => segment::Intent::Brake(position(x, y)),
+				Intent::RunAway(x, y) => segment::Intent::RunAway(position(x, y)),
+				Intent::PilotTo(force, ref rotation) => segment::Intent::PilotTo(force.map(|(x, y)| position(x, y)), match *rotation {
+					Rotation::None => segment::PilotRotation::None,
+					Rotation::Orientation(x, y) => segment::PilotRotation::Orientation(position(x, y)),
+					Rotation::LookAt(x, y) => segment::PilotRotation::LookAt(position(x, y)),
+					Rotation::Turn(angle) => segment::PilotRotation::Turn(angle),
+					Rotation::FromVelocity => segment::PilotRotation::FromVelocity,
+				}),
+			}
+		}
+
+		let mut report = LoadReport::default();
+
+		if let Some(seconds) = src.seconds {
+			world.clock = clock::SimulationTimer::from(clock::seconds(seconds));
+		}
 		let timer = world.clock.clone();
+		if let Some(phase) = src.phase {
+			world.phase = phase;
+		}
 		world.extent.min.x = src.left;
 		world.extent.min.y = src.bottom;
 		world.extent.max.x = src.right;
 		world.extent.max.y = src.top;
 		world.regenerations = src.regenerations;
+		if let Some(topology) = src.topology {
+			world.set_topology(topology);
+		}
+		// snapshots taken before currents existed keep the flow of the configuration
+		if let Some(flow) = src.flow {
+			world.flow = flow;
+		}
+		// and those taken before feeders had any state keep the feeders of the configuration
+		if let Some(feeders) = src.feeders {
+			world.restore_feeders(feeders
+				.into_iter()
+				.map(|f| world::Feeder::restore(f.id, f.spec, f.state, geometry::Position::new(f.x, f.y)))
+				.collect());
+		}
+		if let Some(obstacles) = src.obstacles {
+			world.set_obstacles(obstacles);
+		}
+		if let Some(state) = src.rng_state {
+			world.rng = rng::SimulationRng::from_state(state);
+		}
+		world.particle_rng = match src.particle_rng_state {
+			Some(state) => rng::SimulationRng::from_state(state),
+			None => world.rng.clone().fork(),
+		};
 
-		world.minion_gene_pool.populate_from_base64(&src.minion_gene_pool, src.minion_gene_pool_index);
-		world.resource_gene_pool.populate_from_base64(&src.resource_gene_pool, src.resource_gene_pool_index);
+		let minion_gene_pool = valid_pool("minion", &src.minion_gene_pool, &mut report);
+		if !minion_gene_pool.is_empty() {
+			world.minion_gene_pool.populate_from_base64(&minion_gene_pool, src.minion_gene_pool_index);
+		} else {
+			report.repaired("empty minion gene pool, using the default one".to_owned());
+		}
+		let resource_gene_pool = valid_pool("resource", &src.resource_gene_pool, &mut report);
+		if !resource_gene_pool.is_empty() {
+			world.resource_gene_pool.populate_from_base64(&resource_gene_pool, src.resource_gene_pool_index);
+		} else {
+			report.repaired("empty resource gene pool, using the default one".to_owned());
+		}
+		// snapshots taken before predators existed keep the pool the world was seeded with
+		let enemy_gene_pool = valid_pool("enemy", &src.enemy_gene_pool, &mut report);
+		if !enemy_gene_pool.is_empty() {
+			world.enemy_gene_pool.populate_from_base64(&enemy_gene_pool, src.enemy_gene_pool_index);
+		}
 
 		let mut registered = Vec::new();
 		for src_swarm in src.swarms.iter() {
-			if let Some(agent_type) = agent::AgentType::from_usize(src_swarm.agent_type) {
-				let swarm = world.swarm_mut(&agent_type);
-				swarm.reset(src_swarm.seq);
-				for src_agent in &src_swarm.agents {
-					if let Ok(dna) = src_agent.dna.from_base64() {
-						let id = swarm.rebuild(src_agent.id, &mut gen::Genome::new(dna), agent::InitialState {
-							transform: geometry::Transform::from_components(src_agent.x, src_agent.y, src_agent.angle),
-							motion: geometry::Motion::from_components(src_agent.vx, src_agent.vy, src_agent.spin),
-							age_seconds: clock::seconds(src_agent.age_seconds),
-							age_frames: src_agent.age_frames,
-							maturity: Some(src_agent.maturity),
-							..Default::default()
-						}, &timer);
-						if let Some(agent) = swarm.get_mut(id) {
-							agent.state.restore(src_agent.flags, src_agent.phase, src_agent.energy);
-
-							for (src_segment, dest_segment) in src_agent.segments.iter().zip(agent.segments_mut().iter_mut()) {
-								dest_segment.state.restore(src_segment.charge, src_segment.target_charge);
-							};
-							registered.push(id);
+			let agent_type = match agent::AgentType::from_usize(src_swarm.agent_type) {
+				Some(agent_type) => agent_type,
+				None => {
+					report.skipped(format!("swarm of unknown agent type {}, with {} agents",
+										   src_swarm.agent_type, src_swarm.agents.len()));
+					continue;
+				}
+			};
+			let swarm = world.swarm_mut(&agent_type);
+			swarm.reset(src_swarm.seq);
+			for src_agent in &src_swarm.agents {
+				let dna = match src_agent.dna.from_base64() {
+					Ok(dna) => if dna.is_empty() {
+						report.skipped(format!("{} {}: empty DNA", agent_type, src_agent.id));
+						continue;
+					} else {
+						dna
+					},
+					Err(e) => {
+						report.skipped(format!("{} {}: malformed DNA ({})", agent_type, src_agent.id, e));
+						continue;
+					}
+				};
+				let id = swarm.rebuild(src_agent.id, &mut gen::Genome::new(dna), agent::InitialState {
+					transform: geometry::Transform::from_components(src_agent.x, src_agent.y, src_agent.angle),
+					motion: geometry::Motion::from_components(src_agent.vx, src_agent.vy, src_agent.spin),
+					age_seconds: clock::seconds(src_agent.age_seconds),
+					age_frames: src_agent.age_frames,
+					maturity: Some(src_agent.maturity),
+					lineage: agent::Lineage {
+						mother: src_agent.mother,
+						father: src_agent.father,
+						generation: src_agent.generation,
+						birth: clock::seconds(src_agent.birth),
+					},
+					..Default::default()
+				}, &timer);
+				if let Some(agent) = swarm.get_mut(id) {
+					agent.state.restore(src_agent.flags, src_agent.phase, src_agent.energy);
+					if let Some(ref src_brain) = src_agent.brain {
+						if !restore_brain(src_brain, agent.brain_mut()) {
+							report.repaired(format!("{} {}: learned weights do not fit its brain, using the innate ones", agent_type, id));
+						}
+					}
+					if src_agent.segments.len() != agent.segments().len() {
+						report.repaired(format!("{} {}: {} segments saved, {} developed",
+												agent_type, id, src_agent.segments.len(), agent.segments().len()));
+					}
+					if let Some(ref lifecycle) = src_agent.lifecycle {
+						agent.state.restore_lifecycle(clock::Hourglass::restore(
+							clock::seconds(lifecycle.start),
+							clock::seconds(lifecycle.capacity),
+							clock::seconds(lifecycle.timeout)));
+					}
+					let target_position = src_agent.target_position
+						.map(|(x, y)| geometry::Position::new(x, y))
+						.unwrap_or(*agent.state.target_position());
+					agent.state.retarget(src_agent.target, target_position);
+					if let Some(ref foreign_dna) = src_agent.foreign_dna {
+						match foreign_dna.from_base64() {
+							Ok(ref dna) if !dna.is_empty() => agent.state.fertilise(dna),
+							_ => report.repaired(format!("{} {}: malformed foreign DNA, left unfertilised", agent_type, id)),
 						}
 					}
+					let trajectory = src_agent.trajectory.iter()
+						.map(|&(x, y)| geometry::Position::new(x, y))
+						.collect::<Vec<_>>();
+					agent.state.restore_trajectory(&trajectory);
+					agent.state.restore_hunger(src_agent.fasting, src_agent.reward);
+					for (src_segment, dest_segment) in src_agent.segments.iter().zip(agent.segments_mut().iter_mut()) {
+						dest_segment.state.restore(src_segment.charge, src_segment.target_charge);
+						if let Some(ref pose) = src_segment.pose {
+							dest_segment.transform = geometry::Transform::from_components(pose.x, pose.y, pose.angle);
+							dest_segment.motion = geometry::Motion::from_components(pose.vx, pose.vy, pose.spin);
+							dest_segment.state.set_maturity(pose.maturity);
+							dest_segment.state.restore_age(clock::seconds(pose.age_seconds), pose.age_frames);
+						}
+						if let Some(ref intent) = src_segment.intent {
+							dest_segment.state.intent = restore_intent(intent);
+						}
+					};
+					registered.push(id);
+				} else {
+					report.skipped(format!("{} {}: could not be rebuilt", agent_type, src_agent.id));
 				}
 			}
 		}
@@ -165,18 +832,97 @@ impl Serializer {
 		for id in registered {
 			world.register(id);
 		}
-	}
 
-	#[allow(unused)]
-	pub fn from_string(source: &str, dest: &mut world::World) -> Result<(), serde_json::Error> {
-		let result: Result<World, _> = serde_json::from_str(source);
-		match result {
-			Ok(src) => {
-				Self::restore_snapshot(src, dest);
-				Ok(())
+		world.phylogeny.clear();
+		if src.lineage.is_empty() {
+			// older snapshots carry no ancestry, living minions become the founders
+			let now = timer.seconds();
+			let founders: Vec<_> = world.agents(agent::AgentType::Minion)
+				.iter()
+				.chain(world.agents(agent::AgentType::Enemy).iter())
+				.map(|(id, agent)| (*id, agent.dna().clone()))
+				.collect();
+			for (id, dna) in founders {
+				world.phylogeny.found(id, now, &dna);
+			}
+		} else {
+			for src_node in src.lineage {
+				let mut node = lineage::Node::new(src_node.id, src_node.mother, src_node.father, src_node.generation, src_node.birth);
+				node.death = src_node.death;
+				node.founder = match src_node.founder {
+					Some(founder) => match founder.from_base64() {
+						Ok(dna) => Some(dna),
+						Err(_) => {
+							report.repaired(format!("lineage of {}: malformed founder DNA, dropped", src_node.id));
+							None
+						}
+					},
+					None => None,
+				};
+				node.pending = src_node.pending;
+				world.phylogeny.restore(node);
+			}
+			world.phylogeny.rebuild_children();
+		}
+
+		let mut restored_species = Vec::with_capacity(src.species.len());
+		for s in &src.species {
+			match s.representative.from_base64() {
+				Ok(dna) => restored_species.push(species::Species::new(s.id, dna.into_boxed_slice(), s.founded, world.species.metric())),
+				Err(_) => report.skipped(format!("species {}: malformed representative DNA", s.id)),
+			}
+		}
+		world.species.restore(src.species_seq, restored_species);
+		// older snapshots have no members, which the next classification would assign anyway
+		match src.species_members {
+			Some(members) => {
+				world.species.restore_members(members.into_iter().collect());
+				world.species_due = src.species_due.unwrap_or(0.);
+			}
+			None => world.classify_species(),
+		}
+
+		world.clear_particles();
+		for p in src.particles {
+			let (color, effect) = (p.color, p.effect);
+			world.add_particle(particle::Particle::new(
+				geometry::Transform::from_components(p.x, p.y, p.angle),
+				geometry::Velocity::new(p.dx, p.dy),
+				p.speed,
+				p.tag,
+				p.trail.into_iter().map(|(x, y)| geometry::Position::new(x, y)).collect::<Vec<_>>().into_boxed_slice(),
+				p.faders,
+				color,
+				effect,
+				clock::seconds(p.age),
+				clock::seconds(p.lifespan),
+			));
+		}
+
+		world.pheromones_mut().clear();
+		if let Some(pheromones) = src.pheromones {
+			if !world.pheromones_mut().restore(pheromones.cols, pheromones.rows, &pheromones.channels) {
+				report.repaired(format!("pheromones of a different shape ({}x{}), cleared", pheromones.cols, pheromones.rows));
 			}
-			Err(e) => Err(e)
 		}
+		report
+	}
+
+	/// Parses a snapshot of any supported version, upgrading it to the current one.
+	/// Nothing is restored unless the whole snapshot can be read.
+	pub fn parse(mut src: Value) -> io::Result<(World, LoadReport)> {
+		let mut report = LoadReport::default();
+		migrate(&mut src, &mut report)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		let world = serde_json::from_value(src)?;
+		Ok((world, report))
+	}
+
+	#[allow(unused)]
+	pub fn from_string(source: &str, dest: &mut world::World) -> io::Result<LoadReport> {
+		let (src, mut report) = Self::parse(serde_json::from_str(source)?)?;
+		report.append(Self::restore_snapshot(src, dest));
+		Ok(report)
 	}
 
 	#[allow(unused)]
@@ -185,17 +931,227 @@ impl Serializer {
 		serde_json::to_string_pretty(&s_world)
 	}
 
+	/// Writes a snapshot, streaming it out in the format the extension of the file asks for
+	pub fn write(file_path: &str, src: &World) -> io::Result<()> {
+		let mut out_file = io::BufWriter::new(fs::File::create(file_path)?);
+		match Format::of(file_path) {
+			Format::Json => serde_json::to_writer_pretty(&mut out_file, src)?,
+			Format::Binary => {
+				let version = FORMAT_VERSION;
+				out_file.write_all(BINARY_MAGIC)?;
+				out_file.write_all(&[version as u8, (version >> 8) as u8, (version >> 16) as u8, (version >> 24) as u8])?;
+				let mut encoder = GzEncoder::new(out_file, Compression::default());
+				rmp_serde::encode::write_named(&mut encoder, src).map_err(invalid_data)?;
+				out_file = encoder.finish()?;
+			}
+		}
+		out_file.flush()
+	}
+
+	/// Reads a snapshot of any supported format and version, upgrading it to the current one.
+	/// Binary snapshots of the current version are decoded straight from the stream,
+	/// older ones go through the same migrations as JSON.
+	pub fn read(file_path: &str) -> io::Result<(World, LoadReport)> {
+		let mut in_file = io::BufReader::new(fs::File::open(file_path)?);
+		match Format::of(file_path) {
+			Format::Json => Self::parse(serde_json::from_reader(in_file)?),
+			Format::Binary => {
+				let mut header = [0u8; 8];
+				in_file.read_exact(&mut header)?;
+				if &header[0..4] != BINARY_MAGIC {
+					return Err(invalid_data(format!("{} is not a binary snapshot", file_path)));
+				}
+				let version = header[4] as u32 | (header[5] as u32) << 8 | (header[6] as u32) << 16 | (header[7] as u32) << 24;
+				let decoder = GzDecoder::new(in_file);
+				if version == FORMAT_VERSION {
+					let src = rmp_serde::from_read(decoder).map_err(invalid_data)?;
+					Ok((src, LoadReport::default()))
+				} else {
+					let value = rmp_serde::from_read(decoder).map_err(invalid_data)?;
+					Self::parse(value)
+				}
+			}
+		}
+	}
+
 	pub fn save(file_path: &str, world: &world::World) -> io::Result<()> {
-		let out_file = fs::File::create(file_path)?;
-		let s_world = Self::save_snapshot(world);
-		serde_json::to_writer_pretty(out_file, &s_world)?;
-		Ok(())
+		Self::write(file_path, &Self::save_snapshot(world))
 	}
 
-	pub fn load(file_path: &str, world: &mut world::World) -> io::Result<()> {
-		let in_file = fs::File::open(file_path)?;
-		let src = serde_json::from_reader(in_file)?;
-		Self::restore_snapshot(src, world);
-		Ok(())
+	/// Rewrites a snapshot in the format of the destination file, upgrading it on the way
+	pub fn convert(from_path: &str, to_path: &str) -> io::Result<LoadReport> {
+		let (src, report) = Self::read(from_path)?;
+		Self::write(to_path, &src)?;
+		Ok(report)
+	}
+
+	/// The genomes of the minions alive in a snapshot, as a gene pool,
+	/// or its minion gene pool if none of them were alive
+	pub fn extract_gene_pool(file_path: &str) -> io::Result<(Vec<pool::Record>, LoadReport)> {
+		let (src, report) = Self::read(file_path)?;
+		let species: HashMap<usize, usize> = src.species_members.as_ref()
+			.map(|members| members.iter().cloned().collect())
+			.unwrap_or_default();
+		let minions = src.swarms.iter()
+			.filter(|swarm| swarm.agent_type == agent::AgentType::Minion as usize)
+			.flat_map(|swarm| swarm.agents.iter());
+		let mut records = Vec::new();
+		for agent in minions {
+			if let Some(dna) = agent.dna.from_base64().ok().filter(|dna| !dna.is_empty()) {
+				records.push(pool::Record {
+					generation: Some(agent.generation),
+					lineage: Some(agent.id),
+					species: species.get(&agent.id).cloned(),
+					energy: Some(agent.energy),
+					age: Some(agent.age_seconds),
+					source: Some(file_path.to_owned()),
+					..pool::Record::new(dna.into_boxed_slice())
+				});
+			}
+		}
+		if records.is_empty() {
+			for dna in src.minion_gene_pool.iter().filter_map(|s| s.from_base64().ok()).filter(|dna| !dna.is_empty()) {
+				records.push(pool::Record {
+					source: Some(file_path.to_owned()),
+					..pool::Record::new(dna.into_boxed_slice())
+				});
+			}
+		}
+		Ok((records, report))
+	}
+
+	/// Loads a snapshot into the world, which is left untouched if the file cannot be read or parsed
+	pub fn load(file_path: &str, world: &mut world::World) -> io::Result<LoadReport> {
+		let (src, mut report) = Self::read(file_path)?;
+		report.append(Self::restore_snapshot(src, world));
+		Ok(report)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::process;
+	use core::resource::ResourceLoader;
+	use backend::messagebus::{Message, Outbox, PubSub};
+	use backend::systems::{ParticleSystem, System};
+	use backend::world::segment::{Intent, PilotRotation};
+
+	/// Every resource is missing, so the world falls back to its defaults
+	struct NoResources;
+
+	impl ResourceLoader<u8> for NoResources {
+		fn load(&self, key: &str) -> io::Result<Box<[u8]>> {
+			Err(io::Error::new(io::ErrorKind::NotFound, key.to_owned()))
+		}
+	}
+
+	fn new_world() -> world::World {
+		world::World::new(&NoResources, "missing_gene_pool.csv", 42)
+	}
+
+	#[test]
+	fn save_load_save_is_identical() {
+		let mut world = new_world();
+		world.init_minions();
+		world.init_enemies();
+		world.tick(clock::seconds(12.5));
+		world.add_feeder(feeder::FeederSpec::at(geometry::Position::new(3., -4.)));
+		*world.phase_mut() = [0.1, 0.2, 0.3, 0.4];
+		let foreign_dna = world.agents(agent::AgentType::Enemy).iter().next().unwrap().1.dna().clone();
+		{
+			let mut minions = world.agents_mut(agent::AgentType::Minion).iter_mut();
+			let (_, agent) = minions.next().unwrap();
+			agent.state.retarget(Some(7), geometry::Position::new(1.5, 2.5));
+			agent.state.fertilise(&foreign_dna);
+			agent.state.restore_hunger(3.25, 0.125);
+			for i in 0..5 {
+				agent.state.track_position(&geometry::Position::new(i as f32, -(i as f32)));
+			}
+			// bend every joint away from where the genome would put it
+			for (i, segment) in agent.segments_mut().iter_mut().enumerate() {
+				segment.transform.angle += 0.1 * i as f32;
+				segment.transform.position.x += 0.01 * i as f32;
+				segment.motion.spin = 0.5;
+				segment.state.intent = Intent::PilotTo(Some(geometry::Position::new(0., 1.)), PilotRotation::Turn(0.25));
+			}
+		}
+
+		let saved = Serializer::to_string(&world).unwrap();
+		let mut restored = new_world();
+		let report = Serializer::from_string(&saved, &mut restored).unwrap();
+		assert!(report.is_clean(), "{}", report);
+		assert_eq!(saved, Serializer::to_string(&restored).unwrap());
+	}
+
+	#[test]
+	fn binary_snapshots_convert_to_the_same_json() {
+		let mut world = new_world();
+		world.init_minions();
+		world.tick(clock::seconds(3.));
+		let dir = ::std::env::temp_dir();
+		let binary = dir.join(format!("rust_oids_persist_test_{}.oids", process::id())).to_string_lossy().into_owned();
+		let json = dir.join(format!("rust_oids_persist_test_{}.json", process::id())).to_string_lossy().into_owned();
+		Serializer::save(&binary, &world).unwrap();
+		Serializer::convert(&binary, &json).unwrap();
+		let mut converted = String::new();
+		fs::File::open(&json).unwrap().read_to_string(&mut converted).unwrap();
+		let _ = fs::remove_file(&binary);
+		let _ = fs::remove_file(&json);
+		assert_eq!(Serializer::to_string(&world).unwrap(), converted);
+	}
+
+	#[test]
+	fn unreadable_snapshot_leaves_the_world_untouched() {
+		let mut world = new_world();
+		world.init_minions();
+		let before = Serializer::to_string(&world).unwrap();
+		assert!(Serializer::from_string("{\"format_version\": 999}", &mut world).is_err());
+		assert!(Serializer::from_string("[]", &mut world).is_err());
+		assert_eq!(before, Serializer::to_string(&world).unwrap());
+	}
+
+	#[test]
+	fn old_snapshots_are_migrated() {
+		// the least a snapshot written before versioning had to hold
+		let v0 = "{\"left\": -20, \"bottom\": -20, \"right\": 20, \"top\": 20, \"swarms\": [], \"regenerations\": 3,
+			\"minion_gene_pool\": [], \"minion_gene_pool_index\": 0, \"resource_gene_pool\": [], \"resource_gene_pool_index\": 0}";
+		let mut world = new_world();
+		let report = Serializer::from_string(v0, &mut world).unwrap();
+		assert_eq!(Some(0), report.migrated_from);
+		assert!(report.repaired.iter().any(|r| r.contains("rng_state")), "{}", report);
+		assert_eq!(3, world.regenerations);
+		assert!(Serializer::to_string(&world).unwrap().contains(&format!("\"format_version\": {}", FORMAT_VERSION)));
+
+		let v1 = v0.replacen("{", "{\"format_version\": 1, ", 1);
+		let report = Serializer::from_string(&v1, &mut new_world()).unwrap();
+		assert_eq!(Some(1), report.migrated_from);
+		assert!(!report.repaired.iter().any(|r| r.contains("rng_state")), "{}", report);
+	}
+
+	/// Runs a new world with a burst of particles, and snapshots it
+	fn run_with_particles(seed: u64, frames: usize) -> String {
+		let mut world = world::World::new(&NoResources, "missing_gene_pool.csv", seed);
+		let mut bus = PubSub::new();
+		let mut particles = ParticleSystem::default();
+		particles.attach(&mut bus);
+		world.init_minions();
+		bus.post(Message::NewEmitter(particle::Emitter::for_dead_minion(geometry::Transform::default(), [1., 0.5, 0.25, 1.])));
+		let dt = clock::seconds(1. / 60.);
+		for _ in 0..frames {
+			world.cleanup_before();
+			particles.step(&world, dt);
+			particles.apply(&mut world, &bus);
+			world.tick(dt);
+		}
+		Serializer::to_string(&world).unwrap()
+	}
+
+	#[test]
+	fn same_seed_gives_the_same_snapshot() {
+		let a = run_with_particles(7, 30);
+		assert!(a.contains("\"particle_rng_state\""));
+		assert_eq!(a, run_with_particles(7, 30));
+		assert!(a != run_with_particles(8, 30));
 	}
 }
@@ -5,15 +5,18 @@ use core::color;
 use core::color::ToRgb;
 use core::geometry::*;
 use core::geometry::Transform;
-use core::clock::Timer;
+use core::clock::{SimulationTimer, Timer};
 use backend::world::segment;
 use backend::world::segment::*;
 use backend::world::agent;
-use backend::world::agent::N_WEIGHTS;
+use backend::world::agent::{N_INPUTS, N_OUTPUTS};
 use backend::world::agent::Agent;
 use backend::world::agent::Brain;
 use backend::world::agent::TypedBrain;
 use backend::world::gen::*;
+use backend::world::body::BodyPlan;
+use backend::world::sensor::Vision;
+use backend::world::pheromone::Gland;
 use cgmath;
 use cgmath::InnerSpace;
 
@@ -26,18 +29,29 @@ pub fn phenotype_of(agent_type: &agent::AgentType) -> Box<Phenotype> {
 		&agent::AgentType::Minion => Box::new(Minion {}),
 		&agent::AgentType::Spore => Box::new(Spore {}),
 		&agent::AgentType::Player => Box::new(Player {}),
+		&agent::AgentType::Enemy => Box::new(Enemy {}),
+		&agent::AgentType::Prop => Box::new(Prop {}),
 		_ => Box::new(Resource {}),
 	}
 }
 
+/// Develops a minion genome outside of any world, as the tools which draw, explain or compare genomes see it
+pub fn develop_minion(dna: &Dna, initial_state: agent::InitialState) -> agent::Agent {
+	phenotype_of(&agent::AgentType::Minion).develop(&mut Genome::copy_from(dna), 0, initial_state, &SimulationTimer::new())
+}
+
 struct Resource;
 
 struct Minion;
 
 struct Player;
 
+struct Enemy;
+
 struct Spore;
 
+struct Prop;
+
 impl Phenotype for Resource {
 	fn develop(&self, gen: &mut Genome, id: Id, initial_state: agent::InitialState, timer: &Timer) -> agent::Agent {
 		gen.next_integer::<u8>(0, 3);
@@ -103,6 +117,7 @@ impl Phenotype for Minion {
 			},
 			Livery {
 				albedo: albedo.to_rgba(),
+				hue: tint,
 				..Default::default()
 			},
 			gen.dna(),
@@ -111,127 +126,84 @@ impl Phenotype for Minion {
 		builder
 			.maturity(initial_state.maturity.unwrap_or(MATURITY_MINION_DEFAULT))
 			.gender(gender);
-
-		// personality parameters
-		let mut weights_in = [[0.; N_WEIGHTS]; N_WEIGHTS];
-		let mut weights_hidden = [[0.; N_WEIGHTS]; N_WEIGHTS];
-		let mut weights_out = [[0.; N_WEIGHTS]; N_WEIGHTS];
-		for i in 0..N_WEIGHTS {
-			for j in 0..N_WEIGHTS {
-				weights_in[i][j] = gen.next_float(-4., 4.);
-				weights_hidden[i][j] = gen.next_float(-4., 4.);
-				weights_out[i][j] = gen.next_float(-4., 4.);
-			}
-		}
-		builder
-			.hunger(&gen.next_float(0., 0.9))
-			.haste(&gen.next_float(0., 0.9))
-			.prudence(&gen.next_float(0., 0.9))
-			.fear(&gen.next_float(0.1, 5.))
-			.rest(&gen.next_float(0.2, 1.))
-			.thrust(&gen.next_float(0.2, 1.))
-			.weights_in(&weights_in)
-			.weights_hidden(&weights_hidden)
-			.weights_out(&weights_out);
+		develop_personality(gen, &mut builder);
 		// body plan and shape
 		let torso_shape = gen.any_poly();
-		let torso = builder.start(initial_state.transform, initial_state.motion, &torso_shape).index();
-		let head_shape = gen.iso_triangle();
-		let tail_shape = gen.vbar();
-		let i = ::std::cmp::max(torso_shape.length() as isize / 5, 1);
-		builder
-			.addr(
-				torso,
-				i,
-				&gen.star(),
-				Flags::ARM | Flags::JOINT | Flags::ACTUATOR | Flags::RUDDER,
-			)
-			.addl(
-				torso,
-				-i,
-				&gen.star(),
-				Flags::ARM | Flags::JOINT | Flags::ACTUATOR | Flags::RUDDER,
-			);
-
-		let head = builder
-			.add(
-				torso,
-				0,
-				&head_shape,
-				Flags::HEAD | Flags::MOUTH | Flags::SENSOR | Flags::TRACKER,
-			)
-			.index();
-		builder
-			.addr(
-				head,
-				1,
-				&gen.triangle(),
-				Flags::HEAD | Flags::ACTUATOR | Flags::RUDDER,
-			)
-			.addl(
-				head,
-				-1,
-				&gen.triangle(),
-				Flags::HEAD | Flags::ACTUATOR | Flags::RUDDER,
-			);
-
-		let mut belly = torso;
-		let mut belly_mid = torso_shape.mid();
-		while gen.next_integer(0, 3) == 0 {
-			let belly_shape = gen.any_poly();
-
-			belly = builder
-				.add(
-					belly,
-					belly_mid,
-					&belly_shape,
-					Flags::STORAGE | Flags::JOINT,
-				)
-				.index();
-			belly_mid = belly_shape.mid();
-			if belly_shape.length() > 6 {
-				if gen.next_integer(0, 1) == 0 {
-					builder.addr(
-						belly,
-						2,
-						&gen.star(),
-						Flags::ARM | Flags::ACTUATOR | Flags::RUDDER,
-					);
-				}
-				if gen.next_integer(0, 1) == 0 {
-					builder.addl(
-						belly,
-						-2,
-						&gen.star(),
-						Flags::ARM | Flags::ACTUATOR | Flags::RUDDER,
-					);
-				}
-			}
-			if belly > 20 {
-				break;
-			}
+		let plan = BodyPlan::decode(gen, &torso_shape);
+		builder.start(initial_state.transform, initial_state.motion, &torso_shape);
+		plan.build(&mut builder, &torso_shape);
+		builder.build(timer)
+	}
+}
+
+fn weight_matrix(gen: &mut Genome, rows: usize, columns: usize) -> Vec<Vec<f32>> {
+	let mut weights = vec![vec![0.; columns]; rows];
+	for row in weights.iter_mut() {
+		for w in row.iter_mut() {
+			*w = gen.next_float(-4., 4.);
 		}
-		let leg_shape = gen.star();
+	}
+	weights
+}
+
+/// Personality and brain weights, shared by all the species which have a brain
+fn develop_personality(gen: &mut Genome, builder: &mut AgentBuilder) {
+	// brain topology comes first, then the weights of each layer
+	let hidden_units = gen.next_integer::<usize>(BRAIN_MIN_HIDDEN_UNITS, BRAIN_MAX_HIDDEN_UNITS);
+	let weights_in = weight_matrix(gen, hidden_units, N_INPUTS);
+	let weights_hidden = weight_matrix(gen, hidden_units, hidden_units);
+	let weights_out = weight_matrix(gen, N_OUTPUTS, hidden_units);
+	// personality parameters
+	builder
+		.hunger(&gen.next_float(0., 0.9))
+		.haste(&gen.next_float(0., 0.9))
+		.prudence(&gen.next_float(0., 0.9))
+		.fear(&gen.next_float(0.1, 5.))
+		.rest(&gen.next_float(0.2, 1.))
+		.thrust(&gen.next_float(0.2, 1.))
+		.weights_in(&weights_in)
+		.weights_hidden(&weights_hidden)
+		.weights_out(&weights_out)
+		.learning_rate(&gen.next_float(0., BRAIN_MAX_LEARNING_RATE))
+		.vision(&Vision {
+			rays: gen.next_integer::<usize>(0, MAX_VISION_RAYS),
+			fov: gen.next_float(VISION_MIN_FOV, VISION_MAX_FOV),
+		})
+		.gland(&Gland {
+			channel: gen.next_integer::<usize>(0, PHEROMONE_CHANNELS - 1),
+			rate: gen.next_float(0., GLAND_MAX_RATE),
+		});
+}
+
+impl Phenotype for Enemy {
+	fn develop(&self, gen: &mut Genome, id: Id, initial_state: agent::InitialState, timer: &Timer) -> agent::Agent {
+		let gender = gen.next_integer::<u8>(0, 3);
+		// predators come in shades of red and purple
+		let tint = gen.next_float(0.8, 1.05) % 1.;
+		let albedo = color::Hsl::new(tint, 0.7, 0.4);
+		let mut builder = AgentBuilder::new(
+			id,
+			Material {
+				density: DENSITY_ENEMY,
+				..Default::default()
+			},
+			Livery {
+				albedo: albedo.to_rgba(),
+				..Default::default()
+			},
+			gen.dna(),
+			segment::State::with_charge(0., initial_state.charge, initial_state.charge),
+		);
 		builder
-			.addr(
-				belly,
-				belly_mid - 1,
-				&leg_shape,
-				Flags::LEG | Flags::ACTUATOR | Flags::THRUSTER,
-			)
-			.addl(
-				belly,
-				1 - belly_mid,
-				&leg_shape,
-				Flags::LEG | Flags::ACTUATOR | Flags::THRUSTER,
-			)
-			.add(
-				belly,
-				belly_mid,
-				&tail_shape,
-				Flags::TAIL | Flags::ACTUATOR | Flags::BRAKE,
-			)
-			.build(timer)
+			.maturity(initial_state.maturity.unwrap_or(MATURITY_MINION_DEFAULT))
+			.gender(gender);
+		develop_personality(gen, &mut builder);
+		// spiky torso, the body plan is grown as for minions
+		let torso_shape = gen.star();
+		let plan = BodyPlan::decode(gen, &torso_shape);
+		builder.start(initial_state.transform, initial_state.motion, &torso_shape);
+		plan.build(&mut builder, &torso_shape);
+		builder.build(timer)
 	}
 }
 
@@ -262,6 +234,31 @@ impl Phenotype for Spore {
 	}
 }
 
+impl Phenotype for Prop {
+	fn develop(&self, gen: &mut Genome, id: Id, initial_state: agent::InitialState, timer: &Timer) -> agent::Agent {
+		// obstacles come with their own shape, and are only shaded by the genes
+		let albedo = color::YPbPr::new(0.3, gen.next_float(-0.05, 0.05), gen.next_float(-0.05, 0.05));
+		let body = initial_state.shape.clone().unwrap_or_else(|| gen.ball());
+		let mut builder = AgentBuilder::new(
+			id,
+			Material {
+				density: DENSITY_DEFAULT,
+				..Default::default()
+			},
+			Livery {
+				albedo: albedo.to_rgba(),
+				..Default::default()
+			},
+			gen.dna(),
+			segment::State::with_charge(1., 1., 1.),
+		);
+		builder
+			.maturity(MATURITY_DEFAULT)
+			.start(initial_state.transform, Motion::default(), &body)
+			.build(timer)
+	}
+}
+
 pub struct AgentBuilder {
 	id: Id,
 	material: Material,
@@ -412,6 +409,21 @@ impl AgentBuilder {
 		self
 	}
 
+	pub fn learning_rate(&mut self, value: &<Brain as TypedBrain>::Parameter) -> &mut Self {
+		self.brain.learning_rate = value.clone();
+		self
+	}
+
+	pub fn vision(&mut self, vision: &Vision) -> &mut Self {
+		self.brain.vision = vision.clone();
+		self
+	}
+
+	pub fn gland(&mut self, gland: &Gland) -> &mut Self {
+		self.brain.gland = gland.clone();
+		self
+	}
+
 	pub fn weights_in(&mut self, weights_in: &<Brain as TypedBrain>::WeightMatrix) -> &mut Self {
 		self.brain.weights_in = weights_in.clone();
 		self
@@ -0,0 +1,178 @@
+use core::geometry::*;
+
+/// Gland genes: which channel a creature secretes into, and how much per second
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Gland {
+	pub channel: usize,
+	pub rate: f32,
+}
+
+/// Concentrations of a few chemical channels over a uniform grid covering the extent of the world.
+/// Creatures deposit into it, and the field spreads and fades on its own.
+/// In a wrapping world, it spreads across the borders too.
+#[derive(Clone)]
+pub struct Field {
+	origin: Position,
+	wrap: bool,
+	cell_size: f32,
+	cols: usize,
+	rows: usize,
+	channels: Vec<Vec<f32>>,
+}
+
+impl Field {
+	pub fn new(extent: &Rect, cell_size: f32, channels: usize) -> Self {
+		let cols = (((extent.max.x - extent.min.x) / cell_size).ceil() as usize).max(1);
+		let rows = (((extent.max.y - extent.min.y) / cell_size).ceil() as usize).max(1);
+		Field {
+			origin: extent.min,
+			wrap: false,
+			cell_size,
+			cols,
+			rows,
+			channels: vec![vec![0.; cols * rows]; channels],
+		}
+	}
+
+	/// Empties all the channels
+	pub fn clear(&mut self) {
+		for channel in self.channels.iter_mut() {
+			for value in channel.iter_mut() {
+				*value = 0.;
+			}
+		}
+	}
+
+	/// Whether the opposite borders of the field are neighbours
+	pub fn set_wrap(&mut self, wrap: bool) {
+		self.wrap = wrap;
+	}
+
+	pub fn cols(&self) -> usize { self.cols }
+
+	pub fn rows(&self) -> usize { self.rows }
+
+	pub fn cell_size(&self) -> f32 { self.cell_size }
+
+	pub fn channel_count(&self) -> usize { self.channels.len() }
+
+	/// Raw concentrations of every channel, by row
+	pub fn channels(&self) -> &[Vec<f32>] {
+		&self.channels
+	}
+
+	/// Replaces the concentrations, only if they have the same shape as the field
+	pub fn restore(&mut self, cols: usize, rows: usize, channels: &[Vec<f32>]) -> bool {
+		if cols != self.cols || rows != self.rows || channels.len() != self.channels.len()
+			|| channels.iter().any(|c| c.len() != cols * rows) {
+			return false;
+		}
+		self.channels = channels.to_vec();
+		true
+	}
+
+	fn cell_of(&self, p: &Position) -> (usize, usize) {
+		let col = ((p.x - self.origin.x) / self.cell_size).floor().max(0.) as usize;
+		let row = ((p.y - self.origin.y) / self.cell_size).floor().max(0.) as usize;
+		(col.min(self.cols - 1), row.min(self.rows - 1))
+	}
+
+	fn value(&self, channel: usize, col: usize, row: usize) -> f32 {
+		self.channels[channel][row * self.cols + col]
+	}
+
+	pub fn deposit(&mut self, channel: usize, p: Position, amount: f32) {
+		if channel < self.channels.len() {
+			let (col, row) = self.cell_of(&p);
+			self.channels[channel][row * self.cols + col] += amount;
+		}
+	}
+
+	/// Concentration at the given point, zero for unknown channels
+	pub fn sample(&self, channel: usize, p: Position) -> f32 {
+		if channel < self.channels.len() {
+			let (col, row) = self.cell_of(&p);
+			self.value(channel, col, row)
+		} else {
+			0.
+		}
+	}
+
+	/// Direction of the steepest increase of the concentration at the given point,
+	/// by central differences between the neighbouring cells
+	pub fn gradient(&self, channel: usize, p: Position) -> Position {
+		if channel >= self.channels.len() {
+			return Position::new(0., 0.);
+		}
+		let (col, row) = self.cell_of(&p);
+		let (cols, rows) = (self.cols, self.rows);
+		if self.wrap {
+			let (c0, c1) = ((col + cols - 1) % cols, (col + 1) % cols);
+			let (r0, r1) = ((row + rows - 1) % rows, (row + 1) % rows);
+			let dx = if cols > 2 {
+				(self.value(channel, c1, row) - self.value(channel, c0, row)) / (2. * self.cell_size)
+			} else { 0. };
+			let dy = if rows > 2 {
+				(self.value(channel, col, r1) - self.value(channel, col, r0)) / (2. * self.cell_size)
+			} else { 0. };
+			return Position::new(dx, dy);
+		}
+		let (c0, c1) = (col.saturating_sub(1), (col + 1).min(cols - 1));
+		let (r0, r1) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+		let dx = if c1 > c0 {
+			(self.value(channel, c1, row) - self.value(channel, c0, row)) / ((c1 - c0) as f32 * self.cell_size)
+		} else { 0. };
+		let dy = if r1 > r0 {
+			(self.value(channel, col, r1) - self.value(channel, col, r0)) / ((r1 - r0) as f32 * self.cell_size)
+		} else { 0. };
+		Position::new(dx, dy)
+	}
+
+	/// Spreads every channel to the neighbouring cells, and lets it evaporate.
+	/// `diffusion` is in cells per second, and it is clamped to keep the explicit scheme stable;
+	/// `evaporation` is the fraction lost each second. Nothing leaks out of the borders,
+	/// and in a wrapping field what reaches one border comes in from the opposite one.
+	pub fn step(&mut self, dt: f32, diffusion: f32, evaporation: f32) {
+		let k = (diffusion * dt).min(0.25);
+		let decay = (1. - evaporation).max(0.).powf(dt);
+		let (cols, rows, wrap) = (self.cols, self.rows, self.wrap);
+		for channel in self.channels.iter_mut() {
+			let previous = channel.clone();
+			for row in 0..rows {
+				for col in 0..cols {
+					let i = row * cols + col;
+					let center = previous[i];
+					let mut laplacian = 0.;
+					if col > 0 { laplacian += previous[i - 1] - center; }
+					else if wrap { laplacian += previous[i + cols - 1] - center; }
+					if col + 1 < cols { laplacian += previous[i + 1] - center; }
+					else if wrap { laplacian += previous[i + 1 - cols] - center; }
+					if row > 0 { laplacian += previous[i - cols] - center; }
+					else if wrap { laplacian += previous[i + (rows - 1) * cols] - center; }
+					if row + 1 < rows { laplacian += previous[i + cols] - center; }
+					else if wrap { laplacian += previous[i - (rows - 1) * cols] - center; }
+					channel[i] = (center + k * laplacian) * decay;
+				}
+			}
+		}
+	}
+
+	/// Center and concentration of the cells of a channel above the given threshold
+	pub fn cells_above(&self, channel: usize, threshold: f32) -> Vec<(Position, f32)> {
+		let mut cells = Vec::new();
+		if channel >= self.channels.len() {
+			return cells;
+		}
+		let half = self.cell_size * 0.5;
+		for row in 0..self.rows {
+			for col in 0..self.cols {
+				let value = self.value(channel, col, row);
+				if value > threshold {
+					let center = self.origin + Position::new(col as f32 * self.cell_size + half, row as f32 * self.cell_size + half);
+					cells.push((center, value));
+				}
+			}
+		}
+		cells
+	}
+}
@@ -0,0 +1,382 @@
+use std::collections::HashSet;
+use std::error;
+use std::fmt;
+use std::io;
+use std::str;
+use rand;
+use serialize::base64::{self, ToBase64, FromBase64};
+use backend::obj::Id;
+use backend::world::gen::Dna;
+use backend::world::species::{self, SpeciesId};
+use core::clock::SecondsValue;
+
+/// Gene pool files start with a magic line carrying the format version,
+/// followed by a header naming the columns. Only the `dna` column is mandatory.
+/// Files without the magic line are the legacy format, a bare base64 genome per line.
+pub const FORMAT_VERSION: u32 = 1;
+const MAGIC: &'static str = "#rust-oids gene pool v";
+const COLUMNS: &'static [&'static str] = &["dna", "name", "generation", "lineage", "species", "energy", "age", "source"];
+
+#[derive(Clone, Debug)]
+pub struct Record {
+	pub dna: Dna,
+	pub name: Option<String>,
+	pub generation: Option<usize>,
+	pub lineage: Option<Id>,
+	pub species: Option<SpeciesId>,
+	pub energy: Option<f32>,
+	pub age: Option<SecondsValue>,
+	pub source: Option<String>,
+}
+
+impl Record {
+	pub fn new(dna: Dna) -> Self {
+		Record {
+			dna,
+			name: None,
+			generation: None,
+			lineage: None,
+			species: None,
+			energy: None,
+			age: None,
+			source: None,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+	/// 1-based line number in the source file
+	pub line: usize,
+	pub message: String,
+}
+
+impl ParseError {
+	fn new<S: Into<String>>(line: usize, message: S) -> Self {
+		ParseError { line, message: message.into() }
+	}
+}
+
+impl fmt::Display for ParseError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+impl error::Error for ParseError {
+	fn description(&self) -> &str {
+		&self.message
+	}
+}
+
+impl From<ParseError> for io::Error {
+	fn from(e: ParseError) -> Self {
+		io::Error::new(io::ErrorKind::InvalidData, e)
+	}
+}
+
+/// Rows that could be read, and the line-numbered reasons why the others could not
+pub struct Parsed {
+	pub version: u32,
+	pub records: Vec<Record>,
+	pub errors: Vec<ParseError>,
+}
+
+fn split_row(line: &str) -> Result<Vec<String>, String> {
+	let mut fields = Vec::new();
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut chars = line.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'"' if quoted => if chars.peek() == Some(&'"') {
+				chars.next();
+				field.push('"');
+			} else {
+				quoted = false;
+			},
+			'"' if field.is_empty() => quoted = true,
+			',' if !quoted => fields.push(::std::mem::replace(&mut field, String::new())),
+			c => field.push(c),
+		}
+	}
+	if quoted {
+		return Err("unterminated quoted field".to_owned());
+	}
+	fields.push(field);
+	Ok(fields)
+}
+
+fn quote(field: &str) -> String {
+	if field.contains(',') || field.contains('"') {
+		format!("\"{}\"", field.replace('"', "\"\""))
+	} else {
+		field.to_owned()
+	}
+}
+
+fn parse_field<T: str::FromStr>(name: &str, value: &str) -> Result<Option<T>, String> {
+	if value.is_empty() {
+		Ok(None)
+	} else {
+		value.parse::<T>()
+			.map(Some)
+			.map_err(|_| format!("invalid {} '{}'", name, value))
+	}
+}
+
+fn parse_dna(value: &str) -> Result<Dna, String> {
+	match value.from_base64() {
+		Ok(ref dna) if dna.is_empty() => Err("empty genome".to_owned()),
+		Ok(dna) => Ok(dna.into_boxed_slice()),
+		Err(e) => Err(format!("invalid base64 genome: {}", e)),
+	}
+}
+
+fn parse_record(columns: &[String], fields: &[String]) -> Result<Record, String> {
+	let mut record = None;
+	let mut optional = Vec::new();
+	for (column, value) in columns.iter().zip(fields.iter()) {
+		let value = value.trim();
+		if column == "dna" {
+			record = Some(Record::new(parse_dna(value)?));
+		} else {
+			optional.push((column, value));
+		}
+	}
+	let mut record = record.ok_or_else(|| "missing dna".to_owned())?;
+	for (column, value) in optional {
+		match column.as_str() {
+			"name" => record.name = if value.is_empty() { None } else { Some(value.to_owned()) },
+			"generation" => record.generation = parse_field(column, value)?,
+			"lineage" => record.lineage = parse_field(column, value)?,
+			"species" => record.species = parse_field(column, value)?,
+			"energy" => record.energy = parse_field(column, value)?,
+			"age" => record.age = parse_field(column, value)?,
+			"source" => record.source = if value.is_empty() { None } else { Some(value.to_owned()) },
+			// columns added by future versions are ignored
+			_ => {}
+		}
+	}
+	Ok(record)
+}
+
+/// Reads both the versioned and the legacy format.
+/// Fails only if the file as a whole can't be understood, bad rows are reported in `Parsed::errors`.
+pub fn parse(data: &[u8]) -> Result<Parsed, ParseError> {
+	let text = str::from_utf8(data).map_err(|e| ParseError::new(1, format!("not valid UTF-8: {}", e)))?;
+	let mut lines = text.lines()
+		.enumerate()
+		.map(|(i, line)| (i + 1, line.trim()))
+		.filter(|&(_, line)| !line.is_empty())
+		.peekable();
+
+	let first = lines.peek().cloned();
+	let (version, columns) = match first {
+		Some((n, line)) if line.starts_with(MAGIC) => {
+			lines.next();
+			let version = line[MAGIC.len()..].trim().parse::<u32>()
+				.map_err(|_| ParseError::new(n, format!("invalid version in '{}'", line)))?;
+			if version > FORMAT_VERSION {
+				return Err(ParseError::new(n, format!("unsupported version {}, expected at most {}", version, FORMAT_VERSION)));
+			}
+			let (n, header) = lines.next().ok_or_else(|| ParseError::new(n, "missing header"))?;
+			let columns = split_row(header)
+				.map_err(|e| ParseError::new(n, e))?
+				.into_iter()
+				.map(|c| c.trim().to_lowercase())
+				.collect::<Vec<_>>();
+			if !columns.iter().any(|c| c == "dna") {
+				return Err(ParseError::new(n, "header has no dna column"));
+			}
+			(version, columns)
+		}
+		_ => (0, vec!["dna".to_owned()]),
+	};
+
+	let mut records = Vec::new();
+	let mut errors = Vec::new();
+	for (n, line) in lines {
+		if line.starts_with('#') {
+			continue;
+		}
+		match split_row(line).and_then(|fields| parse_record(&columns, &fields)) {
+			Ok(record) => records.push(record),
+			Err(message) => errors.push(ParseError::new(n, message)),
+		}
+	}
+	Ok(Parsed { version, records, errors })
+}
+
+/// Writes the current version of the format, with all the known columns
+pub fn write<W: io::Write>(out: &mut W, records: &[Record]) -> io::Result<()> {
+	fn opt<T: ToString>(value: &Option<T>) -> String {
+		value.as_ref().map(|v| v.to_string()).unwrap_or_default()
+	}
+	writeln!(out, "{}{}", MAGIC, FORMAT_VERSION)?;
+	writeln!(out, "{}", COLUMNS.join(","))?;
+	for record in records {
+		writeln!(out, "{},{},{},{},{},{},{},{}",
+				 record.dna.to_base64(base64::STANDARD),
+				 quote(&opt(&record.name)),
+				 opt(&record.generation),
+				 opt(&record.lineage),
+				 opt(&record.species),
+				 opt(&record.energy),
+				 opt(&record.age),
+				 quote(&opt(&record.source)))?;
+	}
+	Ok(())
+}
+
+/// Drops every record whose genome is within `distance` of one kept before it, the first occurrence wins.
+/// A distance of zero only drops exact duplicates.
+pub fn dedupe(records: Vec<Record>, distance: f32) -> Vec<Record> {
+	let mut kept: Vec<Record> = Vec::with_capacity(records.len());
+	for record in records {
+		let duplicate = kept.iter().any(|k| if distance <= 0. {
+			k.dna == record.dna
+		} else {
+			species::hamming_distance(&k.dna, &record.dna) <= distance
+		});
+		if !duplicate {
+			kept.push(record);
+		}
+	}
+	kept
+}
+
+/// `n` records picked at random, in their original order
+pub fn sample_random<R: rand::Rng>(records: Vec<Record>, n: usize, rng: &mut R) -> Vec<Record> {
+	let mut indices: Vec<usize> = (0..records.len()).collect();
+	let n = n.min(indices.len());
+	for i in 0..n {
+		let remaining = indices.len() - i;
+		let j = i + ((rng.next_f32() * remaining as f32) as usize).min(remaining - 1);
+		indices.swap(i, j);
+	}
+	let mut picked = indices[..n].to_vec();
+	picked.sort();
+	let mut records: Vec<Option<Record>> = records.into_iter().map(Some).collect();
+	picked.into_iter().filter_map(|i| records[i].take()).collect()
+}
+
+/// `n` records spread as far apart as possible: starting from the first, each pick is the genome
+/// farthest from all the ones picked so far
+pub fn sample_diverse(records: Vec<Record>, n: usize) -> Vec<Record> {
+	let n = n.min(records.len());
+	let mut picked = Vec::with_capacity(n);
+	// distance of every record from the nearest picked one
+	let mut nearest = vec![::std::f32::INFINITY; records.len()];
+	let mut next = 0;
+	while picked.len() < n {
+		picked.push(next);
+		nearest[next] = -1.;
+		let mut best = (0, -1.);
+		for (i, record) in records.iter().enumerate() {
+			if nearest[i] < 0. {
+				continue;
+			}
+			nearest[i] = nearest[i].min(species::hamming_distance(&records[next].dna, &record.dna));
+			if nearest[i] > best.1 {
+				best = (i, nearest[i]);
+			}
+		}
+		next = best.0;
+	}
+	picked.sort();
+	let mut records: Vec<Option<Record>> = records.into_iter().map(Some).collect();
+	picked.into_iter().filter_map(|i| records[i].take()).collect()
+}
+
+/// Records of `a` whose genome is not in `b`
+pub fn difference(a: &[Record], b: &[Record]) -> Vec<Record> {
+	a.iter().filter(|r| !b.iter().any(|o| o.dna == r.dna)).cloned().collect()
+}
+
+/// How many distinct genomes are only in `a`, only in `b`, and in both
+pub fn overlap(a: &[Record], b: &[Record]) -> (usize, usize, usize) {
+	let a: HashSet<&Dna> = a.iter().map(|r| &r.dna).collect();
+	let b: HashSet<&Dna> = b.iter().map(|r| &r.dna).collect();
+	let both = a.intersection(&b).count();
+	(a.len() - both, b.len() - both, both)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn dna(bytes: &[u8]) -> Dna {
+		bytes.to_vec().into_boxed_slice()
+	}
+
+	#[test]
+	fn quoted_fields_survive_a_round_trip() {
+		let records = vec![
+			Record {
+				name: Some("comma, \"quote\" and more".to_owned()),
+				generation: Some(3),
+				lineage: Some(42),
+				species: Some(7),
+				energy: Some(12.5),
+				age: Some(30.25),
+				source: Some("pool,\"a\".csv".to_owned()),
+				..Record::new(dna(&[1, 2, 3, 4]))
+			},
+			Record::new(dna(&[5, 6, 7])),
+		];
+		let mut out = Vec::new();
+		write(&mut out, &records).unwrap();
+		let parsed = parse(&out).unwrap();
+		assert!(parsed.errors.is_empty());
+		assert_eq!(FORMAT_VERSION, parsed.version);
+		assert_eq!(records.len(), parsed.records.len());
+		for (a, b) in records.iter().zip(parsed.records.iter()) {
+			assert_eq!(a.dna, b.dna);
+			assert_eq!(a.name, b.name);
+			assert_eq!(a.generation, b.generation);
+			assert_eq!(a.lineage, b.lineage);
+			assert_eq!(a.species, b.species);
+			assert_eq!(a.energy, b.energy);
+			assert_eq!(a.age, b.age);
+			assert_eq!(a.source, b.source);
+		}
+	}
+
+	#[test]
+	fn optional_columns_may_be_omitted() {
+		let data = format!("{}1\nname,dna\n\"x, y\",{}\n", MAGIC, dna(&[9, 8, 7]).to_base64(base64::STANDARD));
+		let parsed = parse(data.as_bytes()).unwrap();
+		assert!(parsed.errors.is_empty());
+		assert_eq!(1, parsed.records.len());
+		let record = &parsed.records[0];
+		assert_eq!(dna(&[9, 8, 7]), record.dna);
+		assert_eq!(Some("x, y".to_owned()), record.name);
+		assert_eq!(None, record.generation);
+		assert_eq!(None, record.source);
+	}
+
+	#[test]
+	fn errors_report_the_line_of_the_bad_row() {
+		let good = dna(&[1, 2, 3]).to_base64(base64::STANDARD);
+		let data = format!("{}1\ndna,generation\n{},1\n\n{},many\n# a comment\n\"{},2\n!!!,3\n",
+						   MAGIC, good, good, good);
+		let parsed = parse(data.as_bytes()).unwrap();
+		assert_eq!(1, parsed.records.len());
+		let lines: Vec<_> = parsed.errors.iter().map(|e| e.line).collect();
+		assert_eq!(vec![5, 7, 8], lines);
+		match parse(format!("{}9\ndna\n", MAGIC).as_bytes()) {
+			Err(e) => assert_eq!(1, e.line),
+			Ok(_) => panic!("a newer version should not parse"),
+		}
+	}
+
+	#[test]
+	fn overlap_counts_each_genome_once() {
+		let a: Vec<_> = [&[1u8][..], &[1], &[2], &[3]].iter().map(|d| Record::new(dna(d))).collect();
+		let b: Vec<_> = [&[2u8][..], &[2], &[4]].iter().map(|d| Record::new(dna(d))).collect();
+		assert_eq!((2, 1, 1), overlap(&a, &b));
+		assert_eq!(3, difference(&a, &b).len());
+	}
+}
@@ -86,6 +86,11 @@ impl State {
 		self.set_charge(charge);
 	}
 
+	pub fn restore_age(&mut self, age_seconds: Seconds, age_frames: usize) {
+		self.age_seconds = age_seconds;
+		self.age_frames = age_frames;
+	}
+
 	pub fn update(&mut self, dt: Seconds) {
 		self.age_seconds += dt;
 		self.age_frames += 1;
@@ -0,0 +1,203 @@
+use std::fmt;
+use app::constants::{MAX_VISION_RAYS, PHEROMONE_CHANNELS};
+use backend::world::agent::AgentType;
+
+/// The senses of a creature, each channel feeds one input of its brain.
+/// Values are expected to be roughly within -1..1, channels are normalized by the AI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+	/// angle between the head and the torso, for some proprioception
+	NeckAngle,
+	/// position of the target along the direction the head is pointing
+	TargetAhead,
+	/// position of the target across the direction the head is pointing
+	TargetSide,
+	/// energy over the maximum which can be stored
+	EnergyRatio,
+	Speed,
+	/// whether any segment is touching something
+	Touch,
+	/// distance to the nearest beacon
+	BeaconDistance,
+	/// position of the nearest creature of the same kind, along the head direction
+	NeighbourAhead,
+	/// position of the nearest creature of the same kind, across the head direction
+	NeighbourSide,
+	/// time since the last meal
+	Fasting,
+	/// gradient of the n-th pheromone channel, along the head direction
+	PheromoneAhead(usize),
+	/// gradient of the n-th pheromone channel, across the head direction
+	PheromoneSide(usize),
+	/// how close is whatever the n-th vision ray hit, zero if it hit nothing
+	RayProximity(usize),
+	/// what the n-th vision ray hit, see `Seen::code`
+	RayKind(usize),
+}
+
+const N_BODY_CHANNELS: usize = 10;
+
+const N_SCENT_CHANNELS: usize = 2 * PHEROMONE_CHANNELS;
+
+pub const N_CHANNELS: usize = N_BODY_CHANNELS + N_SCENT_CHANNELS + 2 * MAX_VISION_RAYS;
+
+const BODY_CHANNELS: [Channel; N_BODY_CHANNELS] = [
+	Channel::NeckAngle,
+	Channel::TargetAhead,
+	Channel::TargetSide,
+	Channel::EnergyRatio,
+	Channel::Speed,
+	Channel::Touch,
+	Channel::BeaconDistance,
+	Channel::NeighbourAhead,
+	Channel::NeighbourSide,
+	Channel::Fasting,
+];
+
+impl Channel {
+	/// All the channels, in brain input order
+	pub fn all() -> Vec<Channel> {
+		let mut channels = BODY_CHANNELS.to_vec();
+		for i in 0..PHEROMONE_CHANNELS {
+			channels.push(Channel::PheromoneAhead(i));
+			channels.push(Channel::PheromoneSide(i));
+		}
+		for i in 0..MAX_VISION_RAYS {
+			channels.push(Channel::RayProximity(i));
+			channels.push(Channel::RayKind(i));
+		}
+		channels
+	}
+
+	/// Input of the brain this channel is wired to
+	pub fn index(self) -> usize {
+		match self {
+			Channel::NeckAngle => 0,
+			Channel::TargetAhead => 1,
+			Channel::TargetSide => 2,
+			Channel::EnergyRatio => 3,
+			Channel::Speed => 4,
+			Channel::Touch => 5,
+			Channel::BeaconDistance => 6,
+			Channel::NeighbourAhead => 7,
+			Channel::NeighbourSide => 8,
+			Channel::Fasting => 9,
+			Channel::PheromoneAhead(i) => N_BODY_CHANNELS + 2 * i,
+			Channel::PheromoneSide(i) => N_BODY_CHANNELS + 2 * i + 1,
+			Channel::RayProximity(i) => N_BODY_CHANNELS + N_SCENT_CHANNELS + 2 * i,
+			Channel::RayKind(i) => N_BODY_CHANNELS + N_SCENT_CHANNELS + 2 * i + 1,
+		}
+	}
+
+	pub fn name(self) -> String {
+		match self {
+			Channel::NeckAngle => "neck_angle".to_owned(),
+			Channel::TargetAhead => "target_ahead".to_owned(),
+			Channel::TargetSide => "target_side".to_owned(),
+			Channel::EnergyRatio => "energy_ratio".to_owned(),
+			Channel::Speed => "speed".to_owned(),
+			Channel::Touch => "touch".to_owned(),
+			Channel::BeaconDistance => "beacon_distance".to_owned(),
+			Channel::NeighbourAhead => "neighbour_ahead".to_owned(),
+			Channel::NeighbourSide => "neighbour_side".to_owned(),
+			Channel::Fasting => "fasting".to_owned(),
+			Channel::PheromoneAhead(i) => format!("pheromone{}_ahead", i),
+			Channel::PheromoneSide(i) => format!("pheromone{}_side", i),
+			Channel::RayProximity(i) => format!("ray{}_proximity", i),
+			Channel::RayKind(i) => format!("ray{}_kind", i),
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Channel> {
+		Self::all().into_iter().find(|c| c.name() == name)
+	}
+}
+
+impl fmt::Display for Channel {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+/// Vision genes: how many rays a sensor casts, and how wide they are spread
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vision {
+	pub rays: usize,
+	/// field of view in radians, centered on the direction of the sensor
+	pub fov: f32,
+}
+
+impl Vision {
+	/// Angle of the i-th ray relative to the direction of the sensor
+	pub fn ray_angle(&self, i: usize) -> f32 {
+		if self.rays < 2 {
+			0.
+		} else {
+			self.fov * (i as f32 / (self.rays - 1) as f32 - 0.5)
+		}
+	}
+}
+
+/// What a vision ray hit first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seen {
+	Nothing,
+	Wall,
+	Agent(AgentType),
+}
+
+impl Seen {
+	/// Brain input for each kind of thing, spread over -1..1 so that thresholds can tell them apart.
+	/// Food and mates are positive, threats and obstacles negative.
+	pub fn code(self) -> f32 {
+		match self {
+			Seen::Nothing => 0.,
+			Seen::Agent(AgentType::Resource) => 1.,
+			Seen::Agent(AgentType::Spore) => 0.75,
+			Seen::Agent(AgentType::Minion) => 0.5,
+			Seen::Agent(AgentType::Prop) => -0.25,
+			Seen::Wall => -0.5,
+			Seen::Agent(AgentType::Enemy) => -0.75,
+			Seen::Agent(_) => -1.,
+		}
+	}
+}
+
+/// The first hit of a vision ray
+#[derive(Clone, Copy, Debug)]
+pub struct Sight {
+	/// fraction of the range of the ray, 1 if nothing was hit
+	pub distance: f32,
+	pub seen: Seen,
+}
+
+impl Sight {
+	pub fn nothing() -> Self {
+		Sight { distance: 1., seen: Seen::Nothing }
+	}
+
+	pub fn proximity(&self) -> f32 {
+		1. - self.distance
+	}
+}
+
+/// A reading of all the channels, in brain input order
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sensors {
+	values: [f32; N_CHANNELS],
+}
+
+impl Sensors {
+	pub fn set(&mut self, channel: Channel, value: f32) -> &mut Self {
+		self.values[channel.index()] = value;
+		self
+	}
+
+	pub fn get(&self, channel: Channel) -> f32 {
+		self.values[channel.index()]
+	}
+
+	pub fn values(&self) -> &[f32; N_CHANNELS] {
+		&self.values
+	}
+}
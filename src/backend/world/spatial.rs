@@ -0,0 +1,350 @@
+use std::collections::HashSet;
+use cgmath::InnerSpace;
+use backend::obj::Id;
+use backend::world::agent::AgentType;
+use backend::world::agent::TypedAgent;
+use backend::world::topology::Topology;
+use core::geometry::*;
+
+#[derive(Clone, Copy, Debug)]
+struct Entry {
+	id: Id,
+	position: Position,
+	radius: f32,
+}
+
+/// Uniform grid over the extent of the world, holding the position and bounding radius of every agent.
+/// Agents outside the extent are kept in the border cells, so queries never miss them.
+/// In a wrapping world the grid is a torus: queries reach across the borders,
+/// and report the positions of the agents as seen from the query, the nearest way round.
+pub struct Grid {
+	extent: Rect,
+	topology: Topology,
+	cell_size: f32,
+	cols: usize,
+	rows: usize,
+	cells: Vec<Vec<Entry>>,
+	/// the largest radius of any indexed agent, to widen the queries by
+	max_radius: f32,
+}
+
+impl Grid {
+	pub fn new(extent: &Rect, cell_size: f32) -> Self {
+		let cols = (((extent.max.x - extent.min.x) / cell_size).ceil() as usize).max(1);
+		let rows = (((extent.max.y - extent.min.y) / cell_size).ceil() as usize).max(1);
+		Grid {
+			extent: *extent,
+			topology: Topology::default(),
+			cell_size,
+			cols,
+			rows,
+			cells: vec![Vec::new(); cols * rows],
+			max_radius: 0.,
+		}
+	}
+
+	/// Empties the grid, and resizes it if the extent has changed
+	pub fn reset(&mut self, extent: &Rect) {
+		let cols = (((extent.max.x - extent.min.x) / self.cell_size).ceil() as usize).max(1);
+		let rows = (((extent.max.y - extent.min.y) / self.cell_size).ceil() as usize).max(1);
+		if cols != self.cols || rows != self.rows || extent.min != self.extent.min {
+			let topology = self.topology;
+			*self = Grid::new(extent, self.cell_size);
+			self.topology = topology;
+		} else {
+			self.extent = *extent;
+			for cell in self.cells.iter_mut() {
+				cell.clear();
+			}
+			self.max_radius = 0.;
+		}
+	}
+
+	/// The topology decides whether queries reach across the borders of the extent
+	pub fn set_topology(&mut self, topology: Topology) {
+		self.topology = topology;
+	}
+
+	/// Index of the cell at the given column and row, wrapped around the torus
+	fn wrapped_cell_index(&self, col: isize, row: isize) -> usize {
+		let (cols, rows) = (self.cols as isize, self.rows as isize);
+		let (col, row) = (((col % cols) + cols) % cols, ((row % rows) + rows) % rows);
+		row as usize * self.cols + col as usize
+	}
+
+	/// Column and row of a position, not clamped to the grid
+	fn raw_cell_of(&self, p: &Position) -> (isize, isize) {
+		(((p.x - self.extent.min.x) / self.cell_size).floor() as isize,
+		 ((p.y - self.extent.min.y) / self.cell_size).floor() as isize)
+	}
+
+	pub fn insert(&mut self, id: Id, position: Position, radius: f32) {
+		let (col, row) = self.cell_of(&position);
+		self.cells[row * self.cols + col].push(Entry { id, position, radius });
+		self.max_radius = self.max_radius.max(radius);
+	}
+
+	fn cell_of(&self, p: &Position) -> (usize, usize) {
+		let col = ((p.x - self.extent.min.x) / self.cell_size).floor().max(0.) as usize;
+		let row = ((p.y - self.extent.min.y) / self.cell_size).floor().max(0.) as usize;
+		(col.min(self.cols - 1), row.min(self.rows - 1))
+	}
+
+	/// Visits the entries of all the cells overlapping the given box, each cell once
+	fn visit<F>(&self, min: Position, max: Position, mut f: F) where F: FnMut(&Entry) {
+		if !self.topology.wraps() {
+			let (c0, r0) = self.cell_of(&min);
+			let (c1, r1) = self.cell_of(&max);
+			for row in r0..(r1 + 1) {
+				for col in c0..(c1 + 1) {
+					for entry in &self.cells[row * self.cols + col] {
+						f(entry)
+					}
+				}
+			}
+			return;
+		}
+		let (c0, r0) = self.raw_cell_of(&min);
+		let (c1, r1) = self.raw_cell_of(&max);
+		// a box larger than the world would visit some cells twice
+		let c1 = c1.min(c0 + self.cols as isize - 1);
+		let r1 = r1.min(r0 + self.rows as isize - 1);
+		for row in r0..(r1 + 1) {
+			for col in c0..(c1 + 1) {
+				for entry in &self.cells[self.wrapped_cell_index(col, row)] {
+					f(entry)
+				}
+			}
+		}
+	}
+
+	/// Agents of the given type whose bounding circle reaches within `radius` of `center`, in no particular order
+	pub fn within(&self, agent_type: AgentType, center: Position, radius: f32) -> Vec<(Id, Position)> {
+		let r = Position::new(radius + self.max_radius, radius + self.max_radius);
+		let mut found = Vec::new();
+		self.visit(center - r, center + r, |entry| {
+			let d = self.topology.delta(&self.extent, center, entry.position);
+			let reach = radius + entry.radius;
+			if entry.id.type_of() == agent_type && d.magnitude2() <= reach * reach {
+				found.push((entry.id, center + d))
+			}
+		});
+		found
+	}
+
+	/// Up to `k` agents of the given type, nearest first.
+	/// Searches rings of cells of growing size, until the ring is farther than the k-th agent found.
+	pub fn nearest(&self, agent_type: AgentType, center: Position, k: usize) -> Vec<(Id, Position)> {
+		let mut found: Vec<(f32, Id, Position)> = Vec::new();
+		if k == 0 {
+			return Vec::new();
+		}
+		let max_rings = self.cols.max(self.rows) as isize;
+		let wraps = self.topology.wraps();
+		let (c, r) = if wraps {
+			self.raw_cell_of(&center)
+		} else {
+			let (c, r) = self.cell_of(&center);
+			(c as isize, r as isize)
+		};
+		// on a torus the rings overlap themselves once they are as large as the world
+		let mut visited = HashSet::new();
+		for ring in 0..(max_rings + 1) {
+			for row in (r - ring)..(r + ring + 1) {
+				for col in (c - ring)..(c + ring + 1) {
+					// only the border of the ring, the inside was visited already
+					if (row - r).abs() != ring && (col - c).abs() != ring {
+						continue;
+					}
+					let index = if wraps {
+						let index = self.wrapped_cell_index(col, row);
+						if !visited.insert(index) {
+							continue;
+						}
+						index
+					} else if col < 0 || row < 0 || col >= self.cols as isize || row >= self.rows as isize {
+						// past the edge of the world, which the ring overhangs
+						continue;
+					} else {
+						row as usize * self.cols + col as usize
+					};
+					for entry in &self.cells[index] {
+						if entry.id.type_of() == agent_type {
+							let d = self.topology.delta(&self.extent, center, entry.position);
+							found.push((d.magnitude2(), entry.id, center + d));
+						}
+					}
+				}
+			}
+			if found.len() >= k {
+				found.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+				// anything not found yet is at least this far away
+				let reach = ring as f32 * self.cell_size;
+				if found[k - 1].0 <= reach * reach {
+					break;
+				}
+			}
+		}
+		found.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+		found.into_iter().take(k).map(|(_, id, p)| (id, p)).collect()
+	}
+
+	/// First agent, of any type if none is given, whose bounding circle crosses the segment from `p0` to `p1`.
+	/// Returns its id, and how far along the segment it was hit, from 0 to 1.
+	pub fn cast(&self, p0: Position, p1: Position, agent_type: Option<AgentType>, exclude: Option<Id>) -> Option<(Id, f32)> {
+		let d = p1 - p0;
+		let length2 = d.magnitude2();
+		let pad = Position::new(self.max_radius, self.max_radius);
+		let min = Position::new(p0.x.min(p1.x), p0.y.min(p1.y)) - pad;
+		let max = Position::new(p0.x.max(p1.x), p0.y.max(p1.y)) + pad;
+		let mut nearest: Option<(Id, f32)> = None;
+		let mid = p0 + d * 0.5;
+		self.visit(min, max, |entry| {
+			if Some(entry.id) == exclude || agent_type.map(|t| t != entry.id.type_of()).unwrap_or(false) {
+				return;
+			}
+			// the copy of the agent nearest to the segment, if the grid wraps
+			let position = mid + self.topology.delta(&self.extent, mid, entry.position);
+			// solve |p0 + t d - c| = r for the smallest t
+			let m = p0 - position;
+			let b = m.dot(d);
+			let c = m.magnitude2() - entry.radius * entry.radius;
+			let t = if c <= 0. {
+				0.
+			} else if length2 == 0. {
+				return;
+			} else {
+				let discriminant = b * b - length2 * c;
+				if discriminant < 0. {
+					return;
+				}
+				(-b - discriminant.sqrt()) / length2
+			};
+			if t >= 0. && t <= 1. && nearest.map(|(_, n)| t < n).unwrap_or(true) {
+				nearest = Some((entry.id, t));
+			}
+		});
+		nearest
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn minion(n: usize) -> Id {
+		n << 8 | AgentType::Minion as usize
+	}
+
+	fn resource(n: usize) -> Id {
+		n << 8 | AgentType::Resource as usize
+	}
+
+	fn grid(topology: Topology) -> Grid {
+		let mut grid = Grid::new(&Rect::new(-10., -10., 10., 10.), 2.);
+		grid.set_topology(topology);
+		grid
+	}
+
+	#[test]
+	fn nearest_in_the_interior() {
+		let mut grid = grid(Topology::Walled);
+		grid.insert(minion(1), Position::new(0.5, 0.5), 0.5);
+		grid.insert(minion(2), Position::new(1.5, 0.5), 0.5);
+		grid.insert(minion(3), Position::new(-7., 6.), 0.5);
+		grid.insert(resource(1), Position::new(0.6, 0.5), 0.5);
+		let found = grid.nearest(AgentType::Minion, Position::new(0.5, 0.5), 2);
+		assert_eq!(vec![minion(1), minion(2)], found.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+		let found = grid.nearest(AgentType::Minion, Position::new(0.5, 0.5), 5);
+		assert_eq!(vec![minion(1), minion(2), minion(3)], found.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+		assert_eq!(vec![resource(1)], grid.nearest(AgentType::Resource, Position::new(-9., -9.), 3).iter().map(|&(id, _)| id).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn nearest_at_the_border_visits_each_cell_once() {
+		let mut grid = grid(Topology::Walled);
+		// a corner cell, the edges of every ring around it are clamped
+		grid.insert(minion(1), Position::new(9.5, 9.5), 0.5);
+		grid.insert(minion(2), Position::new(7.5, 9.5), 0.5);
+		// agents outside the extent are kept in the border cells
+		grid.insert(minion(3), Position::new(12., -12.), 0.5);
+		let found = grid.nearest(AgentType::Minion, Position::new(9.5, 9.5), 2);
+		assert_eq!(vec![minion(1), minion(2)], found.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+		let found = grid.nearest(AgentType::Minion, Position::new(9.5, 9.5), 10);
+		assert_eq!(vec![minion(1), minion(2), minion(3)], found.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn nearest_reaches_across_the_border_of_a_torus() {
+		let mut grid = grid(Topology::Torus);
+		grid.insert(minion(1), Position::new(9.5, 0.), 0.5);
+		grid.insert(minion(2), Position::new(-9.5, 0.), 0.5);
+		grid.insert(minion(3), Position::new(5., 0.), 0.5);
+		let found = grid.nearest(AgentType::Minion, Position::new(9.5, 0.), 3);
+		assert_eq!(vec![minion(1), minion(2), minion(3)], found.iter().map(|&(id, _)| id).collect::<Vec<_>>());
+		// seen the nearest way round, from the other side of the border
+		assert_eq!(Position::new(10.5, 0.), found[1].1);
+	}
+
+	fn ids(found: &[(Id, Position)]) -> Vec<Id> {
+		let mut ids: Vec<_> = found.iter().map(|&(id, _)| id).collect();
+		ids.sort();
+		ids
+	}
+
+	#[test]
+	fn within_in_the_interior() {
+		let mut grid = grid(Topology::Walled);
+		grid.insert(minion(1), Position::new(0., 0.), 0.5);
+		grid.insert(minion(2), Position::new(3., 0.), 0.5);
+		// farther than the radius, but its body reaches into it
+		grid.insert(minion(3), Position::new(0., 5.), 2.5);
+		grid.insert(minion(4), Position::new(-6., -6.), 0.5);
+		grid.insert(resource(1), Position::new(0.5, 0.), 0.5);
+		assert_eq!(vec![minion(1), minion(2), minion(3)], ids(&grid.within(AgentType::Minion, Position::new(0., 0.), 3.)));
+		assert_eq!(vec![minion(1)], ids(&grid.within(AgentType::Minion, Position::new(0., 0.), 1.)));
+		assert_eq!(vec![resource(1)], ids(&grid.within(AgentType::Resource, Position::new(0., 0.), 1.)));
+	}
+
+	#[test]
+	fn within_at_the_border() {
+		let mut grid = grid(Topology::Walled);
+		grid.insert(minion(1), Position::new(9.5, 9.5), 0.5);
+		// outside the extent, kept in the border cell
+		grid.insert(minion(2), Position::new(11.5, 9.5), 0.5);
+		grid.insert(minion(3), Position::new(-9.5, 9.5), 0.5);
+		assert_eq!(vec![minion(1), minion(2)], ids(&grid.within(AgentType::Minion, Position::new(10., 9.5), 1.)));
+
+		grid.set_topology(Topology::Torus);
+		let found = grid.within(AgentType::Minion, Position::new(9.5, 9.5), 1.);
+		assert_eq!(vec![minion(1), minion(3)], ids(&found));
+		// seen the nearest way round, from the other side of the border
+		assert!(found.iter().any(|&(id, p)| id == minion(3) && p == Position::new(10.5, 9.5)));
+	}
+
+	#[test]
+	fn cast_in_the_interior() {
+		let mut grid = grid(Topology::Walled);
+		grid.insert(minion(1), Position::new(0., 0.), 0.5);
+		grid.insert(minion(2), Position::new(4., 0.), 1.);
+		grid.insert(resource(1), Position::new(2., 0.), 0.5);
+		let p0 = Position::new(0., 0.);
+		let p1 = Position::new(8., 0.);
+		assert_eq!(Some((minion(1), 0.)), grid.cast(p0, p1, None, None));
+		assert_eq!(Some((resource(1), 1.5 / 8.)), grid.cast(p0, p1, None, Some(minion(1))));
+		assert_eq!(Some((minion(2), 3. / 8.)), grid.cast(p0, p1, Some(AgentType::Minion), Some(minion(1))));
+		assert_eq!(None, grid.cast(p0, Position::new(0., 8.), None, Some(minion(1))));
+	}
+
+	#[test]
+	fn cast_at_the_border() {
+		let mut grid = grid(Topology::Walled);
+		grid.insert(minion(1), Position::new(-9.5, 0.), 0.5);
+		let p0 = Position::new(8., 0.);
+		let p1 = Position::new(12., 0.);
+		assert_eq!(None, grid.cast(p0, p1, None, None));
+		grid.set_topology(Topology::Torus);
+		// the ray leaves on the right, and the agent comes back in from the left
+		assert_eq!(Some((minion(1), 0.5)), grid.cast(p0, p1, None, None));
+	}
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::mem;
+use rand;
+use app::constants::*;
+use backend::obj::Id;
+use backend::world::agent;
+use backend::world::agent::Agent;
+use backend::world::gen::Dna;
+use backend::world::phen;
+use core::clock::SecondsValue;
+use core::color::{Hsl, Rgba, ToRgb};
+
+pub type SpeciesId = usize;
+
+/// How the distance between two minions is measured
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+	/// fraction of differing bits in the DNA
+	Hamming,
+	/// root mean square distance of the normalized personality, brain wiring and body size
+	Phenotype,
+}
+
+/// How minions are told apart into species, from the `species` section of the world configuration
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Settings {
+	pub metric: Metric,
+	/// fraction of differing bits for `Metric::Hamming`, normalized RMS distance for `Metric::Phenotype`
+	pub threshold: f32,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings {
+			metric: SPECIES_METRIC,
+			threshold: SPECIES_DISTANCE_THRESHOLD,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Species {
+	pub id: SpeciesId,
+	/// the DNA of the minion that founded the species, all the members are measured against it
+	pub representative: Dna,
+	pub founded: SecondsValue,
+	pub population: usize,
+	/// only measured for `Metric::Phenotype`
+	features: Vec<f32>,
+}
+
+impl Species {
+	pub fn new(id: SpeciesId, representative: Dna, founded: SecondsValue, metric: Metric) -> Self {
+		let features = match metric {
+			Metric::Phenotype => features_of(&phen::develop_minion(&representative, agent::InitialState::default())),
+			Metric::Hamming => Vec::new(),
+		};
+		Species { id, representative, founded, population: 0, features }
+	}
+
+	/// A color which is easy to tell apart from the other species' ones
+	pub fn color(&self) -> Rgba {
+		const GOLDEN_RATIO_CONJUGATE: f32 = 0.618_034;
+		let hue = (self.id as f32 * GOLDEN_RATIO_CONJUGATE).fract();
+		Hsl::new(hue, 0.8, 0.6).to_rgba()
+	}
+}
+
+fn features_of(agent: &Agent) -> Vec<f32> {
+	let brain = agent.brain();
+	let mut features = vec![
+		agent.gender() as f32 / 3.,
+		brain.hunger / 0.9,
+		brain.haste / 0.9,
+		brain.prudence / 0.9,
+		(brain.fear - 0.1) / 4.9,
+		(brain.rest - 0.2) / 0.8,
+		(brain.thrust - 0.2) / 0.8,
+		agent.segments().len() as f32 / 32.,
+		brain.hidden_units() as f32 / BRAIN_MAX_HIDDEN_UNITS as f32,
+	];
+	// the number of hidden units is in the genes, so the weights are summed up over them
+	// to compare brains of any size: how much each input drives the hidden layer,
+	// how strongly the hidden layer is wired, and how much it drives each output
+	let hidden = brain.hidden_units().max(1) as f32;
+	for input in 0..agent::N_INPUTS {
+		let sum = brain.weights_in.iter().map(|row| row.get(input).cloned().unwrap_or(0.)).sum::<f32>();
+		features.push(sum / hidden / 8. + 0.5);
+	}
+	let weights_hidden: Vec<f32> = brain.weights_hidden.iter().flat_map(|row| row.iter().cloned()).collect();
+	let n = weights_hidden.len().max(1) as f32;
+	features.push(weights_hidden.iter().sum::<f32>() / n / 8. + 0.5);
+	features.push((weights_hidden.iter().map(|w| w * w).sum::<f32>() / n).sqrt() / 8.);
+	for row in brain.weights_out.iter() {
+		features.push(row.iter().sum::<f32>() / hidden / 8. + 0.5);
+	}
+	features
+}
+
+/// Fraction of differing bits between two genomes
+pub fn hamming_distance(a: &Dna, b: &Dna) -> f32 {
+	let (short, long) = if a.len() < b.len() { (a, b) } else { (b, a) };
+	if long.is_empty() {
+		return 0.;
+	}
+	let common = short.iter()
+		.zip(long.iter())
+		.fold(0, |n, (x, y)| n + (x ^ y).count_ones() as usize);
+	// bits with no counterpart in the shorter genome all count as different
+	let extra = (long.len() - short.len()) * 8;
+	(common + extra) as f32 / (long.len() * 8) as f32
+}
+
+/// Genetic diversity of a population: the mean Hamming distance between the genomes of every pair,
+/// from 0 for clones to about 0.5 for unrelated random genomes. At most `max_sample` genomes, picked at random
+/// from the whole population rather than from its oldest members, are compared.
+pub fn diversity<R: rand::Rng>(minions: &agent::AgentMap, max_sample: usize, rng: &mut R) -> f32 {
+	let mut genomes: Vec<&Dna> = minions.iter().map(|(_, agent)| agent.dna()).collect();
+	let n = max_sample.min(genomes.len());
+	for i in 0..n {
+		let remaining = genomes.len() - i;
+		let j = i + ((rng.next_f32() * remaining as f32) as usize).min(remaining - 1);
+		genomes.swap(i, j);
+	}
+	genomes.truncate(n);
+	let mut sum = 0.;
+	let mut pairs = 0usize;
+	for (i, a) in genomes.iter().enumerate() {
+		for b in &genomes[i + 1..] {
+			sum += hamming_distance(a, b);
+			pairs += 1;
+		}
+	}
+	if pairs > 0 { sum / pairs as f32 } else { 0. }
+}
+
+fn feature_distance(a: &[f32], b: &[f32]) -> f32 {
+	let n = a.len().max(b.len());
+	if n == 0 {
+		return 0.;
+	}
+	let sum = a.iter().zip(b.iter()).fold(0., |s, (x, y)| s + (x - y) * (x - y));
+	(sum / n as f32).sqrt()
+}
+
+/// Groups minions into species. A minion joins the species whose representative is
+/// nearest within the threshold, founding a new species if none is near enough.
+/// Minions stay in their species for as long as they are within the threshold of it,
+/// and species ids are never reused, so ids are stable across reclassifications.
+#[derive(Clone)]
+pub struct Taxonomy {
+	metric: Metric,
+	threshold: f32,
+	seq: SpeciesId,
+	species: Vec<Species>,
+	members: BTreeMap<Id, SpeciesId>,
+}
+
+impl Taxonomy {
+	pub fn new(settings: Settings) -> Self {
+		Taxonomy {
+			metric: settings.metric,
+			threshold: settings.threshold,
+			seq: 0,
+			species: Vec::new(),
+			members: BTreeMap::new(),
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.seq = 0;
+		self.species.clear();
+		self.members.clear();
+	}
+
+	pub fn seq(&self) -> SpeciesId {
+		self.seq
+	}
+
+	pub fn metric(&self) -> Metric {
+		self.metric
+	}
+
+	pub fn species(&self) -> &[Species] {
+		&self.species
+	}
+
+	pub fn get(&self, id: SpeciesId) -> Option<&Species> {
+		self.species.iter().find(|s| s.id == id)
+	}
+
+	pub fn species_of(&self, id: Id) -> Option<SpeciesId> {
+		self.members.get(&id).cloned()
+	}
+
+	/// Living species and their population, by id
+	pub fn counts(&self) -> Vec<(SpeciesId, usize)> {
+		self.species.iter().map(|s| (s.id, s.population)).collect()
+	}
+
+	/// Species must be restored in id order, members are assigned at the next classification
+	pub fn restore(&mut self, seq: SpeciesId, species: Vec<Species>) {
+		self.clear();
+		self.seq = seq;
+		self.species = species;
+	}
+
+	/// Which species each minion belonged to at the last classification
+	pub fn members(&self) -> &BTreeMap<Id, SpeciesId> {
+		&self.members
+	}
+
+	/// Puts back the members of the restored species, and counts them again
+	pub fn restore_members(&mut self, members: BTreeMap<Id, SpeciesId>) {
+		for species in self.species.iter_mut() {
+			species.population = members.values().filter(|id| **id == species.id).count();
+		}
+		self.members = members;
+	}
+
+	fn distance(&self, species: &Species, dna: &Dna, features: &[f32]) -> f32 {
+		match self.metric {
+			Metric::Hamming => hamming_distance(&species.representative, dna),
+			Metric::Phenotype => feature_distance(&species.features, features),
+		}
+	}
+
+	fn nearest(&self, dna: &Dna, features: &[f32]) -> Option<usize> {
+		let mut best: Option<(usize, f32)> = None;
+		for (i, species) in self.species.iter().enumerate() {
+			let d = self.distance(species, dna, features);
+			if d <= self.threshold && best.map(|(_, b)| d < b).unwrap_or(true) {
+				best = Some((i, d));
+			}
+		}
+		best.map(|(i, _)| i)
+	}
+
+	pub fn classify(&mut self, minions: &agent::AgentMap, now: SecondsValue) {
+		let previous = mem::replace(&mut self.members, BTreeMap::new());
+		for species in self.species.iter_mut() {
+			species.population = 0;
+		}
+		for (id, agent) in minions.iter() {
+			let dna = agent.dna();
+			let features = match self.metric {
+				Metric::Phenotype => features_of(agent),
+				Metric::Hamming => Vec::new(),
+			};
+			let kept = previous.get(id)
+				.and_then(|species_id| self.species.iter().position(|s| s.id == *species_id))
+				.and_then(|i| if self.distance(&self.species[i], dna, &features) <= self.threshold { Some(i) } else { None });
+			let found = kept.or_else(|| self.nearest(dna, &features));
+			let index = match found {
+				Some(i) => i,
+				None => {
+					self.seq += 1;
+					self.species.push(Species::new(self.seq, dna.clone(), now, self.metric));
+					self.species.len() - 1
+				}
+			};
+			let species = &mut self.species[index];
+			species.population += 1;
+			self.members.insert(*id, species.id);
+		}
+		self.species.retain(|s| s.population > 0);
+	}
+}
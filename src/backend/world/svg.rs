@@ -0,0 +1,283 @@
+use std::io;
+use std::io::Write;
+use app::constants::*;
+use backend::obj::*;
+use backend::world;
+use backend::world::agent;
+use backend::world::agent::{Agent, AgentType};
+use backend::world::gen::Dna;
+use backend::world::phen;
+use backend::world::pool;
+use backend::world::topology::Topology;
+use core::color::Rgba;
+use core::geometry::{Position, Rect};
+
+const BACKGROUND: Rgba = [0.05, 0.05, 0.08, 1.];
+const FEEDER_COLOR: Rgba = [1., 0.9, 0.6, 1.];
+const EXTENT_COLOR: Rgba = [0.8, 0.8, 0.8, 1.];
+const LABEL_COLOR: Rgba = [0.4, 0.4, 0.4, 1.];
+/// Room around each organism of a contact sheet, and around a whole picture, in world units
+const MARGIN: f32 = 0.5;
+
+/// A shape in world coordinates
+enum Element {
+	Circle { center: Position, radius: f32, fill: Option<Rgba>, stroke: Option<Rgba> },
+	Polygon { points: Vec<Position>, fill: Option<Rgba>, stroke: Option<Rgba> },
+	Label { position: Position, size: f32, text: String },
+}
+
+impl Element {
+	fn bounds(&self) -> Rect {
+		match *self {
+			Element::Circle { center, radius, .. } =>
+				Rect::new(center.x - radius, center.y - radius, center.x + radius, center.y + radius),
+			Element::Polygon { ref points, .. } => points.iter().fold(
+				Rect::new(points[0].x, points[0].y, points[0].x, points[0].y),
+				|r, p| union(&r, &Rect::new(p.x, p.y, p.x, p.y))),
+			Element::Label { position, size, ref text } =>
+				Rect::new(position.x, position.y, position.x + 0.6 * size * text.len() as f32, position.y + size),
+		}
+	}
+
+	fn translate(&mut self, offset: Position) {
+		match *self {
+			Element::Circle { ref mut center, .. } => *center = *center + offset,
+			Element::Polygon { ref mut points, .. } => for p in points.iter_mut() {
+				*p = *p + offset;
+			},
+			Element::Label { ref mut position, .. } => *position = *position + offset,
+		}
+	}
+}
+
+fn union(a: &Rect, b: &Rect) -> Rect {
+	Rect::new(a.min.x.min(b.min.x), a.min.y.min(b.min.y), a.max.x.max(b.max.x), a.max.y.max(b.max.y))
+}
+
+fn hex(c: Rgba) -> String {
+	let channel = |v: f32| (v.max(0.).min(1.) * 255.).round() as u8;
+	format!("#{:02x}{:02x}{:02x}", channel(c[0]), channel(c[1]), channel(c[2]))
+}
+
+fn paint(fill: Option<Rgba>, stroke: Option<Rgba>) -> String {
+	let fill = match fill {
+		Some(c) => format!("fill=\"{}\" fill-opacity=\"{:.3}\"", hex(c), c[3].max(0.).min(1.)),
+		None => "fill=\"none\"".to_owned(),
+	};
+	match stroke {
+		Some(c) => format!("{} stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"",
+						   fill, hex(c), c[3].max(0.).min(1.)),
+		None => fill,
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// A picture in world coordinates, written as SVG with the y axis pointing up like on screen
+#[derive(Default)]
+pub struct Scene {
+	background: Option<Rgba>,
+	elements: Vec<Element>,
+}
+
+impl Scene {
+	pub fn new() -> Self {
+		Scene::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.elements.is_empty()
+	}
+
+	/// The smallest rectangle holding every element, if there is any
+	pub fn bounds(&self) -> Option<Rect> {
+		self.elements.iter().map(Element::bounds).fold(None, |acc, r| match acc {
+			Some(acc) => Some(union(&acc, &r)),
+			None => Some(r),
+		})
+	}
+
+	/// Draws every segment of the agent, the way `paint_minions` renders them:
+	/// the unit mesh is scaled by the growing radius and placed by the segment transform,
+	/// and filled with the color of the segment
+	pub fn agent(&mut self, agent: &Agent) {
+		for segment in agent.segments() {
+			let transform = segment.transform();
+			let radius = segment.growing_radius();
+			let mesh = segment.mesh();
+			let fill = Some(segment.color());
+			let outline = |vertices: &[Position]| -> Vec<Position> {
+				vertices.iter().map(|v| transform.apply(*v * radius)).collect()
+			};
+			self.elements.push(match mesh.shape {
+				Shape::Ball { .. } => Element::Circle { center: transform.position, radius, fill, stroke: None },
+				Shape::Box { ratio, .. } => Element::Polygon {
+					points: outline(&[
+						Position::new(-ratio, -1.),
+						Position::new(ratio, -1.),
+						Position::new(ratio, 1.),
+						Position::new(-ratio, 1.),
+					]),
+					fill,
+					stroke: None,
+				},
+				Shape::Triangle { .. } => Element::Polygon { points: outline(&mesh.vertices[0..3]), fill, stroke: None },
+				Shape::Star { .. } | Shape::Poly { .. } => Element::Polygon { points: outline(&mesh.vertices[..]), fill, stroke: None },
+			});
+		}
+	}
+
+	pub fn label(&mut self, position: Position, size: f32, text: &str) {
+		self.elements.push(Element::Label { position, size, text: text.to_owned() });
+	}
+
+	fn translate(&mut self, offset: Position) {
+		for element in self.elements.iter_mut() {
+			element.translate(offset);
+		}
+	}
+
+	/// Adds the elements of another scene, moved by `offset`
+	fn append(&mut self, mut other: Scene, offset: Position) {
+		other.translate(offset);
+		self.elements.extend(other.elements);
+	}
+
+	/// Writes the scene as a standalone SVG document, `width` pixels wide
+	pub fn write<W: Write>(&self, out: &mut W, width: u32) -> io::Result<()> {
+		let bounds = self.bounds().unwrap_or_default();
+		let (left, bottom) = (bounds.min.x - MARGIN, bounds.min.y - MARGIN);
+		let w = (bounds.max.x - bounds.min.x + 2. * MARGIN).max(MARGIN);
+		let h = (bounds.max.y - bounds.min.y + 2. * MARGIN).max(MARGIN);
+		let top = bottom + h;
+		let height = (width as f32 * h / w).ceil() as u32;
+		// y is flipped on output, so the view box starts from the top of the scene
+		writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+		writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{:.3} {:.3} {:.3} {:.3}\">",
+				 width, height, left, -top, w, h)?;
+		if let Some(c) = self.background {
+			writeln!(out, "<rect x=\"{:.3}\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\" {}/>", left, -top, w, h, paint(Some(c), None))?;
+		}
+		for element in &self.elements {
+			match *element {
+				Element::Circle { center, radius, fill, stroke } =>
+					writeln!(out, "<circle cx=\"{:.3}\" cy=\"{:.3}\" r=\"{:.3}\" {}/>", center.x, -center.y, radius, paint(fill, stroke))?,
+				Element::Polygon { ref points, fill, stroke } => {
+					let points: Vec<_> = points.iter().map(|p| format!("{:.3},{:.3}", p.x, -p.y)).collect();
+					writeln!(out, "<polygon points=\"{}\" {}/>", points.join(" "), paint(fill, stroke))?
+				}
+				Element::Label { position, size, ref text } =>
+					writeln!(out, "<text x=\"{:.3}\" y=\"{:.3}\" font-family=\"monospace\" font-size=\"{:.3}\" {}>{}</text>",
+							 position.x, -position.y, size, paint(Some(LABEL_COLOR), None), escape(text))?,
+			}
+		}
+		writeln!(out, "</svg>")
+	}
+}
+
+/// Develops a minion genome into a full grown body, with the tint it has in the simulation when charged
+pub fn develop_minion(dna: &Dna) -> Agent {
+	let mut agent = phen::develop_minion(dna, agent::InitialState {
+		maturity: Some(1.),
+		..Default::default()
+	});
+	for segment in agent.segments_mut() {
+		segment.state.set_charge(DEFAULT_MINION_CHARGE);
+	}
+	agent
+}
+
+/// A single organism, full grown
+pub fn organism(dna: &Dna) -> Scene {
+	let mut scene = Scene::new();
+	scene.agent(&develop_minion(dna));
+	scene
+}
+
+/// Every genome of a pool developed in a grid of equal cells, in the order of the pool,
+/// with its index and name or species underneath
+pub fn contact_sheet(records: &[pool::Record]) -> Scene {
+	let cells: Vec<_> = records.iter().map(|record| {
+		let mut scene = Scene::new();
+		scene.agent(&develop_minion(&record.dna));
+		scene
+	}).collect();
+	let size = cells.iter()
+		.filter_map(Scene::bounds)
+		.map(|r| (r.max.x - r.min.x).max(r.max.y - r.min.y))
+		.fold(0., f32::max) + 2. * MARGIN;
+	let label_size = size * 0.08;
+	let columns = (records.len() as f32).sqrt().ceil().max(1.) as usize;
+	let mut sheet = Scene::new();
+	for (i, (cell, record)) in cells.into_iter().zip(records.iter()).enumerate() {
+		let corner = Position::new((i % columns) as f32 * size, -((i / columns) as f32) * (size + 2. * label_size));
+		let center = corner + Position::new(size * 0.5, size * 0.5);
+		if let Some(bounds) = cell.bounds() {
+			sheet.append(cell, center - (bounds.min + bounds.max) * 0.5);
+		}
+		let caption = match (&record.name, record.species) {
+			(&Some(ref name), _) => format!("{} {}", i, name),
+			(&None, Some(species)) => format!("{} species {}", i, species),
+			(&None, None) => format!("{}", i),
+		};
+		sheet.label(corner + Position::new(0., -1.5 * label_size), label_size, &caption);
+	}
+	sheet
+}
+
+/// The whole world on its background: the border as the topology draws it, the feeders,
+/// sized by how much food they have left, and every agent
+pub fn world(world: &world::World) -> Scene {
+	let mut scene = Scene::new();
+	scene.background = Some(BACKGROUND);
+	let extent = &world.extent;
+	match world.topology() {
+		Topology::Circular => {
+			let (center, radius) = Topology::circle(extent);
+			scene.elements.push(Element::Circle { center, radius, fill: None, stroke: Some(EXTENT_COLOR) });
+		}
+		_ => scene.elements.push(Element::Polygon {
+			points: vec![extent.min, extent.top_left(), extent.max, extent.bottom_right()],
+			fill: None,
+			stroke: Some(EXTENT_COLOR),
+		}),
+	}
+	for feeder in world.feeders() {
+		scene.elements.push(Element::Circle {
+			center: feeder.transform().position,
+			radius: 0.5 + 0.5 * feeder.fullness(),
+			fill: Some([FEEDER_COLOR[0], FEEDER_COLOR[1], FEEDER_COLOR[2], 0.3]),
+			stroke: Some(FEEDER_COLOR),
+		});
+	}
+	for agent_type in AgentType::all() {
+		for (_, agent) in world.agents(*agent_type).iter() {
+			scene.agent(agent);
+		}
+	}
+	scene
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use backend::world::gen::GenePool;
+	use core::rng::SimulationRng;
+
+	#[test]
+	fn contact_sheet_draws_every_segment_of_every_genome() {
+		let mut rng = SimulationRng::with_seed(7);
+		let pool = GenePool::random(5, &mut rng);
+		let records: Vec<_> = pool.gene_pool_iter().map(|dna| pool::Record::new(dna.clone())).collect();
+		let segments: usize = records.iter().map(|r| develop_minion(&r.dna).segments().len()).sum();
+		let mut out = Vec::new();
+		contact_sheet(&records).write(&mut out, 800).unwrap();
+		let svg = String::from_utf8(out).unwrap();
+		let shapes = svg.matches("<polygon").count() + svg.matches("<circle").count();
+		assert_eq!(segments, shapes);
+		assert_eq!(records.len(), svg.matches("<text").count());
+		assert!(svg.trim_right().ends_with("</svg>"));
+	}
+}
@@ -1,6 +1,6 @@
 use backend::obj::*;
-use std::collections::HashMap;
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::BTreeMap;
 use core::clock::Timer;
 use backend::world::phen;
 use backend::world::agent;
@@ -22,7 +22,7 @@ impl Swarm {
 			seq: 0,
 			agent_type,
 			phenotype,
-			agents: HashMap::new(),
+			agents: agent::AgentMap::new(),
 		}
 	}
 
@@ -58,14 +58,11 @@ impl Swarm {
 	}
 
 	pub fn free_resources(&mut self, freed: &mut Vec<Agent>) {
-		let mut dead = HashSet::new();
-		for id in self.agents
+		let dead = self.agents
 			.iter()
 			.filter(|&(_, agent)| !agent.state.is_alive())
 			.map(|(&id, _)| id)
-			{
-				dead.insert(id);
-			}
+			.collect::<Vec<_>>();
 		for id in &dead {
 			if let Some(agent) = self.agents.remove(&id) {
 				freed.push(agent);
@@ -75,7 +72,7 @@ impl Swarm {
 
 	fn insert(&mut self, agent: Agent) -> Id {
 		let id = agent.id();
-		self.agents.insert(id, agent);
+		self.agents.insert(agent);
 		id
 	}
 
@@ -96,17 +93,21 @@ impl Swarm {
 	}
 
 	pub fn rebuild(&mut self, id: Id, genome: &mut Genome, initial_state: agent::InitialState, timer: &Timer) -> Id {
-		let entity = self.phenotype.develop(genome, id, initial_state, timer);
+		// never hand out again an id which is already in use
+		self.seq = cmp::max(self.seq, id >> 8);
+		let lineage = initial_state.lineage.clone();
+		let mut entity = self.phenotype.develop(genome, id, initial_state, timer);
+		entity.lineage = lineage;
 		self.insert(entity)
 	}
 
-	pub fn agents(&self) -> &HashMap<Id, Agent> {
+	pub fn agents(&self) -> &agent::AgentMap {
 		&self.agents
 	}
 
-	pub fn agents_mut(&mut self) -> &mut HashMap<Id, Agent> {
+	pub fn agents_mut(&mut self) -> &mut agent::AgentMap {
 		&mut self.agents
 	}
 }
 
-pub type SwarmMap = HashMap<AgentType, Swarm>;
+pub type SwarmMap = BTreeMap<AgentType, Swarm>;
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::str::FromStr;
+use cgmath::InnerSpace;
+use core::geometry::*;
+
+/// What happens at the edge of the world
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+	/// a solid box, and any creature which somehow gets out of it dies
+	Lethal,
+	/// a solid box, nothing dies at the border
+	Walled,
+	/// a solid circle inscribed in the extent
+	Circular,
+	/// no border at all: what leaves on one side comes back from the other
+	Torus,
+}
+
+impl Default for Topology {
+	fn default() -> Self {
+		Topology::Lethal
+	}
+}
+
+impl FromStr for Topology {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"lethal" => Ok(Topology::Lethal),
+			"walled" => Ok(Topology::Walled),
+			"circular" => Ok(Topology::Circular),
+			"torus" => Ok(Topology::Torus),
+			_ => Err(format!("Unknown topology: {} (expected lethal, walled, circular or torus)", s)),
+		}
+	}
+}
+
+impl fmt::Display for Topology {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Topology::Lethal => "lethal",
+			Topology::Walled => "walled",
+			Topology::Circular => "circular",
+			Topology::Torus => "torus",
+		})
+	}
+}
+
+fn inside(extent: &Rect, p: Position) -> bool {
+	p.x >= extent.min.x && p.x <= extent.max.x && p.y >= extent.min.y && p.y <= extent.max.y
+}
+
+/// Brings a coordinate back within `min..min + period`
+fn wrap_coordinate(x: f32, min: f32, period: f32) -> f32 {
+	if period > 0. {
+		let r = (x - min) % period;
+		min + if r < 0. { r + period } else { r }
+	} else {
+		x
+	}
+}
+
+/// Shortest offset along one axis, when `period` wraps around
+fn wrap_delta(d: f32, period: f32) -> f32 {
+	if period > 0. {
+		d - period * (d / period).round()
+	} else {
+		d
+	}
+}
+
+impl Topology {
+	pub fn wraps(self) -> bool {
+		self == Topology::Torus
+	}
+
+	/// Whether a creature at the given position has left the world for good
+	pub fn is_lethal(self, extent: &Rect, p: Position) -> bool {
+		self == Topology::Lethal && !inside(extent, p)
+	}
+
+	/// Center and radius of the boundary of a circular world
+	pub fn circle(extent: &Rect) -> (Position, f32) {
+		let center = (extent.min + extent.max) * 0.5;
+		let radius = (extent.max.x - extent.min.x).min(extent.max.y - extent.min.y) * 0.5;
+		(center, radius)
+	}
+
+	/// Shortest offset from `from` to `to`, going across the border if the world wraps
+	pub fn delta(self, extent: &Rect, from: Position, to: Position) -> Position {
+		let d = to - from;
+		if self.wraps() {
+			Position::new(wrap_delta(d.x, extent.max.x - extent.min.x), wrap_delta(d.y, extent.max.y - extent.min.y))
+		} else {
+			d
+		}
+	}
+
+	pub fn distance(self, extent: &Rect, from: Position, to: Position) -> f32 {
+		self.delta(extent, from, to).magnitude()
+	}
+
+	/// Where a position outside the world comes back in, if the world wraps
+	pub fn wrap(self, extent: &Rect, p: Position) -> Option<Position> {
+		if self.wraps() && !inside(extent, p) {
+			Some(Position::new(
+				wrap_coordinate(p.x, extent.min.x, extent.max.x - extent.min.x),
+				wrap_coordinate(p.y, extent.min.y, extent.max.y - extent.min.y),
+			))
+		} else {
+			None
+		}
+	}
+}
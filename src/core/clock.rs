@@ -160,6 +160,13 @@ impl TimerStopwatch {
 		let t0 = timer.seconds();
 		TimerStopwatch { t0 }
 	}
+
+	/// A stopwatch which was started at the given time
+	pub fn started_at(t0: Seconds) -> Self {
+		TimerStopwatch { t0 }
+	}
+
+	pub fn start(&self) -> Seconds { self.t0 }
 }
 
 impl Stopwatch for TimerStopwatch {
@@ -196,6 +203,21 @@ impl Hourglass {
 		}
 	}
 
+	/// An hourglass in the middle of its run, as saved in a snapshot
+	pub fn restore(start: Seconds, capacity: Seconds, timeout: Seconds) -> Self {
+		Hourglass {
+			stopwatch: TimerStopwatch::started_at(start),
+			capacity,
+			timeout,
+		}
+	}
+
+	pub fn start(&self) -> Seconds { self.stopwatch.start() }
+
+	pub fn capacity(&self) -> Seconds { self.capacity }
+
+	pub fn timeout(&self) -> Seconds { self.timeout }
+
 	pub fn renew<T>(&mut self, timer: &T) where T: Timer {
 		self.timeout = self.capacity;
 		self.stopwatch.reset(timer)
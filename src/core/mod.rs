@@ -5,4 +5,5 @@ pub mod resource;
 pub mod color;
 pub mod geometry;
 pub mod view;
+pub mod rng;
 
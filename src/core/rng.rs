@@ -0,0 +1,81 @@
+use rand;
+use rand::Rng;
+use rand::SeedableRng;
+
+pub type RngState = [u64; 2];
+
+/// Small, fast, seedable generator (xorshift128+) whose state can be saved and restored,
+/// so that a simulation run can be reproduced exactly from a seed or a snapshot
+#[derive(Clone, Debug)]
+pub struct SimulationRng {
+	state: RngState,
+}
+
+fn splitmix64(x: &mut u64) -> u64 {
+	*x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+	let mut z = *x;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^ (z >> 31)
+}
+
+impl SimulationRng {
+	pub fn with_seed(seed: u64) -> Self {
+		let mut s = seed;
+		let a = splitmix64(&mut s);
+		let b = splitmix64(&mut s);
+		// the all-zero state is a fixed point of xorshift
+		SimulationRng { state: if a | b == 0 { [1, 0] } else { [a, b] } }
+	}
+
+	pub fn from_state(state: RngState) -> Self {
+		if state[0] | state[1] == 0 {
+			Self::with_seed(0)
+		} else {
+			SimulationRng { state }
+		}
+	}
+
+	/// A non-deterministic seed, for runs which don't ask for a specific one
+	pub fn random_seed() -> u64 {
+		rand::thread_rng().gen::<u64>()
+	}
+
+	pub fn state(&self) -> RngState {
+		self.state
+	}
+
+	/// Derives an independent, reproducible stream from this one
+	pub fn fork(&mut self) -> Self {
+		let seed = self.next_u64();
+		Self::with_seed(seed)
+	}
+}
+
+impl Rng for SimulationRng {
+	#[inline]
+	fn next_u32(&mut self) -> u32 {
+		(self.next_u64() >> 32) as u32
+	}
+
+	#[inline]
+	fn next_u64(&mut self) -> u64 {
+		let mut s1 = self.state[0];
+		let s0 = self.state[1];
+		let result = s0.wrapping_add(s1);
+		self.state[0] = s0;
+		s1 ^= s1 << 23;
+		self.state[1] = s1 ^ s0 ^ (s1 >> 18) ^ (s0 >> 5);
+		result
+	}
+}
+
+impl SeedableRng<u64> for SimulationRng {
+	fn reseed(&mut self, seed: u64) {
+		*self = Self::with_seed(seed);
+	}
+
+	fn from_seed(seed: u64) -> Self {
+		Self::with_seed(seed)
+	}
+}
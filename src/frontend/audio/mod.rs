@@ -140,8 +140,13 @@ impl AlertPlayer<app::Event, self::Error> for SoundSystemAlertPlayer<ThreadedSou
 			&Event::PrevSpeedFactor |
 			&Event::Reload |
 			&Event::SaveGenePoolToFile |
+			&Event::SaveLineageToFile |
 			&Event::SaveWorldToFile |
 			&Event::DeselectAll |
+			&Event::ToggleSpecies |
+			&Event::TogglePheromones |
+			&Event::AddFeeder(_) |
+			&Event::RemoveFeeder(_) |
 			&Event::ToggleDebug => SoundEffect::UserOption,
 
 			&Event::PickMinion(_) => SoundEffect::SelectMinion,
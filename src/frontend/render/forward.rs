@@ -260,6 +260,8 @@ impl<R: gfx::Resources, C: gfx::CommandBuffer<R>, D> ForwardLighting<R, C, D>
 
 	pub fn setup(&self, encoder: &mut gfx::Encoder<R, C>, camera_projection: M44, camera_view: M44, lights: &Vec<PointLight>) -> Result<()> {
 		let mut lights_buf = lights.clone();
+		// feeders can be added at runtime, but the shaders only have room for so many lights
+		lights_buf.truncate(MAX_NUM_TOTAL_LIGHTS);
 
 		let count = lights_buf.len();
 		while lights_buf.len() < MAX_NUM_TOTAL_LIGHTS {
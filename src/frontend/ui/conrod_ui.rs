@@ -171,6 +171,7 @@ impl Screen {
 				txt_with_label(&mut ids_iter, &mut widgets, "FPS", &format!("{:.1}", frame_update.fps));
 				txt_with_label(&mut ids_iter, &mut widgets, "Population", &format!("{}", frame_update.simulation.population));
 				txt_with_label(&mut ids_iter, &mut widgets, "Extinctions", &format!("{}", frame_update.simulation.extinctions));
+				txt_with_label(&mut ids_iter, &mut widgets, "Species", &format!("{}", frame_update.simulation.species.len()));
 			}
 		};
 		widgets
@@ -7,7 +7,6 @@ mod backend;
 extern crate log;
 extern crate log4rs;
 extern crate chrono;
-extern crate csv;
 
 #[macro_use]
 extern crate bitflags;
@@ -19,6 +18,8 @@ extern crate serde_derive;
 
 extern crate serde;
 extern crate serde_json;
+extern crate rmp_serde;
+extern crate flate2;
 
 extern crate wrapped2d;
 